@@ -1,59 +1,220 @@
-use std::{ sync::{ Arc, Mutex, RwLock }, collections::VecDeque };
+use std::{ sync::{ Arc, Mutex, RwLock, atomic::{ AtomicUsize, AtomicU64, Ordering } }, collections::VecDeque, path::PathBuf, time::Duration };
 
 use chrono::Utc;
 use futures::future::join_all;
 use log::{ info, error, warn };
+use tokio::sync::Semaphore;
 
-use crate::{ MinecraftLauncherError, progress_reporter::ProgressReporter };
+use crate::progress_reporter::ProgressReporter;
 
-use super::Downloadable;
+use super::{ Downloadable, DownloadError };
 
 type DownloadableSync = Arc<dyn Downloadable + Send + Sync>;
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DownloadJobSummary {
+  pub queued: usize,
+  pub in_progress: usize,
+  pub completed: usize,
+  pub failed: usize,
+  pub bytes_downloaded: u64,
+}
+
+pub type DownloadStats = DownloadJobSummary;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadIntent {
+  pub url: String,
+  pub target_path: PathBuf,
+  pub estimated_size: Option<u64>,
+  pub would_skip: bool,
+}
+
+// Shared with the workers so the job can still be queried once it has been started
+#[derive(Debug, Clone, Default)]
+pub struct DownloadJobCounters {
+  queued: Arc<AtomicUsize>,
+  in_progress: Arc<AtomicUsize>,
+  completed: Arc<AtomicUsize>,
+  failed: Arc<AtomicUsize>,
+  bytes_downloaded: Arc<AtomicU64>,
+}
+
+impl DownloadJobCounters {
+  pub fn summary(&self) -> DownloadJobSummary {
+    DownloadJobSummary {
+      queued: self.queued.load(Ordering::Relaxed),
+      in_progress: self.in_progress.load(Ordering::Relaxed),
+      completed: self.completed.load(Ordering::Relaxed),
+      failed: self.failed.load(Ordering::Relaxed),
+      bytes_downloaded: self.bytes_downloaded.load(Ordering::Relaxed),
+    }
+  }
+}
+
 pub struct DownloadJob {
   name: String,
   all_files: Arc<RwLock<Vec<DownloadableSync>>>,
   remaining_files: Arc<Mutex<VecDeque<DownloadableSync>>>,
   failures: Arc<Mutex<Vec<DownloadableSync>>>,
   ignore_failures: bool,
+  max_failures: usize,
   max_pool_size: u16,
   max_download_attempts: u8,
+  concurrency_limit: Option<Arc<Semaphore>>,
+  download_timeout: Option<Duration>,
 
   progress_reporter: Arc<ProgressReporter>,
   downloadable_progress_reporter: Arc<ProgressReporter>,
+  counters: DownloadJobCounters,
 }
 
-impl DownloadJob {
-  pub fn new(name: &str, ignore_failures: bool, max_pool_size: u16, max_download_attempts: u8, progress_reporter: &Arc<ProgressReporter>) -> Self {
-    let progress_reporter = Arc::clone(progress_reporter);
+pub struct DownloadJobBuilder {
+  name: String,
+  ignore_failures: bool,
+  max_failures: usize,
+  max_pool_size: u16,
+  max_download_attempts: u8,
+  concurrency_limit: Option<Arc<Semaphore>>,
+  download_timeout: Option<Duration>,
+  progress_reporter: Arc<ProgressReporter>,
+}
+
+impl Default for DownloadJobBuilder {
+  fn default() -> Self {
+    Self {
+      name: String::new(),
+      ignore_failures: false,
+      max_failures: 0,
+      max_pool_size: 16,
+      max_download_attempts: 5,
+      concurrency_limit: None,
+      download_timeout: None,
+      progress_reporter: Arc::new(ProgressReporter::default()),
+    }
+  }
+}
+
+impl DownloadJobBuilder {
+  pub fn name(mut self, name: String) -> Self {
+    self.name = name;
+    self
+  }
+
+  pub fn ignore_failures(mut self, ignore_failures: bool) -> Self {
+    self.ignore_failures = ignore_failures;
+    self
+  }
+
+  // The job still succeeds if at most max_failures downloads fail, ignore_failures lets any amount fail
+  pub fn ignore_failures_threshold(mut self, max_failures: usize) -> Self {
+    self.max_failures = max_failures;
+    self
+  }
+
+  pub fn max_concurrent(mut self, max_concurrent: u16) -> Self {
+    self.max_pool_size = max_concurrent;
+    self
+  }
+
+  pub fn max_attempts(mut self, max_attempts: u8) -> Self {
+    self.max_download_attempts = max_attempts;
+    self
+  }
+
+  // Shares a download limit between several jobs running at the same time
+  pub fn concurrency_limit(mut self, concurrency_limit: Arc<Semaphore>) -> Self {
+    self.concurrency_limit = Some(concurrency_limit);
+    self
+  }
+
+  // Used for every download that doesn't set its own timeout (e.g. libraries without downloadTimeout)
+  pub fn download_timeout(mut self, download_timeout: Duration) -> Self {
+    self.download_timeout = Some(download_timeout);
+    self
+  }
+
+  pub fn progress_reporter(mut self, progress_reporter: Arc<ProgressReporter>) -> Self {
+    self.progress_reporter = progress_reporter;
+    self
+  }
+
+  pub fn build(self) -> DownloadJob {
+    let progress_reporter = self.progress_reporter;
     let all_files = Arc::new(RwLock::new(vec![]));
     let downloadable_progress_reporter = {
       let progress_reporter = Arc::clone(&progress_reporter);
       let all_files = Arc::clone(&all_files);
       Arc::new(
         ProgressReporter::new(move |_update| {
-          Self::update_progress(&all_files, &progress_reporter);
+          DownloadJob::update_progress(&all_files, &progress_reporter);
         })
       )
     };
 
-    Self {
-      name: name.to_string(),
+    DownloadJob {
+      name: self.name,
       all_files,
       remaining_files: Arc::new(Mutex::new(VecDeque::new())),
       failures: Arc::new(Mutex::new(vec![])),
-      ignore_failures,
-      max_pool_size,
-      max_download_attempts,
+      ignore_failures: self.ignore_failures,
+      max_failures: self.max_failures,
+      max_pool_size: self.max_pool_size,
+      max_download_attempts: self.max_download_attempts,
+      concurrency_limit: self.concurrency_limit,
+      download_timeout: self.download_timeout,
       progress_reporter,
       downloadable_progress_reporter,
+      counters: DownloadJobCounters::default(),
     }
   }
+}
+
+impl DownloadJob {
+  pub fn builder() -> DownloadJobBuilder {
+    DownloadJobBuilder::default()
+  }
+
+  #[deprecated(note = "use DownloadJob::builder() instead")]
+  pub fn new(name: &str, ignore_failures: bool, max_pool_size: u16, max_download_attempts: u8, progress_reporter: &Arc<ProgressReporter>) -> Self {
+    Self::builder()
+      .name(name.to_string())
+      .ignore_failures(ignore_failures)
+      .max_concurrent(max_pool_size)
+      .max_attempts(max_download_attempts)
+      .progress_reporter(Arc::clone(progress_reporter))
+      .build()
+  }
 
   // const MAXIMUM_POOL_SIZE: usize = 16;
 
-  pub async fn start(self) -> Result<(), Box<dyn std::error::Error>> {
+  pub fn summary(&self) -> DownloadJobSummary {
+    self.counters.summary()
+  }
+
+  pub fn counters(&self) -> DownloadJobCounters {
+    self.counters.clone()
+  }
+
+  // Lists the queued files without downloading anything
+  pub async fn dry_run(&self) -> Vec<DownloadIntent> {
+    let all_files = self.all_files.read().unwrap().clone();
+    let sizes = join_all(all_files.iter().map(|file| file.estimated_size())).await;
+    all_files
+      .iter()
+      .zip(sizes)
+      .map(|(file, estimated_size)| DownloadIntent {
+        url: file.url().clone(),
+        target_path: file.get_target_file().clone(),
+        estimated_size,
+        would_skip: !file.force_download() && file.get_target_file().is_file(),
+      })
+      .collect()
+  }
+
+  pub async fn start(self) -> Result<DownloadStats, DownloadError> {
     self.progress_reporter.clear();
+    self.report_estimated_size().await;
 
     let start_time = Utc::now();
     let mut futures = vec![];
@@ -61,6 +222,9 @@ impl DownloadJob {
       let job_name = self.name.clone();
       let remaining_files = Arc::clone(&self.remaining_files);
       let failures = Arc::clone(&self.failures);
+      let counters = self.counters.clone();
+      let concurrency_limit = self.concurrency_limit.clone();
+      let download_timeout = self.download_timeout;
       futures.push(
         tokio::spawn(async move {
           fn pop_downloadable(remaining_files: &Arc<Mutex<VecDeque<DownloadableSync>>>) -> Option<DownloadableSync> {
@@ -69,15 +233,15 @@ impl DownloadJob {
           }
 
           while let Some(downloadable) = pop_downloadable(&remaining_files) {
+            counters.queued.fetch_sub(1, Ordering::Relaxed);
             if downloadable.get_start_time() == None {
               downloadable.set_start_time(Utc::now().timestamp_millis() as u64);
             }
 
             if downloadable.get_attempts() > (self.max_download_attempts as usize) {
               error!("Gave up trying to download {} for job '{}'", downloadable.url(), job_name);
-              if !self.ignore_failures {
-                failures.lock().unwrap().push(downloadable);
-              }
+              counters.failed.fetch_add(1, Ordering::Relaxed);
+              failures.lock().unwrap().push(downloadable);
             } else {
               info!(
                 "Attempting to download {} for job '{}'... (try {})",
@@ -86,19 +250,39 @@ impl DownloadJob {
                 downloadable.get_attempts()
               );
 
+              let permit = match &concurrency_limit {
+                Some(concurrency_limit) => Some(Arc::clone(concurrency_limit).acquire_owned().await.unwrap()),
+                None => None,
+              };
               let mut should_add_back = false;
-              if let Err(err) = downloadable.download().await {
+              counters.in_progress.fetch_add(1, Ordering::Relaxed);
+              let result = match download_timeout.filter(|_| downloadable.get_timeout().is_none()) {
+                Some(download_timeout) =>
+                  match tokio::time::timeout(download_timeout, downloadable.download()).await {
+                    Ok(result) => result.map_err(|err| err.to_string()),
+                    Err(_) => Err(DownloadError::Timeout { url: downloadable.url().clone() }.to_string()),
+                  }
+                None => downloadable.download().await.map_err(|err| err.to_string()),
+              };
+              if let Err(err) = result {
                 warn!("Couldn't download {} for job '{}': {}", downloadable.url(), job_name, err);
                 should_add_back = true;
               } else {
                 info!("Finished downloading {} for job '{}'", downloadable.get_target_file().display(), job_name);
                 downloadable.set_end_time(Utc::now().timestamp_millis() as u64);
+                counters.completed.fetch_add(1, Ordering::Relaxed);
+                if let Ok(metadata) = tokio::fs::metadata(downloadable.get_target_file()).await {
+                  counters.bytes_downloaded.fetch_add(metadata.len(), Ordering::Relaxed);
+                }
               }
+              counters.in_progress.fetch_sub(1, Ordering::Relaxed);
+              drop(permit);
 
               let monitor = downloadable.get_monitor();
               monitor.set_current(monitor.get_total());
 
               if should_add_back {
+                counters.queued.fetch_add(1, Ordering::Relaxed);
                 remaining_files.lock().unwrap().push_back(downloadable);
               }
             }
@@ -109,18 +293,31 @@ impl DownloadJob {
 
     join_all(futures).await;
     let total_time = Utc::now().signed_duration_since(start_time).num_seconds();
-    let failures = self.failures.lock().unwrap();
-    if !failures.is_empty() {
-      Err(MinecraftLauncherError(format!("Job '{}' finished with {} failure(s)! (took {}s)", self.name, failures.len(), total_time)))?;
+    let failure_count = self.failures.lock().unwrap().len();
+    if !self.ignore_failures && failure_count > self.max_failures {
+      error!("Job '{}' finished with {} failure(s)! (took {}s)", self.name, failure_count, total_time);
+      return Err(DownloadError::TooManyFailures { count: failure_count });
+    } else if failure_count > 0 {
+      warn!("Job '{}' finished with {} ignored failure(s) (took {}s)", self.name, failure_count, total_time);
     } else {
       info!("Job '{}' finished successfully (took {}s)", self.name, total_time);
     }
 
     self.progress_reporter.clear();
-    Ok(())
+    Ok(self.summary())
   }
 
   pub fn add_downloadables(&mut self, downloadables: Vec<Box<dyn Downloadable + Send + Sync>>) {
+    self.add_downloadables_from_iterator(downloadables);
+  }
+
+  pub fn add_downloadable_if(&mut self, condition: bool, downloadable: Box<dyn Downloadable + Send + Sync>) {
+    if condition {
+      self.add_downloadables_from_iterator([downloadable]);
+    }
+  }
+
+  pub fn add_downloadables_from_iterator(&mut self, downloadables: impl IntoIterator<Item = Box<dyn Downloadable + Send + Sync>>) {
     let mut all_files = self.all_files.write().unwrap();
     let mut remaining_files = self.remaining_files.lock().unwrap();
     for downloadable in downloadables {
@@ -128,7 +325,32 @@ impl DownloadJob {
       let downloadable_arc = Arc::from(downloadable);
       remaining_files.push_back(Arc::clone(&downloadable_arc));
       all_files.push(downloadable_arc);
+      self.counters.queued.fetch_add(1, Ordering::Relaxed);
+    }
+  }
+
+  async fn report_estimated_size(&self) {
+    let all_files = self.all_files.read().unwrap().clone();
+    let sizes = join_all(
+      all_files.iter().map(|file| async {
+        match file.get_timeout().or(self.download_timeout) {
+          Some(timeout) => tokio::time::timeout(timeout, file.estimated_size()).await.ok().flatten(),
+          None => file.estimated_size().await,
+        }
+      })
+    ).await;
+
+    let mut total_bytes = 0u64;
+    for (file, size) in all_files.iter().zip(sizes) {
+      if let Some(size) = size {
+        file.get_monitor().set_total(size as usize);
+        total_bytes += size;
+      }
     }
+
+    info!("Job '{}' expects to download {} bytes", self.name, total_bytes);
+    // Progress is reported as a percentage, see update_progress
+    self.progress_reporter.set_total(100);
   }
 
   fn update_progress(all_files: &RwLock<Vec<DownloadableSync>>, progress_reporter: &ProgressReporter) {
@@ -141,10 +363,14 @@ impl DownloadJob {
 
       let mut current_size = 0;
       let mut total_size = 0;
+      let mut finished_files = 0;
       let mut last_file: Option<&DownloadableSync> = None;
       for file in all_files {
         current_size += file.get_monitor().get_current();
         total_size += file.get_monitor().get_total();
+        if file.get_end_time().is_some() {
+          finished_files += 1;
+        }
 
         if let Some(last_file) = last_file {
           if last_file.get_end_time().is_none() && (file.get_start_time() >= last_file.get_start_time() || file.get_end_time().is_some()) {
@@ -155,8 +381,73 @@ impl DownloadJob {
       }
 
       let status = last_file.map(|file| file.get_status()).unwrap_or_default();
-      let scaled_current = (((current_size as f64) / (total_size as f64)) * 100.0).ceil();
+      // Bytes make large files weigh more, file count is only used when no sizes are known
+      let fraction = if total_size > 0 {
+        (current_size as f64) / (total_size as f64)
+      } else {
+        (finished_files as f64) / (all_files.len() as f64)
+      };
+      let scaled_current = (fraction * 100.0).ceil().min(100.0);
       progress_reporter.set(status, scaled_current as u32, 100);
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use reqwest::Client;
+  use tokio::net::TcpListener;
+
+  use crate::download_utils::ChecksummedDownloadable;
+
+  use super::*;
+
+  #[tokio::test]
+  async fn test_download_timeout() {
+    // Accepts connections but never answers them
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/file", listener.local_addr().unwrap());
+    let target = std::env::temp_dir().join("mclc-test-download-timeout");
+
+    // A downloadable's own timeout takes precedence over the job's
+    for (download_timeout, own_timeout) in [(Duration::from_millis(200), None), (Duration::from_secs(3600), Some(Duration::from_millis(200)))] {
+      let mut job = DownloadJob::builder()
+        .name("Timeout".to_string())
+        .download_timeout(download_timeout)
+        .max_attempts(0)
+        .ignore_failures(true)
+        .build();
+      job.add_downloadables(vec![Box::new(ChecksummedDownloadable::new(Client::new(), &url, &target, true).with_timeout(own_timeout))]);
+      let stats = tokio::time::timeout(Duration::from_secs(10), job.start()).await.unwrap().unwrap();
+      assert_eq!(stats.failed, 1);
+    }
+    drop(listener);
+  }
+
+  // Nothing listens on port 1, every download fails right away
+  fn failing_job(failures: usize, max_failures: usize, ignore_failures: bool) -> DownloadJob {
+    let mut job = DownloadJob::builder()
+      .name("Failures".to_string())
+      .ignore_failures(ignore_failures)
+      .ignore_failures_threshold(max_failures)
+      .max_attempts(0)
+      .build();
+    for i in 0..failures {
+      let target = std::env::temp_dir().join(format!("mclc-test-failures-{i}"));
+      job.add_downloadables(vec![Box::new(ChecksummedDownloadable::new(Client::new(), "http://127.0.0.1:1/file", &target, true))]);
+    }
+    job
+  }
+
+  #[tokio::test]
+  async fn test_ignore_failures_threshold() {
+    let stats = failing_job(2, 2, false).start().await.unwrap();
+    assert_eq!(stats.failed, 2);
+
+    let err = failing_job(3, 2, false).start().await.unwrap_err();
+    assert!(matches!(err, DownloadError::TooManyFailures { count: 3 }));
+
+    let stats = failing_job(3, 0, true).start().await.unwrap();
+    assert_eq!(stats.failed, 3);
+  }
+}
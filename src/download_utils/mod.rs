@@ -2,9 +2,10 @@ pub mod download_job;
 
 use std::{
   ffi::OsStr,
+  fmt::Debug,
   fs::{ self, create_dir_all, File },
   io::{ Cursor, Read },
-  path::{ PathBuf, MAIN_SEPARATOR_STR },
+  path::{ Path, PathBuf },
   sync::{ Arc, Mutex },
   time::Duration,
 };
@@ -12,32 +13,137 @@ use std::{
 use async_trait::async_trait;
 use libflate::non_blocking::gzip;
 use log::{ info, warn };
-use reqwest::{ header::{ HeaderMap, HeaderValue }, Client, Proxy, Url };
+use reqwest::{ header::{ HeaderMap, HeaderValue, CONTENT_LENGTH }, Client, Proxy, Url };
+use thiserror::Error;
+use tokio::sync::OnceCell;
+
+use crate::{ versions::json::{ Sha1Sum, AssetObject, artifact::join_path_string }, MinecraftLauncherError, progress_reporter::ProgressReporter };
+
+#[derive(Error, Debug)]
+pub enum DownloadError {
+  #[error("{0}")]
+  Io(#[from] std::io::Error),
+  #[error("{0}")]
+  Http(#[from] reqwest::Error),
+  #[error("Checksum did not match downloaded file from {url} (Checksum was {expected}, downloaded {got})")]
+  ChecksumMismatch {
+    url: String,
+    expected: Sha1Sum,
+    got: Sha1Sum,
+  },
+  #[error("Timed out while downloading {url}")]
+  Timeout {
+    url: String,
+  },
+  #[error("{count} file(s) failed to download")]
+  TooManyFailures {
+    count: usize,
+  },
+  #[error("Response from {url} is too large (received {received} bytes, the limit is {max})")]
+  ResponseTooLarge {
+    url: String,
+    received: u64,
+    max: u64,
+  },
+}
+
+impl DownloadError {
+  fn from_request(url: &str, err: reqwest::Error) -> Self {
+    if err.is_timeout() {
+      Self::Timeout { url: url.to_string() }
+    } else {
+      Self::Http(err)
+    }
+  }
+}
+
+// Reads the whole body, giving up as soon as it grows past max_size
+pub(crate) async fn read_body(url: &str, mut res: reqwest::Response, max_size: Option<u64>) -> Result<Vec<u8>, DownloadError> {
+  let Some(max) = max_size else {
+    return Ok(res.bytes().await?.to_vec());
+  };
+  if let Some(received) = res.content_length().filter(|len| *len > max) {
+    return Err(DownloadError::ResponseTooLarge { url: url.to_string(), received, max });
+  }
+  let mut body = vec![];
+  while let Some(chunk) = res.chunk().await? {
+    body.extend_from_slice(&chunk);
+    if body.len() as u64 > max {
+      return Err(DownloadError::ResponseTooLarge { url: url.to_string(), received: body.len() as u64, max });
+    }
+  }
+  Ok(body)
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpClientConfig {
+  pub connect_timeout: Duration,
+  pub read_timeout: Duration, // reqwest can't time out individual reads, so this bounds the whole request unless total_timeout is set
+  pub total_timeout: Option<Duration>,
+}
 
-use crate::{ versions::json::{ Sha1Sum, AssetObject }, MinecraftLauncherError, progress_reporter::ProgressReporter };
+impl Default for HttpClientConfig {
+  fn default() -> Self {
+    Self {
+      connect_timeout: Duration::from_secs(10),
+      read_timeout: Duration::from_secs(30),
+      total_timeout: None,
+    }
+  }
+}
 
-#[derive(Debug, Clone, Default)]
+#[derive(Clone, Default)]
 pub enum ProxyOptions {
   #[default] NoProxy,
   Proxy(reqwest::Url),
+  AuthenticatedProxy {
+    url: reqwest::Url,
+    username: String,
+    password: String,
+  },
+}
+
+impl Debug for ProxyOptions {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::NoProxy => write!(f, "NoProxy"),
+      Self::Proxy(url) => f.debug_tuple("Proxy").field(url).finish(),
+      Self::AuthenticatedProxy { url, username, .. } =>
+        f.debug_struct("AuthenticatedProxy").field("url", url).field("username", username).field("password", &"?????").finish(),
+    }
+  }
 }
 
 impl ProxyOptions {
   fn client_builder(&self) -> reqwest::ClientBuilder {
     let mut builder = Client::builder();
-    if let ProxyOptions::Proxy(url) = self {
-      builder = builder.proxy(Proxy::all(url.as_str()).unwrap());
+    match self {
+      ProxyOptions::NoProxy => {}
+      ProxyOptions::Proxy(url) => {
+        builder = builder.proxy(Proxy::all(url.as_str()).unwrap());
+      }
+      ProxyOptions::AuthenticatedProxy { url, username, password } => {
+        builder = builder.proxy(Proxy::all(url.as_str()).unwrap().basic_auth(username, password));
+      }
     }
     builder
   }
 
   pub fn create_http_client(&self) -> Client {
+    self.create_http_client_with_config(&HttpClientConfig::default())
+  }
+
+  pub fn create_http_client_with_config(&self, config: &HttpClientConfig) -> Client {
     let mut headers = HeaderMap::new();
     headers.append("Cache-Control", HeaderValue::from_static("no-store,max-age=0,no-cache"));
     headers.append("Expires", HeaderValue::from_static("0"));
     headers.append("Pragma", HeaderValue::from_static("no-cache"));
 
-    let builder = self.client_builder().default_headers(headers).timeout(Duration::from_secs(15));
+    let builder = self
+      .client_builder()
+      .default_headers(headers)
+      .connect_timeout(config.connect_timeout)
+      .timeout(config.total_timeout.unwrap_or(config.read_timeout));
     builder.build().unwrap_or(Client::new())
   }
 }
@@ -49,6 +155,7 @@ pub trait Downloadable {
   fn get_target_file(&self) -> &PathBuf;
   fn force_download(&self) -> bool;
   fn get_attempts(&self) -> usize;
+  fn get_timeout(&self) -> Option<Duration>;
 
   fn get_status(&self) -> String;
   fn get_monitor(&self) -> &Arc<DownloadableMonitor>;
@@ -60,7 +167,15 @@ pub trait Downloadable {
 
   async fn make_connection(&self, url: &str) -> reqwest::Result<reqwest::Response> {
     // TODO: CHANGE and handle for each downloadable
-    self.get_http_client().get(url).send().await?.error_for_status()
+    let mut request = self.get_http_client().get(url);
+    if let Some(timeout) = self.get_timeout() {
+      request = request.timeout(timeout);
+    }
+    request.send().await?.error_for_status()
+  }
+
+  async fn estimated_size(&self) -> Option<u64> {
+    None
   }
 
   fn ensure_file_writable(&self, file: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
@@ -87,7 +202,10 @@ pub struct ChecksummedDownloadable {
   pub attempts: Arc<Mutex<usize>>,
   pub start_time: Arc<Mutex<Option<u64>>>,
   pub end_time: Arc<Mutex<Option<u64>>>,
+  pub timeout: Option<Duration>,
 
+  pub size_hint: OnceCell<Option<u64>>,
+  pub max_size: Option<u64>,
   pub monitor: Arc<DownloadableMonitor>,
 }
 
@@ -101,17 +219,37 @@ impl ChecksummedDownloadable {
       attempts: Arc::new(Mutex::new(0)),
       start_time: Arc::new(Mutex::new(None)),
       end_time: Arc::new(Mutex::new(None)),
+      timeout: None,
 
+      size_hint: OnceCell::new(),
+      max_size: None,
       monitor: Arc::new(DownloadableMonitor::new(0, 5242880)),
     }
   }
 
+  pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+    self.timeout = timeout;
+    self
+  }
+
+  // Responses bigger than this fail with DownloadError::ResponseTooLarge
+  pub fn with_max_size(mut self, bytes: u64) -> Self {
+    self.max_size = Some(bytes);
+    self
+  }
+
   const NULL_SHA1: [u8; 20] = [0; 20];
 
   async fn get_remote_hash(&self) -> Result<Sha1Sum, Box<dyn std::error::Error>> {
     let res = self.make_connection(&format!("{}.sha1", self.url)).await?;
     Ok(Sha1Sum::try_from(res.text().await?)?)
   }
+
+  async fn get_remote_size(&self) -> Option<u64> {
+    // Response::content_length() reports the (empty) body size for HEAD requests, so read the header instead
+    let res = self.http_client.head(&self.url).send().await.ok()?.error_for_status().ok()?;
+    res.headers().get(CONTENT_LENGTH)?.to_str().ok()?.parse().ok()
+  }
 }
 
 #[async_trait]
@@ -136,6 +274,10 @@ impl Downloadable for ChecksummedDownloadable {
     *self.attempts.lock().unwrap()
   }
 
+  fn get_timeout(&self) -> Option<Duration> {
+    self.timeout
+  }
+
   fn get_status(&self) -> String {
     let file_name = self.get_target_file().file_name().and_then(OsStr::to_str).unwrap_or(self.url());
     format!("Downloading {}", file_name)
@@ -161,6 +303,10 @@ impl Downloadable for ChecksummedDownloadable {
     *self.end_time.lock().unwrap() = Some(end_time);
   }
 
+  async fn estimated_size(&self) -> Option<u64> {
+    *self.size_hint.get_or_init(|| self.get_remote_size()).await
+  }
+
   async fn download(&self) -> Result<(), Box<dyn std::error::Error + 'life0>> {
     *self.attempts.lock()? += 1;
 
@@ -171,26 +317,26 @@ impl Downloadable for ChecksummedDownloadable {
     let target_file = self.get_target_file();
 
     // Try to get hash from local file
-    if local_hash.is_none() && target_file.is_file() {
-      local_hash = Some(Sha1Sum::from_reader(&mut File::open(target_file)?)?);
+    if !self.force_download && local_hash.is_none() && target_file.is_file() {
+      local_hash = Some(Sha1Sum::from_file(target_file)?);
     }
 
     if expected_hash.is_none() {
       expected_hash = Some(self.get_remote_hash().await.unwrap_or(Sha1Sum::new(Self::NULL_SHA1)));
     }
 
-    if expected_hash.as_ref().unwrap() == &Sha1Sum::new(Self::NULL_SHA1) && target_file.is_file() {
+    if !self.force_download && expected_hash.as_ref().unwrap() == &Sha1Sum::new(Self::NULL_SHA1) && target_file.is_file() {
       info!("Couldn't find a checksum so assuming our copy is good");
       return Ok(());
     } else if expected_hash == local_hash {
       info!("Remote checksum matches local file");
       return Ok(());
     } else {
-      let res = self.make_connection(&self.url).await?;
+      let res = self.make_connection(&self.url).await.map_err(|err| DownloadError::from_request(&self.url, err))?;
       if let Some(content_len) = res.content_length() {
         self.monitor.set_total(content_len as usize);
       }
-      let bytes = res.bytes().await?;
+      let bytes = read_body(&self.url, res, self.max_size).await?;
       local_hash = Some(Sha1Sum::from_reader(&mut Cursor::new(&bytes))?);
       fs::write(&target_file, &bytes)?;
       if expected_hash.as_ref().unwrap() == &Sha1Sum::new(Self::NULL_SHA1) {
@@ -200,14 +346,7 @@ impl Downloadable for ChecksummedDownloadable {
         info!("Downloaded successfully and checksum matched");
         return Ok(());
       } else {
-        Err(
-          Box::new(
-            std::io::Error::new(
-              std::io::ErrorKind::Other,
-              format!("Checksum did not match downloaded file (Checksum was {}, downloaded {})", expected_hash.unwrap(), local_hash.unwrap())
-            )
-          )
-        )?;
+        Err(DownloadError::ChecksumMismatch { url: self.url.clone(), expected: expected_hash.unwrap(), got: local_hash.unwrap() })?;
       }
     }
     Ok(())
@@ -222,8 +361,12 @@ pub struct PreHashedDownloadable {
   pub attempts: Arc<Mutex<usize>>,
   pub start_time: Arc<Mutex<Option<u64>>>,
   pub end_time: Arc<Mutex<Option<u64>>>,
+  pub timeout: Option<Duration>,
 
   pub expected_hash: Sha1Sum,
+  pub expected_size: Option<u64>,
+  pub fallback_url: Option<String>,
+  pub max_size: Option<u64>,
   pub monitor: Arc<DownloadableMonitor>,
 }
 
@@ -237,11 +380,36 @@ impl PreHashedDownloadable {
       attempts: Arc::new(Mutex::new(0)),
       start_time: Arc::new(Mutex::new(None)),
       end_time: Arc::new(Mutex::new(None)),
+      timeout: None,
 
       expected_hash,
+      expected_size: None,
+      fallback_url: None,
+      max_size: None,
       monitor: Arc::new(DownloadableMonitor::new(0, 5242880)),
     }
   }
+
+  pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+    self.timeout = timeout;
+    self
+  }
+
+  pub fn with_expected_size(mut self, expected_size: u64) -> Self {
+    self.expected_size = Some(expected_size);
+    self
+  }
+
+  // Mirror tried when the primary url returns a non-2xx status or fails to connect
+  pub fn with_fallback_url(mut self, fallback: &str) -> Self {
+    self.fallback_url = Some(fallback.to_string());
+    self
+  }
+
+  pub fn with_max_size(mut self, bytes: u64) -> Self {
+    self.max_size = Some(bytes);
+    self
+  }
 }
 
 #[async_trait]
@@ -266,6 +434,10 @@ impl Downloadable for PreHashedDownloadable {
     *self.attempts.lock().unwrap()
   }
 
+  fn get_timeout(&self) -> Option<Duration> {
+    self.timeout
+  }
+
   fn get_status(&self) -> String {
     let file_name = self.get_target_file().file_name().and_then(OsStr::to_str).unwrap_or(self.url());
     format!("Downloading {}", file_name)
@@ -291,38 +463,43 @@ impl Downloadable for PreHashedDownloadable {
     *self.end_time.lock().unwrap() = Some(end_time);
   }
 
+  async fn estimated_size(&self) -> Option<u64> {
+    self.expected_size
+  }
+
   async fn download(&self) -> Result<(), Box<dyn std::error::Error + 'life0>> {
     *self.attempts.lock()? += 1;
     self.ensure_file_writable(&self.target_file)?;
     let target = self.get_target_file();
     if target.is_file() {
-      let local_hash = Sha1Sum::from_reader(&mut File::open(target)?)?;
-      if local_hash == self.expected_hash {
+      let local_hash = Sha1Sum::from_file(target)?;
+      if !self.force_download && local_hash == self.expected_hash {
         info!("Local file matches hash, using it");
         return Ok(());
       }
       fs::remove_file(target)?;
     }
 
-    let res = self.make_connection(&self.url).await?;
+    let (url, res) = match (self.make_connection(&self.url).await, &self.fallback_url) {
+      (Ok(res), _) => (&self.url, res),
+      (Err(err), Some(fallback_url)) => {
+        warn!("Failed to download {}, trying fallback {}: {}", self.url, fallback_url, err);
+        let res = self.make_connection(fallback_url).await.map_err(|err| DownloadError::from_request(fallback_url, err))?;
+        (fallback_url, res)
+      }
+      (Err(err), None) => Err(DownloadError::from_request(&self.url, err))?,
+    };
     if let Some(content_len) = res.content_length() {
       self.monitor.set_total(content_len as usize);
     }
-    let bytes = res.bytes().await?;
+    let bytes = read_body(url, res, self.max_size).await?;
     let local_hash = Sha1Sum::from_reader(&mut Cursor::new(&bytes))?;
     fs::write(&target, &bytes)?;
     if local_hash == self.expected_hash {
       info!("Downloaded successfully and checksum matched");
       return Ok(());
     } else {
-      Err(
-        Box::new(
-          std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("Checksum did not match downloaded file (Checksum was {}, downloaded {})", self.expected_hash, local_hash)
-          )
-        )
-      )?;
+      Err(DownloadError::ChecksumMismatch { url: url.clone(), expected: self.expected_hash.clone(), got: local_hash })?;
     }
 
     Ok(())
@@ -337,6 +514,7 @@ pub struct EtagDownloadable {
   pub attempts: Arc<Mutex<usize>>,
   pub start_time: Arc<Mutex<Option<u64>>>,
   pub end_time: Arc<Mutex<Option<u64>>>,
+  pub timeout: Option<Duration>,
 
   pub monitor: Arc<DownloadableMonitor>,
 }
@@ -351,6 +529,7 @@ impl EtagDownloadable {
       attempts: Arc::new(Mutex::new(0)),
       start_time: Arc::new(Mutex::new(None)),
       end_time: Arc::new(Mutex::new(None)),
+      timeout: None,
 
       monitor: Arc::new(DownloadableMonitor::new(0, 5242880)),
     }
@@ -392,6 +571,10 @@ impl Downloadable for EtagDownloadable {
     *self.attempts.lock().unwrap()
   }
 
+  fn get_timeout(&self) -> Option<Duration> {
+    self.timeout
+  }
+
   fn get_status(&self) -> String {
     let file_name = self.get_target_file().file_name().and_then(OsStr::to_str).unwrap_or(self.url());
     format!("Downloading {}", file_name)
@@ -422,7 +605,7 @@ impl Downloadable for EtagDownloadable {
     self.ensure_file_writable(&self.target_file)?;
 
     let target = &self.target_file;
-    let res = self.make_connection(&self.url).await?;
+    let res = self.make_connection(&self.url).await.map_err(|err| DownloadError::from_request(&self.url, err))?;
     if let Some(content_len) = res.content_length() {
       self.monitor.set_total(content_len as usize);
     }
@@ -458,6 +641,7 @@ pub struct AssetDownloadable {
   pub attempts: Arc<Mutex<usize>>,
   pub start_time: Arc<Mutex<Option<u64>>>,
   pub end_time: Arc<Mutex<Option<u64>>>,
+  pub timeout: Option<Duration>,
 
   pub name: String,
   pub status: Mutex<AssetDownloadableStatus>,
@@ -468,26 +652,26 @@ pub struct AssetDownloadable {
 }
 
 impl AssetDownloadable {
-  pub fn new(http_client: Client, name: &str, asset: &AssetObject, url_base: &str, objects_dir: &PathBuf) -> Self {
+  pub fn new(http_client: Client, name: &str, asset: &AssetObject, url_base: &str, objects_dir: &Path, force_download: bool) -> Self {
     let path = AssetObject::create_path_from_hash(&asset.hash);
-    let mut url = Url::parse(url_base).unwrap();
-    url.set_path(&path);
-    let url = url.to_string();
-    let target_file = objects_dir.join(path.replace("/", MAIN_SEPARATOR_STR));
+    // Joined rather than replacing the path, so CDNs below a subdirectory work
+    let url = Url::parse(url_base).unwrap().join(&path).unwrap().to_string();
+    let target_file = join_path_string(objects_dir, &path);
     Self {
       http_client,
       url,
       target_file,
-      force_download: false,
+      force_download,
       attempts: Arc::new(Mutex::new(0)),
       start_time: Arc::new(Mutex::new(None)),
       end_time: Arc::new(Mutex::new(None)),
+      timeout: None,
 
       name: name.to_string(),
       status: Mutex::new(AssetDownloadableStatus::Downloading),
       asset: asset.clone(),
       url_base: url_base.to_string(),
-      destination: objects_dir.clone(),
+      destination: objects_dir.to_path_buf(),
       monitor: Arc::new(DownloadableMonitor::new(0, 5242880)),
     }
   }
@@ -539,6 +723,10 @@ impl Downloadable for AssetDownloadable {
     *self.attempts.lock().unwrap()
   }
 
+  fn get_timeout(&self) -> Option<Duration> {
+    self.timeout
+  }
+
   fn get_status(&self) -> String {
     format!("{} {}", self.status.lock().unwrap().as_str(), self.name)
   }
@@ -563,6 +751,10 @@ impl Downloadable for AssetDownloadable {
     *self.end_time.lock().unwrap() = Some(end_time);
   }
 
+  async fn estimated_size(&self) -> Option<u64> {
+    Some(self.asset.size)
+  }
+
   async fn download(&self) -> Result<(), Box<dyn std::error::Error + 'life0>> {
     *self.attempts.lock()? += 1;
     if let Ok(mut status) = self.status.lock() {
@@ -577,8 +769,7 @@ impl Downloadable for AssetDownloadable {
     };
     let url = self.url();
     let compressed_url = if self.asset.has_compressed_alternative() {
-      let mut url = Url::parse(&self.url_base)?;
-      url.set_path(&AssetObject::create_path_from_hash(self.asset.compressed_hash.as_ref().unwrap()));
+      let url = Url::parse(&self.url_base)?.join(&AssetObject::create_path_from_hash(self.asset.compressed_hash.as_ref().unwrap()))?;
       Some(url.to_string())
     } else {
       None
@@ -588,6 +779,15 @@ impl Downloadable for AssetDownloadable {
       self.ensure_file_writable(&compressed_target)?;
     }
 
+    if self.force_download {
+      if target.is_file() {
+        fs::remove_file(target)?;
+      }
+      if let Some(compressed_target) = compressed_target.as_ref().filter(|file| file.is_file()) {
+        fs::remove_file(compressed_target)?;
+      }
+    }
+
     if target.is_file() {
       let file_len = target.metadata()?.len();
       if file_len == self.asset.size {
@@ -601,7 +801,7 @@ impl Downloadable for AssetDownloadable {
 
     if let Some(compressed_target) = &compressed_target {
       if compressed_target.is_file() {
-        let local_hash = Sha1Sum::from_reader(&mut File::open(compressed_target)?)?;
+        let local_hash = Sha1Sum::from_file(compressed_target)?;
         if &local_hash == self.asset.compressed_hash.as_ref().unwrap() {
           return self.decompress_asset(target, &compressed_target);
         }
@@ -712,3 +912,42 @@ impl DownloadableMonitor {
     // TODO: fire update?
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use tokio::{ io::{ AsyncReadExt, AsyncWriteExt }, net::TcpListener };
+
+  use super::*;
+
+  // Answers every request with `body`, without a Content-Length so the size is only known while reading
+  async fn serve(body: &'static [u8]) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+      while let Ok((mut socket, _)) = listener.accept().await {
+        let mut request = [0; 1024];
+        let _ = socket.read(&mut request).await;
+        let _ = socket.write_all(b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n").await;
+        let _ = socket.write_all(body).await;
+      }
+    });
+    format!("http://{address}/file")
+  }
+
+  #[tokio::test]
+  async fn test_checksummed_max_size() {
+    let url = serve(&[b'a'; 100]).await;
+    let target = std::env::temp_dir().join("mclc-test-max-size").join("file");
+    let _ = fs::remove_file(&target);
+
+    let downloadable = ChecksummedDownloadable::new(Client::new(), &url, &target, true).with_max_size(10);
+    let err = downloadable.download().await.unwrap_err();
+    assert!(err.to_string().starts_with(&format!("Response from {url} is too large")));
+    assert!(!target.exists());
+
+    let downloadable = ChecksummedDownloadable::new(Client::new(), &url, &target, true).with_max_size(100);
+    downloadable.download().await.unwrap();
+    assert_eq!(fs::read(&target).unwrap().len(), 100);
+    fs::remove_dir_all(target.parent().unwrap()).unwrap();
+  }
+}
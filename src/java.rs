@@ -0,0 +1,233 @@
+use std::{ collections::{ HashMap, HashSet }, env, fs::read_dir, path::{ Path, PathBuf }, process::Command };
+
+use log::debug;
+use regex::Regex;
+use thiserror::Error;
+
+use crate::versions::json::{ rule::OperatingSystem, LocalVersionInfo };
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JavaInstallation {
+  pub path: PathBuf, // Java executable
+  pub version: String,
+  pub major_version: u8,
+}
+
+impl JavaInstallation {
+  pub fn from_executable(path: &Path) -> Option<JavaInstallation> {
+    let output = Command::new(path).arg("-version").output().ok()?;
+    // java -version prints to stderr
+    let output = String::from_utf8_lossy(&output.stderr);
+    let version = Regex::new(r#"version "([^"]+)""#).unwrap().captures(&output)?.get(1)?.as_str().to_string();
+    Some(JavaInstallation {
+      path: path.to_path_buf(),
+      major_version: Self::parse_major_version(&version)?,
+      version,
+    })
+  }
+
+  // "1.8.0_392" -> 8, "17.0.9" -> 17, "21" -> 21
+  fn parse_major_version(version: &str) -> Option<u8> {
+    let mut parts = version.split(|c: char| !c.is_ascii_digit());
+    let major = parts.next()?.parse().ok()?;
+    if major == 1 { parts.next()?.parse().ok() } else { Some(major) }
+  }
+
+  fn version_parts(&self) -> Vec<u32> {
+    self.version
+      .split(|c: char| !c.is_ascii_digit())
+      .filter_map(|part| part.parse().ok())
+      .collect()
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JavaAgent {
+  pub path: PathBuf, // Agent jar
+  pub options: Option<String>,
+}
+
+impl JavaAgent {
+  pub fn get_argument(&self) -> String {
+    match &self.options {
+      Some(options) => format!("-javaagent:{}={}", self.path.display(), options),
+      None => format!("-javaagent:{}", self.path.display()),
+    }
+  }
+}
+
+#[derive(Error, Debug)]
+pub enum JavaAgentError {
+  #[error("Java agent is not a valid jar: {0}")]
+  NotAJar(PathBuf),
+  #[error("Java agent manifest has no Premain-Class")]
+  NoPremainClass,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JavaAgentInfo {
+  pub manifest_entries: HashMap<String, String>, // Main section only
+  pub premain_class: Option<String>,
+  pub can_redefine_classes: bool,
+}
+
+impl JavaAgentInfo {
+  // Lines starting with a space continue the previous value, manifests wrap at 72 bytes
+  pub fn from_manifest(manifest: &str) -> JavaAgentInfo {
+    let mut entries: HashMap<String, String> = HashMap::new();
+    let mut last_key: Option<String> = None;
+    for line in manifest.lines() {
+      if line.is_empty() {
+        break;
+      }
+      if let Some(continuation) = line.strip_prefix(' ') {
+        if let Some(value) = last_key.as_ref().and_then(|key| entries.get_mut(key)) {
+          value.push_str(continuation);
+        }
+        continue;
+      }
+      if let Some((key, value)) = line.split_once(':') {
+        entries.insert(key.trim().to_string(), value.trim_start().to_string());
+        last_key = Some(key.trim().to_string());
+      }
+    }
+    JavaAgentInfo {
+      premain_class: entries.get("Premain-Class").cloned(),
+      can_redefine_classes: entries.get("Can-Redefine-Classes").is_some_and(|value| value.trim().eq_ignore_ascii_case("true")),
+      manifest_entries: entries,
+    }
+  }
+}
+
+pub struct JavaInstallationFinder;
+
+impl JavaInstallationFinder {
+  pub fn find_all() -> Vec<JavaInstallation> {
+    let mut seen = HashSet::new();
+    let mut installations = vec![];
+    for executable in Self::get_candidate_executables() {
+      let Ok(canonical) = executable.canonicalize() else {
+        continue;
+      };
+      if !seen.insert(canonical) {
+        continue;
+      }
+      if let Some(installation) = JavaInstallation::from_executable(&executable) {
+        debug!("Found Java {} at {}", installation.version, installation.path.display());
+        installations.push(installation);
+      }
+    }
+    installations
+  }
+
+  // Newest installation of the given major version
+  pub fn find_newest(major_version: u8) -> Option<JavaInstallation> {
+    Self::find_all()
+      .into_iter()
+      .filter(|installation| installation.major_version == major_version)
+      .max_by_key(|installation| installation.version_parts())
+  }
+
+  // Prefers Mojang's own runtime for the version's component, like the official launcher does
+  pub fn find_compatible(local_version: &LocalVersionInfo) -> Option<JavaInstallation> {
+    let major_version = local_version.get_runtime_major_version().unwrap_or(8);
+    if let Some(runtime_id) = local_version.get_runtime_id() {
+      let executable_name = Self::get_executable_name();
+      for platform_dir in list_dirs(&OperatingSystem::get_game_dir().join("runtime").join(runtime_id)) {
+        let java_home = platform_dir.join(runtime_id);
+        for java_home in [java_home.join("jre.bundle").join("Contents").join("Home"), java_home] {
+          let installation = JavaInstallation::from_executable(&java_home.join("bin").join(executable_name));
+          if let Some(installation) = installation.filter(|installation| installation.major_version == major_version) {
+            return Some(installation);
+          }
+        }
+      }
+    }
+    Self::find_newest(major_version)
+  }
+
+  fn get_executable_name() -> &'static str {
+    if OperatingSystem::get_current_platform() == OperatingSystem::Windows { "java.exe" } else { "java" }
+  }
+
+  fn get_candidate_executables() -> Vec<PathBuf> {
+    let executable_name = Self::get_executable_name();
+    let mut java_homes = vec![];
+    if let Some(java_home) = env::var_os("JAVA_HOME") {
+      java_homes.push(PathBuf::from(java_home));
+    }
+
+    let mut parent_dirs = vec![];
+    match OperatingSystem::get_current_platform() {
+      OperatingSystem::Windows => {
+        for program_files in ["ProgramFiles", "ProgramFiles(x86)"].iter().filter_map(env::var_os) {
+          let program_files = PathBuf::from(program_files);
+          parent_dirs.extend(["Java", "Eclipse Adoptium", "Zulu", "Microsoft"].map(|vendor| program_files.join(vendor)));
+        }
+      }
+      OperatingSystem::Osx => {
+        for dir in list_dirs(Path::new("/Library/Java/JavaVirtualMachines")) {
+          java_homes.push(dir.join("Contents").join("Home"));
+        }
+      }
+      _ => parent_dirs.extend(["/usr/lib/jvm", "/usr/java", "/opt/java"].map(PathBuf::from)),
+    }
+    for parent_dir in parent_dirs {
+      java_homes.extend(list_dirs(&parent_dir));
+    }
+
+    // Runtimes installed by the official launcher: runtime/<component>/<platform>/<component>
+    for component_dir in list_dirs(&OperatingSystem::get_game_dir().join("runtime")) {
+      for platform_dir in list_dirs(&component_dir) {
+        for java_home in list_dirs(&platform_dir) {
+          java_homes.push(java_home.join("jre.bundle").join("Contents").join("Home"));
+          java_homes.push(java_home);
+        }
+      }
+    }
+
+    let mut executables: Vec<PathBuf> = java_homes
+      .iter()
+      .map(|java_home| java_home.join("bin").join(executable_name))
+      .collect();
+    if let Some(path) = env::var_os("PATH") {
+      executables.extend(env::split_paths(&path).map(|dir| dir.join(executable_name)));
+    }
+    executables.into_iter().filter(|executable| executable.is_file()).collect()
+  }
+}
+
+fn list_dirs(dir: &Path) -> Vec<PathBuf> {
+  let Ok(entries) = read_dir(dir) else {
+    return vec![];
+  };
+  entries
+    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+    .filter(|path| path.is_dir())
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_java_agent_info_from_manifest() {
+    let manifest =
+      "Manifest-Version: 1.0\r\nPremain-Class: moe.yushi.authlibinjector.Premai\r\n n\r\nCan-Redefine-Classes: TRUE\r\n\r\nName: moe/\r\nSealed: true\r\n";
+    let info = JavaAgentInfo::from_manifest(manifest);
+    assert_eq!(info.premain_class.as_deref(), Some("moe.yushi.authlibinjector.Premain"));
+    assert!(info.can_redefine_classes);
+    assert_eq!(info.manifest_entries.len(), 3);
+    assert!(!info.manifest_entries.contains_key("Sealed"));
+  }
+
+  #[test]
+  fn test_parse_major_version() {
+    assert_eq!(JavaInstallation::parse_major_version("1.8.0_392"), Some(8));
+    assert_eq!(JavaInstallation::parse_major_version("17.0.9"), Some(17));
+    assert_eq!(JavaInstallation::parse_major_version("21"), Some(21));
+    assert_eq!(JavaInstallation::parse_major_version("21-ea"), Some(21));
+    assert_eq!(JavaInstallation::parse_major_version("unknown"), None);
+  }
+}
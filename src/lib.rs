@@ -1,4 +1,5 @@
 pub mod download_utils;
+pub mod java;
 pub mod versions;
 pub mod profile_manager;
 pub mod options;
@@ -8,57 +9,71 @@ pub mod progress_reporter;
 mod tests;
 
 use std::{
-  path::{ PathBuf, MAIN_SEPARATOR_STR },
+  path::{ Path, PathBuf },
   fs::{ self, create_dir_all, File },
-  env::consts::ARCH,
   collections::{ HashMap, HashSet },
   ops::Deref,
   io::{ self, Write },
+  process::ExitStatus,
   sync::Arc,
+  time::{ Duration, Instant },
 };
 
-use chrono::{ Utc, Timelike };
-use download_utils::{ ProxyOptions, download_job::DownloadJob };
+use chrono::{ Utc, Timelike, DateTime };
+use download_utils::{ ProxyOptions, download_job::{ DownloadJob, DownloadJobBuilder } };
 use log::{ info, error, debug, warn };
-use options::{ GameOptions, MinecraftFeatureMatcher };
-use os_info::Type::Windows;
+use options::{ GameOptions, MinecraftFeatureMatcher, NativeExtractionStrategy };
 use process::GameProcess;
 use progress_reporter::ProgressReporter;
+use rayon::prelude::*;
 use regex::Regex;
-use serde_json::json;
 use thiserror::Error;
 use versions::{
   VersionManager,
-  json::{ rule::{ FeatureMatcher, RuleFeatureType, OperatingSystem }, LocalVersionInfo, AssetIndex },
-  info::VersionInfo,
+  ModLoaderInstaller,
+  get_asset_object_path,
+  json::{ rule::OperatingSystem, LocalVersionInfo, AssetIndex, AssetObject, ArgumentType },
+  info::{ VersionInfo, MCVersion },
 };
 use zip::ZipArchive;
 
-use crate::{ versions::json::{ ArgumentType, library::ExtractRules, Sha1Sum }, process::GameProcessBuilder };
+use crate::{ versions::json::{ library::{ ExtractRules, LibrarySource }, MainClassType, Sha1Sum }, process::GameProcessBuilder };
 
 #[derive(Error, Debug)]
 #[error("{0}")]
 pub struct MinecraftLauncherError(String);
 
-const DEFAULT_JRE_ARGUMENTS_32BIT: &str =
-  "-Xmx2G -XX:+UnlockExperimentalVMOptions -XX:+UseG1GC -XX:G1NewSizePercent=20 -XX:G1ReservePercent=20 -XX:MaxGCPauseMillis=50 -XX:G1HeapRegionSize=32M";
-const DEFAULT_JRE_ARGUMENTS_64BIT: &str =
-  "-Xmx2G -XX:+UnlockExperimentalVMOptions -XX:+UseG1GC -XX:G1NewSizePercent=20 -XX:G1ReservePercent=20 -XX:MaxGCPauseMillis=50 -XX:G1HeapRegionSize=32M";
+#[derive(Error, Debug)]
+pub enum LaunchError {
+  #[error("Version {0:?} has no main class")]
+  MissingMainClass(MCVersion),
+  #[error("Version {0:?} is a server version and can't be launched as a client")]
+  ServerMainClass(MCVersion),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartReason {
+  Requested,
+  Crash(i32), // Exit code
+  OutOfMemory,
+}
 
 pub struct MinecraftGameRunner {
   options: GameOptions,
   feature_matcher: Box<MinecraftFeatureMatcher>,
   version_manager: VersionManager,
   local_version: Option<LocalVersionInfo>,
+  mod_loader: Option<Box<dyn ModLoaderInstaller>>,
 
   natives_dir: Option<PathBuf>,
   virtual_dir: Option<PathBuf>,
+  game_pid: Option<u32>,
 }
 
 impl MinecraftGameRunner {
   pub fn new(options: GameOptions) -> Self {
     let feature_matcher = Box::new(MinecraftFeatureMatcher(false, options.resolution.clone()));
-    let version_manager = VersionManager::new(options.game_dir.clone(), feature_matcher.clone());
+    let version_manager = VersionManager::new(options.get_data_dir().clone(), feature_matcher.clone());
 
     Self {
       options,
@@ -66,42 +81,56 @@ impl MinecraftGameRunner {
       version_manager,
 
       local_version: None,
+      mod_loader: None,
       natives_dir: None,
       virtual_dir: None,
+      game_pid: None,
     }
   }
 
+  pub fn with_mod_loader(mut self, loader: impl ModLoaderInstaller + 'static) -> Self {
+    self.mod_loader = Some(Box::new(loader));
+    self
+  }
+
   fn get_local_version(&self) -> &LocalVersionInfo {
     self.local_version.as_ref().unwrap()
   }
 
   fn get_virtual_dir(&self) -> &PathBuf {
-    &self.virtual_dir.as_ref().unwrap()
-  }
-
-  fn get_natives_dir(&self) -> &PathBuf {
-    &self.natives_dir.as_ref().unwrap()
+    self.virtual_dir.as_ref().unwrap()
   }
 
   fn get_version_dir(&self) -> PathBuf {
-    self.options.game_dir.join("versions").join(&self.options.version.to_string())
+    self.options.get_data_dir().join("versions").join(&self.options.version.to_string())
   }
 
   fn get_assets_dir(&self) -> PathBuf {
-    self.options.game_dir.join("assets")
+    VersionManager::get_assets_dir(self.options.get_data_dir())
   }
 
   fn get_asset_index(&self) -> Option<AssetIndex> {
     let asset_index_id = &self.get_local_version().asset_index.as_ref()?.id;
     let asset_index_json_path = self.get_assets_dir().join("indexes").join(format!("{}.json", asset_index_id));
+    AssetIndex::from_file(&asset_index_json_path).ok()
+  }
 
-    let file = &mut File::open(asset_index_json_path).ok()?;
-    Some(serde_json::from_reader(file).ok()?)
+  // --title was removed in 1.15 (first snapshot 19w34a)
+  fn supports_title_argument(local_version: &LocalVersionInfo) -> bool {
+    let removed_at = DateTime::parse_from_rfc3339("2019-08-21T00:00:00+00:00").unwrap();
+    local_version.get_release_time().inner() < &removed_at
   }
 
-  fn is_win_ten(&self) -> bool {
-    let os = os_info::get();
-    os.os_type() == Windows && os.edition().is_some_and(|edition| edition.contains("Windows 10"))
+  // --screenshotPath was added in 1.20.2, older versions only read the system property
+  fn supports_screenshot_path_argument(local_version: &LocalVersionInfo) -> bool {
+    let added_at = DateTime::parse_from_rfc3339("2023-09-20T09:02:57+00:00").unwrap();
+    local_version.get_release_time().inner() >= &added_at
+  }
+
+  // --renderBackend is only understood by 1.16+ (first release 2020-06-23)
+  fn supports_render_backend_argument(local_version: &LocalVersionInfo) -> bool {
+    let added_at = DateTime::parse_from_rfc3339("2020-06-23T16:20:52+00:00").unwrap();
+    local_version.get_release_time().inner() >= &added_at
   }
 
   fn progress_reporter(&self) -> &Arc<ProgressReporter> {
@@ -112,8 +141,17 @@ impl MinecraftGameRunner {
 impl MinecraftGameRunner {
   pub async fn launch(&mut self) -> Result<GameProcess, Box<dyn std::error::Error>> {
     // TODO: maybe initialize everything here and avoid initializing another instance with the same game runner until it's completed
+    self.options.launcher_options_required()?;
     self.progress_reporter().set("Fetching version manifest", 0, 2);
-    self.version_manager.refresh().await?;
+    // Self-contained versions don't need the manifest, everything below still verifies the local files
+    if let Err(err) = self.version_manager.refresh_remote().await {
+      // The offline check only knows about the vanilla version, the mod loader decides itself whether it needs to download
+      if self.mod_loader.is_some() || !self.can_launch_offline().await {
+        return Err(err);
+      }
+      warn!("Couldn't fetch the version manifest, launching offline: {err}");
+    }
+    self.version_manager.refresh_local()?;
     info!("Queuing library & version downloads");
 
     self.progress_reporter().set_status("Resolving local version").set_progress(1);
@@ -132,54 +170,160 @@ impl MinecraftGameRunner {
       local_version = self.version_manager.install_version(&self.options.version).await?;
     }
 
-    local_version = local_version.resolve(&self.version_manager, HashSet::new()).await?;
+    local_version = self.resolve_with_mod_loader(local_version).await?;
 
     self.progress_reporter().clear();
     // TODO: self.migrate_old_assets()
-    self.download_required_files(&local_version).await?;
+    {
+      let _guard = self.progress_reporter().section("Downloading resources");
+      self.download_required_files(&local_version).await?;
+    }
 
     self.local_version = Some(local_version);
     self.launch_game().await
   }
 
+  // The mod loader replaces the vanilla version with its own, which inherits from it
+  async fn resolve_with_mod_loader(&self, mut local_version: LocalVersionInfo) -> Result<LocalVersionInfo, Box<dyn std::error::Error>> {
+    if let Some(mod_loader) = &self.mod_loader {
+      self.progress_reporter().set_status("Installing mod loader");
+      local_version = mod_loader.install(&self.version_manager, &self.options.version).await?;
+    }
+    local_version.resolve(&self.version_manager, HashSet::new()).await
+  }
+
+  // Launches the game and waits for it to exit, relaunching it after clean exits while reconnect attempts remain.
+  // The version is only prepared once, reconnects just start the game again
+  pub async fn launch_and_wait(&mut self) -> Result<ExitStatus, Box<dyn std::error::Error>> {
+    let mut reconnect_attempts = self.options.reconnect_attempts;
+    let mut process = self.launch().await?;
+    loop {
+      let status = self.on_game_exit(&mut process).await?;
+      if !status.success() || reconnect_attempts == 0 {
+        return Ok(status);
+      }
+      reconnect_attempts -= 1;
+      info!("Game exited cleanly, reconnecting ({} attempt(s) left)", reconnect_attempts);
+      self.remove_launch_dirs()?;
+      process = self.launch_game().await?;
+    }
+  }
+
+  // Relaunches with the same options, the previous game process is killed if it is still running
+  pub async fn restart(&mut self, process: &mut GameProcess, reason: RestartReason) -> Result<GameProcess, Box<dyn std::error::Error>> {
+    if self.local_version.is_none() {
+      Err(MinecraftLauncherError("The game has to be launched before it can be restarted".to_string()))?;
+    }
+    warn!("Restarting game ({reason:?})");
+    if process.try_wait()?.is_none() {
+      info!("Killing previous game process {}", process.pid());
+      process.kill()?;
+    }
+    self.game_pid = None;
+    self.remove_launch_dirs()?;
+    self.perform_cleanups()?;
+    self.launch_game().await
+  }
+
+  async fn can_launch_offline(&self) -> bool {
+    if let Err(err) = self.version_manager.refresh_local() {
+      warn!("Failed to scan local versions: {err}");
+      return false;
+    }
+
+    let Some(local_version) = self.version_manager.get_local_version(&self.options.version) else {
+      return false;
+    };
+    let Ok(resolved) = local_version.resolve(&self.version_manager, HashSet::new()).await else {
+      return false;
+    };
+    resolved.applies_to_current_environment(self.feature_matcher.deref()) &&
+      !resolved.requires_internet(self.options.get_data_dir(), self.feature_matcher.deref(), self.options.authentication.as_ref())
+  }
+
+  fn download_job_builder(&self, name: &str, progress_prefix: &str) -> DownloadJobBuilder {
+    let builder = DownloadJob::builder()
+      .name(name.to_string())
+      .max_concurrent(self.options.max_concurrent_downloads)
+      .max_attempts(self.options.max_download_attempts)
+      .progress_reporter(Arc::new(self.progress_reporter().with_prefix(progress_prefix).into()));
+    match self.options.download_timeout {
+      Some(download_timeout) => builder.download_timeout(download_timeout),
+      None => builder,
+    }
+  }
+
   async fn download_required_files(&self, local_version: &LocalVersionInfo) -> Result<(), Box<dyn std::error::Error>> {
-    let mut job1 = DownloadJob::new(
-      "Version & Libraries",
-      false,
-      self.options.max_concurrent_downloads,
-      self.options.max_download_attempts,
-      self.progress_reporter()
-    );
+    let _lock = self.version_manager.lock()?;
+    if self.options.force_download_all {
+      warn!("Force download is enabled, every file will be downloaded again regardless of local state!");
+    }
+    let mut job1 = self.download_job_builder("Version & Libraries", "[Libraries]").build();
     self.version_manager.download_version(&self, local_version, &mut job1)?;
 
-    let mut job2 = DownloadJob::new(
-      "Resources",
-      false,
-      self.options.max_concurrent_downloads,
-      self.options.max_download_attempts,
-      self.progress_reporter()
+    let mut job2 = self.download_job_builder("Resources", "[Assets]").build();
+    job2.add_downloadables(
+      self.version_manager
+        .get_resource_files(
+          &self.options.proxy,
+          &self.options.http_client_config,
+          self.options.get_data_dir(),
+          local_version,
+          self.options.force_download_all
+        ).await
+        .unwrap()
     );
-    job2.add_downloadables(self.version_manager.get_resource_files(&self.options.proxy, &self.options.game_dir, &local_version).await.unwrap());
 
     job1.start().await?;
     job2.start().await?;
     Ok(())
   }
 
-  async fn launch_game(&mut self) -> Result<GameProcess, Box<dyn std::error::Error>> {
-    info!("Launching game");
-
-    let natives_dir = self.get_version_dir().join(format!("{}-natives-{}", self.options.version.to_string(), Utc::now().nanosecond()));
+  fn extract_natives(&self, natives_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
     if !natives_dir.is_dir() {
-      fs::create_dir_all(&natives_dir)?;
+      fs::create_dir_all(natives_dir)?;
     }
 
     info!("Unpacking natives to {}", natives_dir.display());
 
-    if let Err(err) = self.unpack_natives(&natives_dir) {
+    let mut extracted_natives = vec![];
+    let unpack_result = self.unpack_natives(natives_dir, &mut extracted_natives);
+    if self.options.natives_debug_info {
+      let manifest: String = extracted_natives
+        .iter()
+        .map(|(file, source)| format!("{} <- {}\n", file.display(), source.display()))
+        .collect();
+      if let Err(err) = fs::write(natives_dir.join("manifest.txt"), manifest) {
+        warn!("Couldn't write natives manifest: {err}");
+      }
+    }
+    if let Err(err) = unpack_result {
       error!("Couldn't unpack natives! {err}");
+      if self.options.preserve_natives_on_error || self.options.native_extraction_strategy != NativeExtractionStrategy::TempDir {
+        warn!("Keeping partially extracted natives at {}", natives_dir.display());
+      } else if let Err(err) = fs::remove_dir_all(natives_dir) {
+        warn!("Couldn't remove natives directory {}: {err}", natives_dir.display());
+      }
       Err(MinecraftLauncherError(format!("Couldn't unpack natives! {err}")))?;
     }
+    Ok(())
+  }
+
+  async fn launch_game(&mut self) -> Result<GameProcess, Box<dyn std::error::Error>> {
+    let progress_reporter = Arc::clone(self.progress_reporter());
+    let _guard = progress_reporter.section("Launching game");
+    info!("Launching game");
+
+    let natives_dir = match &self.options.native_extraction_strategy {
+      NativeExtractionStrategy::TempDir =>
+        Some(self.get_version_dir().join(format!("{}-natives-{}", self.options.version.to_string(), Utc::now().nanosecond()))),
+      NativeExtractionStrategy::FixedDir(dir) => Some(dir.clone()),
+      NativeExtractionStrategy::JavaLibraryPath(_) => None,
+    };
+    if let Some(natives_dir) = &natives_dir {
+      self.extract_natives(natives_dir)?;
+    }
+    self.natives_dir = natives_dir;
 
     let virtual_dir = self.reconstruct_assets();
     if let Err(err) = &virtual_dir {
@@ -188,7 +332,6 @@ impl MinecraftGameRunner {
     }
     self.virtual_dir = virtual_dir.ok();
 
-    self.natives_dir = Some(natives_dir);
 
     let game_dir = &self.options.game_dir;
     info!("Launching in {}", game_dir.display());
@@ -208,104 +351,123 @@ impl MinecraftGameRunner {
     let mut game_process_builder = GameProcessBuilder::new();
     game_process_builder.with_java_path(&self.options.java_path);
     game_process_builder.directory(game_dir);
+    game_process_builder.with_sandbox(self.options.sandbox);
+    game_process_builder.with_capture_output(self.options.capture_output);
+    game_process_builder.with_capture_output_timeout(self.options.capture_output_timeout);
+    if let Some(crash_report_archive_dir) = &self.options.crash_report_archive_dir {
+      game_process_builder.with_crash_report_archive_dir(&crash_report_archive_dir.join(self.options.version.to_string()));
+    }
 
-    if let Some(jvm_args) = &self.options.jvm_args {
-      game_process_builder.with_arguments(jvm_args.clone());
-    } else {
-      let args = if ARCH == "x86_64" { DEFAULT_JRE_ARGUMENTS_64BIT } else { DEFAULT_JRE_ARGUMENTS_32BIT };
-      game_process_builder.with_arguments(
-        args
-          .split(" ")
-          .map(|s| s.to_string())
-          .collect()
-      );
+    if let (Some(auth_server), Some(authlib_injector_path)) = (&self.options.auth_server, &self.options.authlib_injector_path) {
+      game_process_builder.with_argument(format!("-javaagent:{}={}", authlib_injector_path.display(), auth_server));
     }
 
     let substitutor = self.create_arguments_substitutor();
 
     // Add JVM args
     let local_version = self.local_version.as_ref().unwrap();
-    if !local_version.arguments.is_empty() {
-      if let Some(arguments) = local_version.arguments.get(&ArgumentType::Jvm) {
-        game_process_builder.with_arguments(
-          arguments
-            .iter()
-            .map(|v| v.apply(self.feature_matcher.deref()))
-            .flatten()
-            .flatten()
-            .cloned()
-            .map(&substitutor)
-            .collect::<Vec<_>>()
-        );
-      }
-    } else if let Some(_) = &local_version.minecraft_arguments {
-      if OperatingSystem::get_current_platform() == OperatingSystem::Windows {
-        game_process_builder.with_argument("-XX:HeapDumpPath=MojangTricksIntelDriversForPerformance_javaw.exe_minecraft.exe.heapdump");
-        if self.is_win_ten() {
-          game_process_builder.with_arguments(vec!["-Dos.name=Windows 10", "-Dos.version=10.0"]);
+    if local_version.get_main_class_type() == MainClassType::Server {
+      Err(LaunchError::ServerMainClass(local_version.get_id().clone()))?;
+    }
+    let version_argument_count = local_version.applicable_arguments_count(self.feature_matcher.deref());
+    debug!("Version {} provides {} argument(s)", local_version.get_id().to_string(), version_argument_count);
+    game_process_builder.with_version_argument_count(version_argument_count);
+    let user_args: Vec<String> = match &self.options.jvm_args {
+      Some(jvm_args) => jvm_args.clone(),
+      None => self.options.jvm_args_profile.get_arguments(),
+    }
+      .into_iter()
+      .map(&substitutor)
+      .collect();
+    let mut version_args: Vec<String> = local_version
+      .get_arguments_flat(ArgumentType::Jvm, self.feature_matcher.deref())
+      .into_iter()
+      .map(&substitutor)
+      .collect();
+    let os = OperatingSystem::get_current_platform();
+    if self.options.custom_icon.is_some() && os == OperatingSystem::Osx {
+      version_args.retain(|arg| !arg.starts_with("-Xdock:icon="));
+    }
+    game_process_builder.with_arguments(self.options.jvm_args_precedence.apply(user_args, version_args));
+
+    if let Some(custom_icon) = &self.options.custom_icon {
+      match os {
+        OperatingSystem::Osx => {
+          game_process_builder.with_argument(format!("-Xdock:icon={}", custom_icon.display()));
+        }
+        OperatingSystem::Windows => {
+          game_process_builder.with_argument(format!("-Djava.awt.taskbar.icon={}", custom_icon.display()));
         }
-      } else if OperatingSystem::get_current_platform() == OperatingSystem::Osx {
-        game_process_builder.with_arguments(vec![&substitutor("-Xdock:icon=${asset=icons/minecraft.icns}".to_string()), "-Xdock:name=Minecraft"]);
+        _ => debug!("Custom icons are only supported on macOS and Windows"),
       }
+    }
 
-      game_process_builder.with_argument(&substitutor("-Djava.library.path=${natives_directory}".to_string()));
-      game_process_builder.with_argument(&substitutor("-Dminecraft.launcher.brand=${launcher_name}".to_string()));
-      game_process_builder.with_argument(&substitutor("-Dminecraft.launcher.version=${launcher_version}".to_string()));
-      game_process_builder.with_argument(&substitutor("-Dminecraft.client.jar=${primary_jar}".to_string()));
-      game_process_builder.with_arguments(vec!["-cp".to_string(), substitutor("${classpath}".to_string())]);
+    let screenshot_dir = self.options.screenshot_dir.as_ref();
+    let supports_screenshot_path = Self::supports_screenshot_path_argument(local_version);
+    if let Some(screenshot_dir) = screenshot_dir {
+      fs::create_dir_all(screenshot_dir)?;
+      if !supports_screenshot_path {
+        game_process_builder.with_argument(format!("-Dminecraft.screenshotDir={}", screenshot_dir.display()));
+      }
     }
 
-    game_process_builder.with_argument(&local_version.get_main_class());
+    let rendering_backend = self.options.rendering_backend.as_ref();
+    if rendering_backend.is_some_and(|backend| backend == "none") {
+      game_process_builder.with_argument("-Djava.awt.headless=true");
+    }
+
+    game_process_builder.with_argument(local_version.get_main_class()?);
     info!("Half command: {}", game_process_builder.get_args().join(" "));
-    if !local_version.arguments.is_empty() {
-      if let Some(arguments) = local_version.arguments.get(&ArgumentType::Game) {
-        game_process_builder.with_arguments(
-          arguments
-            .iter()
-            .map(|v| v.apply(self.feature_matcher.deref()))
-            .flatten()
-            .flatten()
-            .cloned()
-            .map(&substitutor)
-            .collect::<Vec<_>>()
-        );
+    game_process_builder.with_arguments(
+      local_version
+        .get_arguments_flat(ArgumentType::Game, self.feature_matcher.deref())
+        .into_iter()
+        .map(&substitutor)
+        .collect()
+    );
+
+    let proxy = match &self.options.proxy {
+      ProxyOptions::NoProxy => None,
+      ProxyOptions::Proxy(url) => {
+        let password = url.password().map(|password| password.to_string());
+        Some((url, url.username().to_string(), password))
       }
-    } else if let Some(minecraft_arguments) = &local_version.minecraft_arguments {
-      game_process_builder.with_arguments(
-        minecraft_arguments
-          .split(" ")
-          .map(|s| s.to_string())
-          .map(&substitutor)
-          .collect::<Vec<_>>()
-      );
+      ProxyOptions::AuthenticatedProxy { url, username, password } => Some((url, username.clone(), Some(password.clone()))),
+    };
+    if let Some((url, username, password)) = &proxy {
+      game_process_builder.with_arguments(vec!["--proxyHost".to_string(), url.host_str().unwrap().to_string()]);
+      game_process_builder.with_arguments(vec!["--proxyPort".to_string(), url.port().unwrap().to_string()]);
 
-      if self.feature_matcher.has_feature(&RuleFeatureType::IsDemoUser, &json!(true)) {
-        game_process_builder.with_argument("--demo");
+      if !username.is_empty() {
+        game_process_builder.with_arguments(vec!["--proxyUser".to_string(), username.clone()]);
       }
 
-      if self.feature_matcher.has_feature(&RuleFeatureType::HasCustomResolution, &json!(true)) {
-        game_process_builder.with_arguments(
-          vec![
-            "--width".to_string(),
-            substitutor("${resolution_width}".to_string()),
-            "--height".to_string(),
-            substitutor("${resolution_height}".to_string())
-          ]
-        );
+      if let Some(password) = password {
+        game_process_builder.with_arguments(vec!["--proxyPass".to_string(), password.clone()]);
       }
     }
 
-    // TODO: get proxy auth?
-    if let ProxyOptions::Proxy(url) = &self.options.proxy {
-      game_process_builder.with_arguments(vec!["--proxyHost".to_string(), url.host_str().unwrap().to_string()]);
-      game_process_builder.with_arguments(vec!["--proxyPort".to_string(), url.port().unwrap().to_string()]);
+    if let Some(screenshot_dir) = screenshot_dir.filter(|_| supports_screenshot_path) {
+      game_process_builder.with_arguments(vec!["--screenshotPath".to_string(), screenshot_dir.display().to_string()]);
+    }
 
-      if !url.username().is_empty() {
-        game_process_builder.with_arguments(vec!["--proxyUser".to_string(), url.username().to_string()]);
+    if self.options.no_gui {
+      game_process_builder.with_argument("--nogui");
+    }
+
+    if let Some(rendering_backend) = rendering_backend {
+      if Self::supports_render_backend_argument(local_version) {
+        game_process_builder.with_arguments(vec!["--renderBackend".to_string(), rendering_backend.clone()]);
+      } else {
+        warn!("Version {} doesn't support --renderBackend, ignoring rendering_backend", local_version.get_id().to_string());
       }
+    }
 
-      if let Some(passowrd) = url.password() {
-        game_process_builder.with_arguments(vec!["--proxyPass".to_string(), passowrd.to_string()]);
+    if let Some(window_title) = &self.options.window_title {
+      if Self::supports_title_argument(local_version) {
+        game_process_builder.with_arguments(vec!["--title".to_string(), substitutor(window_title.clone())]);
+      } else {
+        warn!("Version {} doesn't support custom window titles, ignoring window_title", local_version.get_id().to_string());
       }
     }
 
@@ -316,7 +478,15 @@ impl MinecraftGameRunner {
       if !token.is_empty() {
         args = args.replace(&token, "?????");
       }
+      if let Some((_, _, Some(password))) = &proxy {
+        if !password.is_empty() {
+          args = args.replace(password, "?????");
+        }
+      }
       debug!("Running {} {}", &self.options.java_path.display(), args);
+      if self.options.debug_args {
+        eprintln!("Running {} {}", &self.options.java_path.display(), args);
+      }
     }
 
     let regex = Regex::new(r"\$\{.+\}")?;
@@ -331,11 +501,57 @@ impl MinecraftGameRunner {
     self.perform_cleanups()?;
 
     match process {
-      Ok(process) => Ok(process),
+      Ok(process) => {
+        self.game_pid = Some(process.pid());
+        self.on_game_start().await;
+        Ok(process)
+      }
       Err(err) => Err(Box::new(MinecraftLauncherError(format!("Failed to launch game: {err}")))),
     }
   }
 
+  pub async fn on_game_start(&self) {
+    if self.options.close_launcher_on_game_start {
+      self.options.lifecycle_hooks.game_started();
+    }
+  }
+
+  // Waits for the game to exit without blocking the runtime
+  pub async fn on_game_exit(&self, process: &mut GameProcess) -> io::Result<ExitStatus> {
+    let status = loop {
+      if let Some(status) = process.try_wait()? {
+        break status;
+      }
+      tokio::time::sleep(Duration::from_millis(250)).await;
+    };
+    if self.options.reopen_launcher_on_game_exit {
+      self.options.lifecycle_hooks.game_exited(&status);
+    }
+    Ok(status)
+  }
+
+  pub async fn cleanup(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    self.remove_launch_dirs()
+  }
+
+  fn remove_launch_dirs(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    // Only per-launch natives are removed, fixed and pre-extracted directories belong to the user
+    if let Some(natives_dir) = self.natives_dir.take() {
+      if self.options.native_extraction_strategy == NativeExtractionStrategy::TempDir && natives_dir.is_dir() {
+        debug!("Deleting {}", natives_dir.display());
+        fs::remove_dir_all(&natives_dir)?;
+      }
+    }
+    if let Some(virtual_dir) = self.virtual_dir.take() {
+      // Resource-mapped assets live in the game directory and are kept
+      if virtual_dir != self.options.game_dir.join("resources") && virtual_dir.is_dir() {
+        debug!("Deleting {}", virtual_dir.display());
+        fs::remove_dir_all(&virtual_dir)?;
+      }
+    }
+    Ok(())
+  }
+
   fn perform_cleanups(&self) -> Result<(), Box<dyn std::error::Error>> {
     // this.cleanupOrphanedVersions();
     // this.cleanupOrphanedAssets();
@@ -346,54 +562,34 @@ impl MinecraftGameRunner {
   }
 
   fn cleanup_old_natives(&self) -> Result<(), Box<dyn std::error::Error>> {
-    let game_dir = &self.version_manager.game_dir;
-
-    let current_time = Utc::now().timestamp_millis() as u128;
-    // let time_threshold = Duration::from_secs(3600);
-
-    for local_ver in self.version_manager.get_local_versions() {
-      let version_id = local_ver.get_id().to_string();
-      let version_dir = game_dir.join("versions").join(&version_id);
-      let dirs: Vec<PathBuf> = fs
-        ::read_dir(&version_dir)?
-        .filter_map(|file| file.ok())
-        .filter(|file| file.file_type().unwrap().is_dir())
-        .map(|file| file.file_name().to_str().unwrap().to_string())
-        .filter(|name| name.starts_with(&format!("{version_id}-natives-")))
-        .map(|name| version_dir.join(name))
-        .collect();
-      for native_dir in dirs {
-        let modified_time = native_dir.metadata()?.modified()?;
-        if current_time - modified_time.elapsed()?.as_millis() >= 3600000 {
-          debug!("Deleting {}", native_dir.display());
-          if let Err(err) = fs::remove_dir_all(&native_dir) {
-            warn!("Failed to delete {}: {}", native_dir.display(), err);
-          }
-        }
-      }
-    }
+    let report = self.version_manager.gc_old_natives(Duration::from_secs(3600))?;
+    debug!("Deleted {} of {} natives directories, {} bytes freed", report.deleted.len(), report.inspected.len(), report.bytes_freed);
     Ok(())
   }
 
-  fn unpack_natives(&self, natives_dir: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+  // Extracted files are added to `extracted` as (path relative to natives_dir, source jar)
+  fn unpack_natives(&self, natives_dir: &Path, extracted: &mut Vec<(PathBuf, PathBuf)>) -> Result<(), Box<dyn std::error::Error>> {
     let os = OperatingSystem::get_current_platform();
     let libs = self.local_version.as_ref().unwrap().get_relevant_libraries(self.feature_matcher.deref());
 
     fn unpack_native(
-      natives_dir: &PathBuf,
+      natives_dir: &Path,
       mut zip_archive: ZipArchive<File>,
-      extract_rules: Option<&ExtractRules>
+      extract_rules: Option<&ExtractRules>,
+      extracted: &mut Vec<PathBuf>
     ) -> Result<(), Box<dyn std::error::Error>> {
       for i in 0..zip_archive.len() {
-        let mut file = zip_archive.by_index(i).unwrap();
-        let file_zip_path = file.enclosed_name().unwrap().to_owned();
+        let mut file = zip_archive.by_index(i)?;
+        let Some(file_zip_path) = file.enclosed_name().map(Path::to_path_buf) else {
+          Err(MinecraftLauncherError(format!("Native {} points outside of the natives directory", file.name())))?
+        };
         if let Some(extract_rules) = extract_rules {
           if !extract_rules.should_extract(&file_zip_path) {
             continue;
           }
         }
 
-        let output_file = natives_dir.join(file_zip_path);
+        let output_file = natives_dir.join(&file_zip_path);
         create_dir_all(output_file.parent().unwrap())?;
         if file.is_dir() {
           continue;
@@ -401,18 +597,20 @@ impl MinecraftGameRunner {
 
         let mut output_file = File::create(output_file)?;
         io::copy(&mut file, &mut output_file)?;
+        extracted.push(file_zip_path);
       }
       Ok(())
     }
 
     for lib in libs {
-      let natives = &lib.natives;
-      if let Some(native_id) = natives.get(&os) {
-        let file = &self.options.game_dir.join("libraries").join(lib.get_artifact_path(Some(native_id)).replace("/", MAIN_SEPARATOR_STR));
+      if let Some(file) = lib.get_native_path(self.options.get_data_dir(), &os) {
 
-        let zip_file = ZipArchive::new(File::open(file)?)?;
+        let zip_file = ZipArchive::new(File::open(&file)?)?;
         let extract_rules = lib.extract.as_ref();
-        let _ = unpack_native(natives_dir, zip_file, extract_rules); // Ignore errors
+        let mut extracted_files = vec![];
+        let unpack_result = unpack_native(natives_dir, zip_file, extract_rules, &mut extracted_files);
+        extracted.extend(extracted_files.into_iter().map(|extracted_file| (extracted_file, file.clone())));
+        unpack_result?;
       }
     }
 
@@ -420,9 +618,30 @@ impl MinecraftGameRunner {
   }
 
   fn reconstruct_assets(&self) -> Result<PathBuf, Box<dyn std::error::Error>> {
-    let assets_dir = self.options.game_dir.join("assets"); //self.assets_dir;
+    fn reconstruct_asset(
+      virtual_dir: &Path,
+      assets_dir: &Path,
+      name: &str,
+      asset_obj: &AssetObject
+    ) -> io::Result<()> {
+      let asset_file = virtual_dir.join(name);
+      let object_file = get_asset_object_path(assets_dir, &asset_obj.hash);
+
+      if asset_file.is_file() {
+        let existing_hash = Sha1Sum::from_file(&asset_file).map_err(|err| io::Error::other(err.to_string()))?;
+        if existing_hash == asset_obj.hash {
+          return Ok(());
+        }
+      }
+
+      debug!("Copying asset for virtual or resource-mapped: {}", asset_file.display());
+      create_dir_all(asset_file.parent().unwrap())?;
+      fs::copy(object_file, asset_file)?;
+      Ok(())
+    }
+
+    let assets_dir = self.get_assets_dir();
     let indexes_dir = assets_dir.join("indexes");
-    let objects_dir = assets_dir.join("objects");
     let asset_index_id = &self.get_local_version().asset_index.as_ref().unwrap().id;
     let asset_index_file = indexes_dir.join(format!("{}.json", asset_index_id));
     let mut virtual_dir = assets_dir.join("virtual").join(asset_index_id);
@@ -431,7 +650,7 @@ impl MinecraftGameRunner {
       warn!("No assets index file {}; can't reconstruct assets", virtual_dir.display());
       return Ok(virtual_dir);
     } else {
-      let asset_index: AssetIndex = serde_json::from_reader(File::open(asset_index_file)?)?;
+      let asset_index = AssetIndex::from_file(&asset_index_file)?;
       if asset_index.map_to_resources {
         virtual_dir = self.options.game_dir.join("resources");
       }
@@ -439,23 +658,15 @@ impl MinecraftGameRunner {
       if asset_index.is_virtual || asset_index.map_to_resources {
         info!("Reconstructing virtual assets folder at {}", virtual_dir.display());
 
-        for asset_obj_entry in asset_index.get_file_map() {
-          let asset_file = virtual_dir.join(asset_obj_entry.0);
-          let object_file = objects_dir.join(&asset_obj_entry.1.hash.to_string()[0..2]).join(asset_obj_entry.1.hash.to_string());
-
-          let mut should_copy = true;
-          if asset_file.is_file() {
-            let hash = Sha1Sum::from_reader(&mut File::open(&asset_file)?)?;
-            if hash != asset_obj_entry.1.hash {
-              should_copy = true;
-            }
-          }
-
-          if should_copy {
-            info!("Copying asset for virtual or resource-mapped: {}", asset_file.display());
-            fs::copy(object_file, asset_file)?;
-          }
+        let start = Instant::now();
+        let file_map = asset_index.get_file_map();
+        let reconstruct = |(name, asset_obj): (&&String, &&AssetObject)| reconstruct_asset(&virtual_dir, &assets_dir, name, asset_obj);
+        if self.options.parallel_asset_reconstruction {
+          file_map.par_iter().try_for_each(reconstruct)?;
+        } else {
+          file_map.iter().try_for_each(reconstruct)?;
         }
+        info!("Reconstructed {} assets in {:?}", file_map.len(), start.elapsed());
 
         let mut last_used_file = File::create(virtual_dir.join(".lastused"))?;
         last_used_file.write_all(&Utc::now().to_rfc3339().as_bytes())?;
@@ -468,36 +679,31 @@ impl MinecraftGameRunner {
   fn create_arguments_substitutor(&self) -> impl Fn(String) -> String {
     let mut substitutor = ArgumentSubstitutorBuilder::new();
 
-    let classpath_separator = if OperatingSystem::get_current_platform() == OperatingSystem::Windows { ";" } else { ":" };
+    let classpath_separator = self.options.get_classpath_separator();
     let version_id = self.options.version.to_string();
     let local_version = self.get_local_version();
     let game_dir = &self.options.game_dir;
 
     let classpath = self.construct_classpath(self.local_version.as_ref().unwrap()).unwrap();
     let assets_dir = self.get_assets_dir();
-    let libraries_dir = game_dir.join("libraries");
-    let natives_dir = self.get_natives_dir();
+    let libraries_dir = VersionManager::get_library_dir(self.options.get_data_dir());
     let virtual_dir = self.get_virtual_dir();
 
     let launcher_opts = self.options.launcher_options.as_ref();
 
     let jar_id = local_version.get_jar().to_string();
-    let jar_path = game_dir.join("versions").join(&jar_id).join(format!("{}.jar", &jar_id));
+    let jar_path = self.options.get_data_dir().join("versions").join(&jar_id).join(format!("{}.jar", &jar_id));
 
     let asset_index_substitutions = {
       let mut map = HashMap::new();
 
       if let Some(asset_index) = self.get_asset_index() {
         for (asset_name, asset) in asset_index.get_file_map() {
-          let hash = asset.hash.to_string();
-          let asset_path = assets_dir
-            .join("objects")
-            .join(&hash[0..2])
-            .join(hash)
+          let asset_path = get_asset_object_path(&assets_dir, &asset.hash)
             .to_str()
             .unwrap()
             .to_string();
-          map.insert(format!("asset={asset_name}"), asset_path);
+          map.insert(asset_name.to_string(), asset_path);
         }
       }
 
@@ -515,9 +721,9 @@ impl MinecraftGameRunner {
     substitutor
       .add("profile_name", "")
       .add("version_name", &version_id)
-      .add("game_directory", game_dir.to_str().unwrap())
-      .add("game_assets", virtual_dir.to_str().unwrap())
-      .add("assets_root", assets_dir.to_str().unwrap())
+      .add_path("game_directory", game_dir)
+      .add_path("game_assets", virtual_dir)
+      .add_path("assets_root", &assets_dir)
       .add("assets_index_name", &local_version.asset_index.as_ref().unwrap().id)
       .add("version_type", &local_version.get_type().get_name());
 
@@ -529,7 +735,7 @@ impl MinecraftGameRunner {
       substitutor.add("resolution_height", "");
     }
 
-    substitutor.add("language", "en-us").add_all(asset_index_substitutions);
+    substitutor.add("language", "en-us").add_from_hashmap_with_prefix("asset=", asset_index_substitutions);
 
     if let Some(launcher_opts) = launcher_opts {
       substitutor.add("launcher_name", &launcher_opts.launcher_name).add("launcher_version", &launcher_opts.launcher_version);
@@ -537,18 +743,26 @@ impl MinecraftGameRunner {
       substitutor.add("launcher_name", "").add("launcher_version", "");
     }
 
-    substitutor
-      .add("natives_directory", natives_dir.to_str().unwrap())
+    if let NativeExtractionStrategy::JavaLibraryPath(dirs) = &self.options.native_extraction_strategy {
+      let library_path: Vec<String> = dirs
+        .iter()
+        .map(|dir| dir.to_string_lossy().replace("\\", "/"))
+        .collect();
+      substitutor.add("natives_directory", library_path.join(&classpath_separator));
+    } else {
+      substitutor.add_path("natives_directory", self.natives_dir.as_ref().unwrap());
+    }
 
+    substitutor
       .add("classpath", &classpath)
       .add("classpath_separator", classpath_separator)
-      .add("primary_jar", jar_path.to_str().unwrap());
+      .add_path("primary_jar", &jar_path);
 
     substitutor
       .add("clientid", "") // TODO: figure out
       .add("auth_xuid", ""); // TODO: only for msa
 
-    substitutor.add("library_directory", &libraries_dir.to_str().unwrap()); // Forge compatibility
+    substitutor.add_path("library_directory", &libraries_dir); // Forge compatibility
 
     substitutor.add_all(self.options.authentication.get_extra_substitutors());
     substitutor.add_all(self.options.substitutor_overrides.clone()); // Override if needed
@@ -556,10 +770,38 @@ impl MinecraftGameRunner {
     substitutor.build()
   }
 
+  // Classpath of the installed version in launch order, with where each entry comes from
+  pub fn get_classpath_with_sources(&self) -> Option<Vec<(PathBuf, LibrarySource)>> {
+    let local_version = self.version_manager.get_local_version(&self.options.version)?;
+    let os = OperatingSystem::get_current_platform();
+    let mut entries: Vec<_> = self.options.classpath_extras
+      .iter()
+      .map(|path| (path.clone(), LibrarySource::ClasspathExtra))
+      .collect();
+    entries.extend(local_version.get_classpath_with_sources(&os, self.options.get_data_dir(), self.feature_matcher.deref()));
+
+    let classpath = self.options.classpath_order.apply(entries.iter().map(|(path, _)| path.clone()).collect());
+    Some(
+      classpath
+        .into_iter()
+        .map(|path| {
+          // Paths only known to the classpath order were added by the launcher too
+          let source = entries
+            .iter()
+            .find(|(entry, _)| entry == &path)
+            .map_or(LibrarySource::ClasspathExtra, |(_, source)| source.clone());
+          (path, source)
+        })
+        .collect()
+    )
+  }
+
   fn construct_classpath(&self, local_version: &LocalVersionInfo) -> Result<String, MinecraftLauncherError> {
     let os = OperatingSystem::get_current_platform();
-    let separator = if os == OperatingSystem::Windows { ";" } else { ":" };
-    let classpath = local_version.get_classpath(&os, &self.options.game_dir, self.feature_matcher.deref());
+    let separator = self.options.get_classpath_separator();
+    let mut classpath = self.options.classpath_extras.clone();
+    classpath.extend(local_version.get_classpath(&os, self.options.get_data_dir(), self.feature_matcher.deref()));
+    let classpath = self.options.classpath_order.apply(classpath);
     for path in &classpath {
       if !path.is_file() {
         return Err(MinecraftLauncherError(format!("Classpath file not found: {}", path.display())));
@@ -570,11 +812,21 @@ impl MinecraftGameRunner {
         .iter()
         .map(|s| s.to_str().unwrap().to_string())
         .collect::<Vec<_>>()
-        .join(separator)
+        .join(&separator)
     )
   }
 }
 
+impl Drop for MinecraftGameRunner {
+  fn drop(&mut self) {
+    if self.game_pid.is_some_and(|pid| !process::is_process_running(pid)) {
+      if let Err(err) = self.remove_launch_dirs() {
+        warn!("Failed to clean up after the game: {err}");
+      }
+    }
+  }
+}
+
 pub struct ArgumentSubstitutorBuilder {
   map: HashMap<String, String>,
 }
@@ -589,6 +841,10 @@ impl ArgumentSubstitutorBuilder {
     self
   }
 
+  pub fn add_path(&mut self, key: impl AsRef<str>, path: &Path) -> &mut Self {
+    self.add(key, path.to_string_lossy().replace("\\", "/"))
+  }
+
   pub fn add_all(&mut self, map: HashMap<impl AsRef<str>, impl AsRef<str>>) -> &mut Self {
     for (key, value) in map {
       self.add(key, value);
@@ -596,6 +852,15 @@ impl ArgumentSubstitutorBuilder {
     self
   }
 
+  // Keys are joined to the prefix with a '.', unless the prefix already ends with a separator ("asset=")
+  pub fn add_from_hashmap_with_prefix(&mut self, prefix: &str, map: HashMap<String, String>) -> &mut Self {
+    let prefix = if prefix.ends_with(['.', '=']) { prefix.to_string() } else { format!("{prefix}.") };
+    for (key, value) in map {
+      self.add(format!("{prefix}{key}"), value);
+    }
+    self
+  }
+
   pub fn build(self) -> impl Fn(String) -> String {
     move |input| {
       let mut output = input;
@@ -1,13 +1,17 @@
-use std::{ path::PathBuf, collections::HashMap, fmt::Debug, sync::Arc };
+use std::{ env::consts::ARCH, path::{ Path, PathBuf }, collections::HashMap, fmt::Debug, process::ExitStatus, sync::Arc, time::Duration };
 
-use derive_builder::Builder;
+use derive_builder::{ Builder, UninitializedFieldError };
+use reqwest::Url;
 use serde_json::Value;
+use thiserror::Error;
 
 use crate::{
-  versions::{ info::MCVersion, json::rule::{ FeatureMatcher, RuleFeatureType } },
-  download_utils::ProxyOptions,
-  profile_manager::auth::UserAuthentication,
+  versions::{ info::MCVersion, json::{ JavaVersionInfo, rule::{ FeatureMatcher, OperatingSystem, RuleFeatureType } }, VersionManager },
+  java::JavaInstallationFinder,
+  download_utils::{ ProxyOptions, HttpClientConfig },
+  profile_manager::{ auth::UserAuthentication, Profile },
   progress_reporter::ProgressReporter,
+  process::DEFAULT_CAPTURE_OUTPUT_TIMEOUT,
 };
 
 #[derive(Debug, Clone, Copy)]
@@ -27,6 +31,102 @@ impl MinecraftResolution {
   }
 }
 
+const DEFAULT_JRE_ARGUMENTS_32BIT: &str =
+  "-Xmx2G -XX:+UnlockExperimentalVMOptions -XX:+UseG1GC -XX:G1NewSizePercent=20 -XX:G1ReservePercent=20 -XX:MaxGCPauseMillis=50 -XX:G1HeapRegionSize=32M";
+const DEFAULT_JRE_ARGUMENTS_64BIT: &str =
+  "-Xmx2G -XX:+UnlockExperimentalVMOptions -XX:+UseG1GC -XX:G1NewSizePercent=20 -XX:G1ReservePercent=20 -XX:MaxGCPauseMillis=50 -XX:G1HeapRegionSize=32M";
+const LOW_MEMORY_JRE_ARGUMENTS: &str = "-Xmx1G -XX:+UseSerialGC -XX:MinHeapFreeRatio=10 -XX:MaxHeapFreeRatio=30";
+const HIGH_PERFORMANCE_JRE_ARGUMENTS: &str =
+  "-Xms4G -Xmx4G -XX:+UnlockExperimentalVMOptions -XX:+UseG1GC -XX:G1NewSizePercent=30 -XX:G1MaxNewSizePercent=40 -XX:G1ReservePercent=20 -XX:MaxGCPauseMillis=50 -XX:G1HeapRegionSize=32M -XX:+ParallelRefProcEnabled -XX:+AlwaysPreTouch -XX:+DisableExplicitGC";
+
+// JVM arguments used when GameOptions::jvm_args isn't set
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum JvmArgsProfile {
+  #[default]
+  Default,
+  LowMemory,
+  HighPerformance,
+  Custom(Vec<String>),
+}
+
+impl JvmArgsProfile {
+  pub fn for_available_ram(ram_mb: u64) -> JvmArgsProfile {
+    match ram_mb {
+      0..=4095 => Self::LowMemory,
+      4096..=12287 => Self::Default,
+      _ => Self::HighPerformance,
+    }
+  }
+
+  pub fn get_arguments(&self) -> Vec<String> {
+    let args = match self {
+      Self::Default => if ARCH == "x86_64" { DEFAULT_JRE_ARGUMENTS_64BIT } else { DEFAULT_JRE_ARGUMENTS_32BIT },
+      Self::LowMemory => LOW_MEMORY_JRE_ARGUMENTS,
+      Self::HighPerformance => HIGH_PERFORMANCE_JRE_ARGUMENTS,
+      Self::Custom(args) => {
+        return args.clone();
+      }
+    };
+    args
+      .split(" ")
+      .map(|s| s.to_string())
+      .collect()
+  }
+}
+
+// Where the user JVM arguments (jvm_args or jvm_args_profile) go relative to the version json ones. The JVM keeps the
+// last value of a repeated flag, so whichever comes last decides the heap size when both set -Xmx/-Xms.
+// User arguments get the same ${...} substitution as the version json ones in every mode
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum JvmArgsPrecedence {
+  #[default]
+  UserFirst, // A modpack json setting -Xmx overrides the user's memory settings
+  VersionFirst, // The user's memory settings always win
+  UserOnly, // Version json arguments are dropped, the user arguments have to pass -cp ${classpath} themselves
+}
+
+impl JvmArgsPrecedence {
+  pub fn apply(&self, user_args: Vec<String>, version_args: Vec<String>) -> Vec<String> {
+    match self {
+      Self::UserFirst => user_args.into_iter().chain(version_args).collect(),
+      Self::VersionFirst => version_args.into_iter().chain(user_args).collect(),
+      Self::UserOnly => user_args,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ClasspathOrder {
+  #[default]
+  NativeFirst, // Order from the version json
+  CustomFirst(Vec<PathBuf>), // Listed paths first (e.g. a mod loader jar), then the remaining entries
+  Alphabetical,
+}
+
+impl ClasspathOrder {
+  pub fn apply(&self, mut classpath: Vec<PathBuf>) -> Vec<PathBuf> {
+    match self {
+      Self::NativeFirst => classpath,
+      Self::CustomFirst(first) => {
+        classpath.retain(|path| !first.contains(path));
+        first.iter().cloned().chain(classpath).collect()
+      }
+      Self::Alphabetical => {
+        classpath.sort();
+        classpath
+      }
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum NativeExtractionStrategy {
+  #[default]
+  TempDir, // A new directory per launch, removed on cleanup
+  FixedDir(PathBuf), // Extracted to the same directory on every launch and never removed
+  JavaLibraryPath(Vec<PathBuf>), // Already extracted natives, passed as java.library.path as they are
+}
+
 #[derive(Debug, Clone)]
 pub struct LauncherOptions {
   pub launcher_name: String,
@@ -39,14 +139,100 @@ impl LauncherOptions {
   }
 }
 
+type GameStartHook = Box<dyn Fn() + Send + Sync>;
+type GameExitHook = Box<dyn Fn(&ExitStatus) + Send + Sync>;
+
+// Callbacks for launchers that hide while the game runs, the crate never touches windows itself
+#[derive(Default)]
+pub struct LifecycleHooks {
+  on_game_start: Option<GameStartHook>,
+  on_game_exit: Option<GameExitHook>,
+}
+
+impl LifecycleHooks {
+  pub fn game_started(&self) {
+    if let Some(on_game_start) = &self.on_game_start {
+      on_game_start();
+    }
+  }
+
+  pub fn game_exited(&self, status: &ExitStatus) {
+    if let Some(on_game_exit) = &self.on_game_exit {
+      on_game_exit(status);
+    }
+  }
+}
+
+impl Debug for LifecycleHooks {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "LifecycleHooks")
+  }
+}
+
+#[derive(Debug, Error)]
+pub enum ValidationError {
+  #[error("Java executable not found: {0}")]
+  JavaNotFound(PathBuf),
+  #[error("Java executable is not executable: {0}")]
+  JavaNotExecutable(PathBuf),
+  #[error("Game directory is a file: {0}")]
+  GameDirIsFile(PathBuf),
+  #[error("Resolution must have both a width and a height")]
+  InvalidResolution,
+  #[error("Classpath extra not found: {0}")]
+  ClasspathExtraNotFound(PathBuf),
+  #[error("Launcher options must be set to launch the game")]
+  LauncherOptionsNotSet,
+  #[error("Classpath separator must be a non-alphanumeric ASCII character: {0:?}")]
+  InvalidClasspathSeparator(char),
+  #[error("authlib-injector jar not found: {0}")]
+  AuthlibInjectorNotFound(PathBuf),
+  #[error("Authentication server must use https: {0}")]
+  InsecureAuthServer(Url),
+  #[error("Custom icon must be an existing .icns file on macOS or .ico file on Windows: {0}")]
+  InvalidCustomIcon(PathBuf),
+  #[error("Max download attempts must be at least 1")]
+  InvalidMaxAttempts,
+  #[error("Max concurrent downloads must be at least 1")]
+  InvalidMaxConcurrentDownloads,
+}
+
+#[derive(Debug, Error)]
+pub enum GameOptionsBuildError {
+  #[error("Game option `{0}` must be set")]
+  UninitializedField(&'static str),
+  #[error("Invalid game options: {}", .0.iter().map(|err| err.to_string()).collect::<Vec<_>>().join(", "))]
+  Validation(Vec<ValidationError>),
+  #[error("No installed Java {required} runtime was found")]
+  NoCompatibleJavaFound {
+    required: u8,
+  },
+}
+
+impl From<UninitializedFieldError> for GameOptionsBuildError {
+  fn from(err: UninitializedFieldError) -> Self {
+    Self::UninitializedField(err.field_name())
+  }
+}
+
+impl From<Vec<ValidationError>> for GameOptionsBuildError {
+  fn from(errors: Vec<ValidationError>) -> Self {
+    Self::Validation(errors)
+  }
+}
+
 #[derive(Debug, Builder)]
-#[builder(pattern = "owned", setter(strip_option))]
+#[builder(pattern = "owned", setter(strip_option), build_fn(validate = "Self::validate", error = "GameOptionsBuildError"))]
 pub struct GameOptions {
   pub version: MCVersion,
   pub game_dir: PathBuf,
   #[builder(default)]
+  pub user_data_dir: Option<PathBuf>,
+  #[builder(default)]
   pub proxy: ProxyOptions,
   #[builder(default)]
+  pub http_client_config: HttpClientConfig,
+  #[builder(default)]
   pub resolution: Option<MinecraftResolution>,
   pub java_path: PathBuf,
   pub authentication: Box<dyn UserAuthentication + Send + Sync>,
@@ -55,18 +241,198 @@ pub struct GameOptions {
   #[builder(default)]
   pub substitutor_overrides: HashMap<String, String>,
   #[builder(default)]
-  pub jvm_args: Option<Vec<String>>,
+  pub jvm_args: Option<Vec<String>>, // Takes precedence over jvm_args_profile
+  #[builder(default)]
+  pub jvm_args_profile: JvmArgsProfile,
+  #[builder(default)]
+  pub jvm_args_precedence: JvmArgsPrecedence,
+  #[builder(default)]
+  pub auth_server: Option<Url>, // authlib-injector, only used together with authlib_injector_path
+  #[builder(default)]
+  pub authlib_injector_path: Option<PathBuf>,
+  #[builder(default)]
+  pub classpath_extras: Vec<PathBuf>,
+  #[builder(default)]
+  pub classpath_order: ClasspathOrder,
+  #[builder(default)]
+  pub extra_classpath_separator: Option<char>, // Overrides the platform separator, for Wine/WSL2 setups
+  #[builder(default)]
+  pub window_title: Option<String>,
+  #[builder(default)]
+  pub screenshot_dir: Option<PathBuf>,
+  #[builder(default)]
+  pub custom_icon: Option<PathBuf>, // Dock icon on macOS (.icns), taskbar icon on Windows (.ico)
+  #[builder(default)]
+  pub crash_report_archive_dir: Option<PathBuf>, // Reports are archived to <dir>/<version>/<timestamp>.txt
+  #[builder(default)]
+  pub no_gui: bool,
+  #[builder(default)]
+  pub rendering_backend: Option<String>,
+  // Falls back to an unsandboxed launch if bwrap/sandbox-exec/runas isn't available. On windows runas exits once
+  // the game has started, so the returned process can't be waited on
+  #[builder(default)]
+  pub sandbox: bool,
+  #[builder(default)]
+  pub capture_output: bool,
+  #[builder(default = "Some(DEFAULT_CAPTURE_OUTPUT_TIMEOUT)")]
+  pub capture_output_timeout: Option<Duration>, // How long dropping the process waits for its output to be captured
+  #[builder(default)]
+  pub parallel_asset_reconstruction: bool,
+  #[builder(default)]
+  pub force_download_all: bool,
+  #[builder(default)]
+  pub native_extraction_strategy: NativeExtractionStrategy,
+  #[builder(default)]
+  pub preserve_natives_on_error: bool, // Keep a partially extracted natives dir instead of removing it
+  #[builder(default)]
+  pub natives_debug_info: bool, // Writes manifest.txt listing every extracted native and its source jar
+  #[builder(default)]
+  pub debug_args: bool, // Prints the (redacted) launch command to stderr regardless of the log level
 
   #[builder(default, setter(custom))]
   pub progress_reporter: Arc<ProgressReporter>,
 
+  #[builder(default)]
+  pub reconnect_attempts: u32, // Relaunches after a clean exit, see MinecraftGameRunner::launch_and_wait
+  #[builder(default)]
+  pub close_launcher_on_game_start: bool,
+  #[builder(default)]
+  pub reopen_launcher_on_game_exit: bool,
+  #[builder(default, setter(custom))]
+  pub lifecycle_hooks: LifecycleHooks,
+
+  // At least 1, more than ~64 tends to get throttled by the CDN
   #[builder(default = "16")]
   pub max_concurrent_downloads: u16,
+  // At least 1 (a single try without retries), more than ~10 mostly delays reporting a broken download
   #[builder(default = "5")]
   pub max_download_attempts: u8,
+  // Applies to downloads without a timeout of their own, e.g. libraries without downloadTimeout
+  #[builder(default)]
+  pub download_timeout: Option<Duration>,
+}
+
+impl GameOptions {
+  pub fn get_data_dir(&self) -> &PathBuf {
+    self.user_data_dir.as_ref().unwrap_or(&self.game_dir)
+  }
+
+  pub fn get_classpath_separator(&self) -> String {
+    match self.extra_classpath_separator {
+      Some(separator) => separator.to_string(),
+      None => (if OperatingSystem::get_current_platform() == OperatingSystem::Windows { ";" } else { ":" }).to_string(),
+    }
+  }
+
+  pub fn launcher_options_required(&self) -> Result<&LauncherOptions, ValidationError> {
+    self.launcher_options.as_ref().ok_or(ValidationError::LauncherOptionsNotSet)
+  }
 }
 
 impl GameOptionsBuilder {
+  // Authentication and the progress reporter aren't part of a profile and still have to be set
+  pub fn from_profile(profile: &Profile) -> Self {
+    let mut builder = Self::default();
+    if let Some(version) = &profile.last_version_id {
+      builder = builder.version(version.clone());
+    }
+    if let Some(game_dir) = &profile.game_dir {
+      builder = builder.game_dir(game_dir.clone());
+    }
+    if let Some(java_dir) = &profile.java_dir {
+      builder = builder.java_path(java_dir.clone());
+    }
+    if let Some(java_args) = &profile.java_args {
+      builder = builder.jvm_args(java_args.split_whitespace().map(|arg| arg.to_string()).collect());
+    }
+    if let Some(resolution) = &profile.resolution {
+      builder = builder.resolution(MinecraftResolution::new(resolution.width, resolution.height));
+    }
+    builder
+  }
+
+  // Picks the newest installed JVM matching the Java version required by the target version
+  pub async fn detect_java(self) -> Result<Self, GameOptionsBuildError> {
+    let required = self.get_required_java_version().await;
+    match JavaInstallationFinder::find_newest(required) {
+      Some(installation) => Ok(self.java_path(installation.path)),
+      None => Err(GameOptionsBuildError::NoCompatibleJavaFound { required }),
+    }
+  }
+
+  // Versions that aren't installed yet fall back to the default runtime
+  async fn get_required_java_version(&self) -> u8 {
+    let default_version = JavaVersionInfo::default().major_version as u8;
+    let (Some(data_dir), Some(version)) = (self.user_data_dir.clone().flatten().or_else(|| self.game_dir.clone()), &self.version) else {
+      return default_version;
+    };
+    let resolution = self.resolution.flatten();
+    let version_manager = VersionManager::new(data_dir, Box::new(MinecraftFeatureMatcher::new(false, resolution)));
+    if version_manager.refresh_local().is_err() {
+      return default_version;
+    }
+    version_manager.get_java_version_requirement(version).await.unwrap_or(default_version)
+  }
+
+  pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+    let mut errors = vec![];
+    if let Some(java_path) = &self.java_path {
+      if !java_path.is_file() {
+        errors.push(ValidationError::JavaNotFound(java_path.clone()));
+      } else if !is_executable(java_path) {
+        errors.push(ValidationError::JavaNotExecutable(java_path.clone()));
+      }
+    }
+    if let Some(game_dir) = &self.game_dir {
+      if game_dir.is_file() {
+        errors.push(ValidationError::GameDirIsFile(game_dir.clone()));
+      }
+    }
+    if let Some(Some(resolution)) = &self.resolution {
+      if resolution.width() == 0 || resolution.height() == 0 {
+        errors.push(ValidationError::InvalidResolution);
+      }
+    }
+    if let Some(classpath_extras) = &self.classpath_extras {
+      for path in classpath_extras.iter().filter(|path| !path.is_file()) {
+        errors.push(ValidationError::ClasspathExtraNotFound(path.clone()));
+      }
+    }
+    if let Some(Some(authlib_injector_path)) = &self.authlib_injector_path {
+      if !authlib_injector_path.is_file() {
+        errors.push(ValidationError::AuthlibInjectorNotFound(authlib_injector_path.clone()));
+      }
+    }
+    if let Some(Some(auth_server)) = &self.auth_server {
+      if auth_server.scheme() != "https" {
+        errors.push(ValidationError::InsecureAuthServer(auth_server.clone()));
+      }
+    }
+    if let Some(Some(separator)) = self.extra_classpath_separator {
+      if !separator.is_ascii() || separator.is_ascii_alphanumeric() {
+        errors.push(ValidationError::InvalidClasspathSeparator(separator));
+      }
+    }
+    if let Some(Some(custom_icon)) = &self.custom_icon {
+      let extension = match OperatingSystem::get_current_platform() {
+        OperatingSystem::Osx => Some("icns"),
+        OperatingSystem::Windows => Some("ico"),
+        _ => None,
+      };
+      let has_extension = extension.is_none_or(|extension| custom_icon.extension().is_some_and(|ext| ext.eq_ignore_ascii_case(extension)));
+      if !custom_icon.is_file() || !has_extension {
+        errors.push(ValidationError::InvalidCustomIcon(custom_icon.clone()));
+      }
+    }
+    if self.max_download_attempts == Some(0) {
+      errors.push(ValidationError::InvalidMaxAttempts);
+    }
+    if self.max_concurrent_downloads == Some(0) {
+      errors.push(ValidationError::InvalidMaxConcurrentDownloads);
+    }
+    if errors.is_empty() { Ok(()) } else { Err(errors) }
+  }
+
   pub fn progress_reporter(self, progress_reporter: ProgressReporter) -> Self {
     self.progress_reporter_arc(&Arc::new(progress_reporter))
   }
@@ -75,6 +441,29 @@ impl GameOptionsBuilder {
     self.progress_reporter = Some(Arc::clone(arc));
     self
   }
+
+  // Called when the game starts if close_launcher_on_game_start is set
+  pub fn on_game_start(mut self, on_game_start: impl Fn() + Send + Sync + 'static) -> Self {
+    self.lifecycle_hooks.get_or_insert_with(LifecycleHooks::default).on_game_start = Some(Box::new(on_game_start));
+    self
+  }
+
+  // Called when the game exits if reopen_launcher_on_game_exit is set
+  pub fn on_game_exit(mut self, on_game_exit: impl Fn(&ExitStatus) + Send + Sync + 'static) -> Self {
+    self.lifecycle_hooks.get_or_insert_with(LifecycleHooks::default).on_game_exit = Some(Box::new(on_game_exit));
+    self
+  }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+  use std::os::unix::fs::PermissionsExt;
+  path.metadata().is_ok_and(|metadata| metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+  path.is_file()
 }
 
 #[derive(Debug, Clone)]
@@ -99,3 +488,132 @@ impl FeatureMatcher for MinecraftFeatureMatcher {
     return false;
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use crate::profile_manager::auth::OfflineUserAuthentication;
+
+  use super::*;
+
+  #[test]
+  fn test_jvm_args_profile() {
+    assert_eq!(JvmArgsProfile::for_available_ram(2048), JvmArgsProfile::LowMemory);
+    assert_eq!(JvmArgsProfile::for_available_ram(8192), JvmArgsProfile::Default);
+    assert_eq!(JvmArgsProfile::for_available_ram(16384), JvmArgsProfile::HighPerformance);
+    assert!(JvmArgsProfile::Default.get_arguments().contains(&"-Xmx2G".to_string()));
+    let custom = vec!["-Xmx3G".to_string()];
+    assert_eq!(JvmArgsProfile::Custom(custom.clone()).get_arguments(), custom);
+  }
+
+  #[test]
+  fn test_validate_download_limits() {
+    assert!(GameOptionsBuilder::default().validate().is_ok());
+    assert!(GameOptionsBuilder::default().max_download_attempts(1).max_concurrent_downloads(1).validate().is_ok());
+    let errors = GameOptionsBuilder::default().max_download_attempts(0).max_concurrent_downloads(0).validate().unwrap_err();
+    assert!(matches!(errors.as_slice(), [ValidationError::InvalidMaxAttempts, ValidationError::InvalidMaxConcurrentDownloads]));
+  }
+
+  #[test]
+  fn test_validate_custom_icon() {
+    let missing_icon = std::env::temp_dir().join("mclc-test-missing-icon.ico");
+    let errors = GameOptionsBuilder::default().custom_icon(missing_icon.clone()).validate().unwrap_err();
+    assert!(matches!(errors.as_slice(), [ValidationError::InvalidCustomIcon(path)] if path == &missing_icon));
+  }
+
+  #[test]
+  fn test_validate_paths() {
+    let file = std::env::temp_dir().join("mclc-test-validate-file");
+    std::fs::write(&file, []).unwrap();
+    let missing = std::env::temp_dir().join("mclc-test-validate-missing");
+    let errors = GameOptionsBuilder::default()
+      .java_path(missing.clone())
+      .game_dir(file.clone())
+      .classpath_extras(vec![missing.clone()])
+      .authlib_injector_path(missing.clone())
+      .validate()
+      .unwrap_err();
+    assert!(
+      matches!(errors.as_slice(), [
+        ValidationError::JavaNotFound(java),
+        ValidationError::GameDirIsFile(game_dir),
+        ValidationError::ClasspathExtraNotFound(extra),
+        ValidationError::AuthlibInjectorNotFound(authlib_injector),
+      ] if java == &missing && game_dir == &file && extra == &missing && authlib_injector == &missing)
+    );
+
+    #[cfg(unix)]
+    assert!(matches!(GameOptionsBuilder::default().java_path(file.clone()).validate().unwrap_err().as_slice(), [ValidationError::JavaNotExecutable(_)]));
+  }
+
+  #[test]
+  fn test_validate_values() {
+    let errors = GameOptionsBuilder::default()
+      .resolution(MinecraftResolution::new(854, 0))
+      .auth_server(Url::parse("http://example.com/api/yggdrasil").unwrap())
+      .extra_classpath_separator('a')
+      .validate()
+      .unwrap_err();
+    assert!(
+      matches!(errors.as_slice(), [
+        ValidationError::InvalidResolution,
+        ValidationError::InsecureAuthServer(_),
+        ValidationError::InvalidClasspathSeparator('a'),
+      ])
+    );
+    assert!(GameOptionsBuilder::default().extra_classpath_separator(';').validate().is_ok());
+  }
+
+  fn valid_builder() -> GameOptionsBuilder {
+    GameOptionsBuilder::default()
+      .version(MCVersion::new("1.20.4"))
+      .game_dir(std::env::temp_dir().join("mclc-test-options"))
+      .java_path(std::env::current_exe().unwrap())
+      .authentication(Box::new(OfflineUserAuthentication::new("Steve")))
+  }
+
+  #[test]
+  fn test_build_errors() {
+    let err = GameOptionsBuilder::default().version(MCVersion::new("1.20.4")).build().unwrap_err();
+    assert!(matches!(err, GameOptionsBuildError::UninitializedField("game_dir")));
+    assert_eq!(err.to_string(), "Game option `game_dir` must be set");
+
+    let err = valid_builder().max_download_attempts(0).max_concurrent_downloads(0).build().unwrap_err();
+    assert!(matches!(&err, GameOptionsBuildError::Validation(errors) if errors.len() == 2));
+    assert_eq!(
+      err.to_string(),
+      "Invalid game options: Max download attempts must be at least 1, Max concurrent downloads must be at least 1"
+    );
+  }
+
+  #[test]
+  fn test_launcher_options_required() {
+    let options = valid_builder().build().unwrap();
+    assert!(matches!(options.launcher_options_required(), Err(ValidationError::LauncherOptionsNotSet)));
+
+    let options = valid_builder().launcher_options(LauncherOptions::new("Test Launcher", "v1.0.0")).build().unwrap();
+    assert!(options.launcher_options_required().is_ok());
+  }
+
+  #[test]
+  fn test_jvm_args_precedence() {
+    let user_args = vec!["-Xmx4G".to_string()];
+    let version_args = vec!["-Xmx2G".to_string(), "-cp".to_string()];
+    assert_eq!(JvmArgsPrecedence::UserFirst.apply(user_args.clone(), version_args.clone()), ["-Xmx4G", "-Xmx2G", "-cp"]);
+    assert_eq!(JvmArgsPrecedence::VersionFirst.apply(user_args.clone(), version_args.clone()), ["-Xmx2G", "-cp", "-Xmx4G"]);
+    assert_eq!(JvmArgsPrecedence::UserOnly.apply(user_args, version_args), ["-Xmx4G"]);
+  }
+
+  #[test]
+  fn test_classpath_order() {
+    let classpath = vec![PathBuf::from("b.jar"), PathBuf::from("forge.jar"), PathBuf::from("a.jar")];
+    assert_eq!(ClasspathOrder::NativeFirst.apply(classpath.clone()), classpath);
+    assert_eq!(
+      ClasspathOrder::Alphabetical.apply(classpath.clone()),
+      vec![PathBuf::from("a.jar"), PathBuf::from("b.jar"), PathBuf::from("forge.jar")]
+    );
+    assert_eq!(
+      ClasspathOrder::CustomFirst(vec![PathBuf::from("forge.jar"), PathBuf::from("c.jar")]).apply(classpath),
+      vec![PathBuf::from("forge.jar"), PathBuf::from("c.jar"), PathBuf::from("b.jar"), PathBuf::from("a.jar")]
+    );
+  }
+}
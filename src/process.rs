@@ -1,16 +1,88 @@
-use std::{ process::{ Child, ChildStdout, ChildStderr, Command, Stdio }, io::BufReader, path::PathBuf, os::windows::process::CommandExt };
+use std::{
+  process::{ Child, Command, ExitStatus, Stdio },
+  io::{ self, BufReader, Read, BufRead },
+  path::{ PathBuf, Path },
+  fs,
+  env,
+  collections::{ HashMap, VecDeque },
+  ffi::OsString,
+  sync::{ Arc, Mutex, mpsc::{ self, Receiver, TrySendError } },
+  thread::{ self, JoinHandle },
+  time::{ Duration, Instant, SystemTime },
+  os::windows::process::CommandExt,
+};
 
-use crate::versions::json::rule::OperatingSystem;
+use chrono::Utc;
+use futures::{ stream, Stream };
+use log::{ info, warn };
+use serde::{ Deserialize, Serialize };
+use sysinfo::{ Pid, ProcessStatus, System };
+use thiserror::Error;
+
+use crate::{ versions::json::rule::OperatingSystem, MinecraftLauncherError };
+
+type ProcessOutput = BufReader<Box<dyn Read + Send + Sync>>;
+
+const MAX_OUTPUT_LOG_LINES: usize = 10_000;
+const FORWARDED_LINES_BUFFER: usize = 1024;
+const WINDOW_POLL_INTERVAL: Duration = Duration::from_millis(250);
+const RESOURCE_USAGE_CACHE_TIME: Duration = Duration::from_millis(500);
+const CAPTURE_OUTPUT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+pub const DEFAULT_CAPTURE_OUTPUT_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Error)]
+pub enum WaitError {
+  #[error("Timed out waiting for the game window")]
+  Timeout,
+  #[error("The game exited before its window was shown")]
+  ProcessExited,
+  #[error("Waiting for the game window is not supported on {0}")]
+  Unsupported(String),
+  #[error("{0}")]
+  Io(#[from] io::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum SandboxError {
+  #[error("Sandboxing is not supported on {0}")]
+  Unsupported(String),
+  #[error("Couldn't find {0}, which is needed to sandbox the game")]
+  NotFound(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProcessResourceUsage {
+  pub rss_bytes: u64,
+  pub cpu_percent: f64, // Of a single core, since the previous poll
+  pub thread_count: Option<u32>, // Only known on linux
+}
 
 pub struct GameProcess {
   child: Child,
-  stdout: BufReader<ChildStdout>,
-  stderr: BufReader<ChildStderr>,
+  stdout: ProcessOutput,
+  stderr: ProcessOutput,
+  pub output_log: Arc<Mutex<VecDeque<String>>>,
+  started_at: SystemTime,
+  game_dir: PathBuf,
+  crash_report_archive_dir: Option<PathBuf>, // Already points to the version's own subdirectory
+  resource_usage_cache: Mutex<Option<(Instant, ProcessResourceUsage)>>,
+  system: Mutex<System>, // Kept between polls, cpu usage is measured since the previous refresh
+  capture_threads: Vec<JoinHandle<()>>,
+  capture_output_timeout: Option<Duration>, // How long drop waits for the capture threads to drain the output
 }
 
 impl GameProcess {
   pub fn new(java_path: &PathBuf, game_dir: &PathBuf, args: Vec<String>) -> Self {
-    let mut child = Command::new(java_path)
+    Self::spawn(java_path, game_dir, args, None)
+  }
+
+  // The environment is inherited when `env` is None
+  fn spawn(java_path: &PathBuf, game_dir: &PathBuf, args: Vec<String>, env: Option<&HashMap<OsString, OsString>>) -> Self {
+    let mut command = Command::new(java_path);
+    if let Some(env) = env {
+      command.env_clear().envs(env);
+    }
+    let mut child = command
       .stdout(Stdio::piped())
       .stderr(Stdio::piped())
       .current_dir(game_dir)
@@ -19,21 +91,45 @@ impl GameProcess {
       .spawn()
       .unwrap();
     Self {
-      stdout: BufReader::new(child.stdout.take().unwrap()),
-      stderr: BufReader::new(child.stderr.take().unwrap()),
+      stdout: BufReader::new(Box::new(child.stdout.take().unwrap())),
+      stderr: BufReader::new(Box::new(child.stderr.take().unwrap())),
       child,
+      output_log: Arc::new(Mutex::new(VecDeque::new())),
+      started_at: SystemTime::now(),
+      game_dir: game_dir.clone(),
+      crash_report_archive_dir: None,
+      resource_usage_cache: Mutex::new(None),
+      system: Mutex::new(System::new()),
+      capture_threads: vec![],
+      capture_output_timeout: Some(DEFAULT_CAPTURE_OUTPUT_TIMEOUT),
     }
   }
 
+  fn with_output_capture(mut self) -> Self {
+    let empty = || BufReader::new(Box::new(io::empty()) as Box<dyn Read + Send + Sync>);
+    let stdout = std::mem::replace(&mut self.stdout, empty());
+    let stderr = std::mem::replace(&mut self.stderr, empty());
+    let (stdout, stdout_thread) = capture_output(stdout, &self.output_log);
+    let (stderr, stderr_thread) = capture_output(stderr, &self.output_log);
+    self.stdout = BufReader::new(Box::new(stdout));
+    self.stderr = BufReader::new(Box::new(stderr));
+    self.capture_threads = vec![stdout_thread, stderr_thread];
+    self
+  }
+
+  pub fn pid(&self) -> u32 {
+    self.child.id()
+  }
+
   pub fn inner(&self) -> &Child {
     &self.child
   }
 
-  pub fn stdout(&mut self) -> &mut BufReader<ChildStdout> {
+  pub fn stdout(&mut self) -> &mut ProcessOutput {
     &mut self.stdout
   }
 
-  pub fn stderr(&mut self) -> &mut BufReader<ChildStderr> {
+  pub fn stderr(&mut self) -> &mut ProcessOutput {
     &mut self.stderr
   }
 
@@ -44,12 +140,357 @@ impl GameProcess {
       Err(_) => Some(1),
     }
   }
+
+  pub fn crash_log(&mut self) -> Vec<String> {
+    match self.exit_status() {
+      Some(code) if code != 0 => self.output_log.lock().unwrap().iter().cloned().collect(),
+      _ => vec![],
+    }
+  }
+
+  pub fn wait_for_window_ready(&mut self, timeout: Duration) -> Result<(), WaitError> {
+    let start = Instant::now();
+    loop {
+      if has_visible_window(self.pid())? {
+        return Ok(());
+      }
+      if self.exit_status().is_some() {
+        return Err(WaitError::ProcessExited);
+      }
+      if start.elapsed() >= timeout {
+        return Err(WaitError::Timeout);
+      }
+      thread::sleep(WINDOW_POLL_INTERVAL);
+    }
+  }
+
+  // Waits for the game to exit and returns the crash report it wrote, if any.
+  // The report is copied to the archive dir first so the next crash can't overwrite it
+  pub fn wait_with_crash_detection(&mut self) -> Result<Option<PathBuf>, io::Error> {
+    self.child.wait()?;
+
+    let Some(crash_report) = self.find_crash_report() else {
+      return Ok(None);
+    };
+    let Some(archive_dir) = &self.crash_report_archive_dir else {
+      return Ok(Some(crash_report));
+    };
+    fs::create_dir_all(archive_dir)?;
+    let archived = archive_dir.join(format!("{}.txt", Utc::now().format("%Y-%m-%d_%H.%M.%S")));
+    fs::copy(&crash_report, &archived)?;
+    info!("Archived crash report to {}", archived.display());
+    Ok(Some(archived))
+  }
+
+  // Newest report written to crash-reports since the game was started
+  fn find_crash_report(&self) -> Option<PathBuf> {
+    let crash_reports_dir = self.game_dir.join("crash-reports");
+    fs::read_dir(crash_reports_dir)
+      .ok()?
+      .filter_map(|entry| entry.ok())
+      .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+      .filter(|(modified, path)| *modified >= self.started_at && path.extension().is_some_and(|ext| ext == "txt"))
+      .max_by_key(|(modified, _)| *modified)
+      .map(|(_, path)| path)
+  }
+
+  pub fn list_crash_reports(&self) -> Vec<PathBuf> {
+    let Some(Ok(entries)) = self.crash_report_archive_dir.as_ref().map(fs::read_dir) else {
+      return vec![];
+    };
+    let mut reports: Vec<PathBuf> = entries
+      .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+      .filter(|path| path.is_file())
+      .collect();
+    reports.sort();
+    reports
+  }
+
+  pub fn try_wait(&mut self) -> Result<Option<ExitStatus>, io::Error> {
+    self.child.try_wait()
+  }
+
+  // Kills the game and reaps it, a killed child lingers as a zombie until it's waited on
+  pub fn kill(&mut self) -> io::Result<()> {
+    self.child.kill()?;
+    self.child.wait()?;
+    Ok(())
+  }
+
+  // None once the process has exited
+  pub fn resource_usage(&self) -> Option<ProcessResourceUsage> {
+    let mut cache = self.resource_usage_cache.lock().unwrap();
+    if let Some((read_at, usage)) = *cache {
+      if read_at.elapsed() < RESOURCE_USAGE_CACHE_TIME {
+        return Some(usage);
+      }
+    }
+    let usage = read_resource_usage(&mut self.system.lock().unwrap(), self.pid());
+    *cache = usage.map(|usage| (Instant::now(), usage));
+    usage
+  }
+
+  // Blocks the current thread until the process exits
+  pub fn exit_status_blocking(&mut self) -> io::Result<ExitStatus> {
+    self.drain_uncaptured_output();
+    self.child.wait()
+  }
+
+  pub async fn wait(&mut self) -> io::Result<ExitStatus> {
+    self.drain_uncaptured_output();
+    loop {
+      if let Some(status) = self.try_wait()? {
+        return Ok(status);
+      }
+      tokio::time::sleep(WINDOW_POLL_INTERVAL).await;
+    }
+  }
+
+  // Nobody reads the pipes when output isn't captured, a chatty game would fill them and never exit.
+  // Whatever the caller didn't read yet is discarded
+  fn drain_uncaptured_output(&mut self) {
+    if !self.capture_threads.is_empty() {
+      return;
+    }
+    let empty = || BufReader::new(Box::new(io::empty()) as Box<dyn Read + Send + Sync>);
+    for mut output in [std::mem::replace(&mut self.stdout, empty()), std::mem::replace(&mut self.stderr, empty())] {
+      thread::spawn(move || io::copy(&mut output, &mut io::sink()));
+    }
+  }
+
+  // Ends once the process has exited
+  pub fn watch_resource_usage(&self, interval: Duration) -> impl Stream<Item = ProcessResourceUsage> {
+    stream::unfold((System::new(), self.pid(), true), move |(mut system, pid, first)| async move {
+      if !first {
+        tokio::time::sleep(interval).await;
+      }
+      let (system, usage) = tokio::task::spawn_blocking(move || {
+        let usage = read_resource_usage(&mut system, pid);
+        (system, usage)
+      }).await.ok()?;
+      Some((usage?, (system, pid, false)))
+    })
+  }
+
+  // Stops tracking the process without killing it
+  pub fn detach(self) -> u32 {
+    self.pid()
+  }
+}
+
+impl Drop for GameProcess {
+  // The pipes only reach EOF once the process has exited, waiting on a running game would just stall the drop
+  fn drop(&mut self) {
+    let Some(timeout) = self.capture_output_timeout else {
+      return;
+    };
+    if self.capture_threads.is_empty() || !matches!(self.try_wait(), Ok(Some(_))) {
+      return;
+    }
+    let start = Instant::now();
+    while !self.capture_threads.iter().all(|thread| thread.is_finished()) {
+      if start.elapsed() >= timeout {
+        warn!("Timed out waiting for the game output to be captured");
+        return;
+      }
+      thread::sleep(CAPTURE_OUTPUT_POLL_INTERVAL);
+    }
+  }
+}
+
+// A game started outside of the launcher. It isn't our child, so neither its output nor its exit code can be read
+pub struct AttachedProcess {
+  pid: u32,
+}
+
+impl AttachedProcess {
+  pub fn attach(pid: u32) -> Result<Self, Box<dyn std::error::Error>> {
+    if !is_process_running(pid) {
+      Err(MinecraftLauncherError(format!("No running process with pid {pid}")))?;
+    }
+    Ok(Self { pid })
+  }
+
+  pub fn pid(&self) -> u32 {
+    self.pid
+  }
+
+  pub fn is_running(&self) -> bool {
+    is_process_running(self.pid)
+  }
+
+  // Blocks until the process is gone, it's reaped by its own parent
+  pub fn kill(&self) -> io::Result<()> {
+    kill_process(self.pid)?;
+    while self.is_running() {
+      thread::sleep(WINDOW_POLL_INTERVAL);
+    }
+    Ok(())
+  }
+
+  // Stops tracking the process without killing it
+  pub fn detach(self) -> u32 {
+    self.pid
+  }
+}
+
+// Reads the stream on a background thread so the output is captured even if nobody listens to it.
+// Lines are forwarded to the returned reader on a best-effort basis, they're dropped (and counted) if the reader falls behind.
+fn capture_output(mut output: ProcessOutput, output_log: &Arc<Mutex<VecDeque<String>>>) -> (ForwardedOutput, JoinHandle<()>) {
+  let output_log = Arc::clone(output_log);
+  let (sender, receiver) = mpsc::sync_channel(FORWARDED_LINES_BUFFER);
+  let handle = thread::spawn(move || {
+    let mut line = vec![];
+    let mut dropped_lines = 0;
+    while let Ok(read) = output.read_until(b'\n', &mut line) {
+      if read == 0 {
+        break;
+      }
+      {
+        let mut output_log = output_log.lock().unwrap();
+        if output_log.len() >= MAX_OUTPUT_LOG_LINES {
+          output_log.pop_front();
+        }
+        output_log.push_back(String::from_utf8_lossy(&line).trim_end().to_string());
+      }
+      if let Err(TrySendError::Full(_)) = sender.try_send(std::mem::take(&mut line)) {
+        dropped_lines += 1;
+      }
+    }
+    if dropped_lines > 0 {
+      warn!("Dropped {dropped_lines} output line(s) the reader couldn't keep up with, they're still in the output log");
+    }
+  });
+  (ForwardedOutput { receiver: Mutex::new(receiver), buffer: vec![], position: 0 }, handle)
+}
+
+struct ForwardedOutput {
+  receiver: Mutex<Receiver<Vec<u8>>>,
+  buffer: Vec<u8>,
+  position: usize,
+}
+
+impl Read for ForwardedOutput {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    if self.position >= self.buffer.len() {
+      match self.receiver.lock().unwrap().recv() {
+        Ok(line) => {
+          self.buffer = line;
+          self.position = 0;
+        }
+        Err(_) => return Ok(0),
+      }
+    }
+    let len = buf.len().min(self.buffer.len() - self.position);
+    buf[..len].copy_from_slice(&self.buffer[self.position..self.position + len]);
+    self.position += len;
+    Ok(len)
+  }
+}
+
+pub(crate) fn is_process_running(pid: u32) -> bool {
+  match OperatingSystem::get_current_platform() {
+    OperatingSystem::Linux => Path::new("/proc").join(pid.to_string()).is_dir(),
+    OperatingSystem::Windows =>
+      Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {pid}"), "/NH"])
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).contains(&pid.to_string())),
+    _ =>
+      Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success()),
+  }
+}
+
+pub(crate) fn kill_process(pid: u32) -> io::Result<()> {
+  let status = match OperatingSystem::get_current_platform() {
+    OperatingSystem::Windows => Command::new("taskkill").args(["/F", "/PID", &pid.to_string()]).stdout(Stdio::null()).status()?,
+    _ => Command::new("kill").args(["-9", &pid.to_string()]).stderr(Stdio::null()).status()?,
+  };
+  if !status.success() && is_process_running(pid) {
+    return Err(io::Error::other(format!("Failed to kill process {pid}")));
+  }
+  Ok(())
+}
+
+// Zombies count as exited
+fn read_resource_usage(system: &mut System, pid: u32) -> Option<ProcessResourceUsage> {
+  let pid = Pid::from_u32(pid);
+  if !system.refresh_process(pid) {
+    return None;
+  }
+  let process = system.process(pid).filter(|process| process.status() != ProcessStatus::Zombie)?;
+  Some(ProcessResourceUsage {
+    rss_bytes: process.memory(),
+    cpu_percent: process.cpu_usage() as f64,
+    thread_count: count_threads(pid),
+  })
+}
+
+// sysinfo only lists the threads on a full refresh of every process
+fn count_threads(pid: Pid) -> Option<u32> {
+  if OperatingSystem::get_current_platform() != OperatingSystem::Linux {
+    return None;
+  }
+  let tasks = fs::read_dir(Path::new("/proc").join(pid.to_string()).join("task")).ok()?;
+  Some(tasks.count() as u32)
+}
+
+fn has_visible_window(pid: u32) -> Result<bool, WaitError> {
+  match OperatingSystem::get_current_platform() {
+    OperatingSystem::Linux => {
+      let status = Command::new("xdotool")
+        .args(["search", "--onlyvisible", "--pid", &pid.to_string(), "--name", "Minecraft"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+      Ok(status.success())
+    }
+    OperatingSystem::Windows => {
+      // The last column is the main window title, "N/A" while the process has no window
+      let output = Command::new("tasklist")
+        .args(["/V", "/FO", "CSV", "/NH", "/FI", &format!("PID eq {pid}")])
+        .output()?;
+      let output = String::from_utf8_lossy(&output.stdout);
+      Ok(
+        output
+          .lines()
+          .filter_map(|line| line.trim_end().rsplit_once("\",\"").map(|(_, title)| title.trim_end_matches('"')))
+          .any(|title| title != "N/A")
+      )
+    }
+    os => Err(WaitError::Unsupported(os.get_name())),
+  }
+}
+
+// Everything needed to spawn the game again without recomputing the arguments
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DryRunReport {
+  pub java_path: Option<PathBuf>,
+  pub directory: Option<PathBuf>,
+  pub arguments: Vec<String>,
+  pub sandbox: bool,
+  pub capture_output: bool,
+  #[serde(default)]
+  pub crash_report_archive_dir: Option<PathBuf>,
+  #[serde(default)]
+  pub version_argument_count: Option<usize>, // Arguments the version json provides once its rules are evaluated
 }
 
 pub struct GameProcessBuilder {
   arguments: Vec<String>,
   java_path: Option<PathBuf>,
   directory: Option<PathBuf>,
+  sandbox: bool,
+  capture_output: bool,
+  capture_output_timeout: Option<Duration>,
+  crash_report_archive_dir: Option<PathBuf>,
+  version_argument_count: Option<usize>,
+  env: Option<HashMap<OsString, OsString>>, // Replaces the inherited environment when set
 }
 
 impl GameProcessBuilder {
@@ -58,11 +499,43 @@ impl GameProcessBuilder {
       java_path: None,
       arguments: vec![],
       directory: None,
+      sandbox: false,
+      capture_output: false,
+      capture_output_timeout: Some(DEFAULT_CAPTURE_OUTPUT_TIMEOUT),
+      crash_report_archive_dir: None,
+      version_argument_count: None,
+      env: None,
     }
   }
 
-  pub fn with_java_path(&mut self, java_path: &PathBuf) -> &mut Self {
-    self.java_path = Some(java_path.clone());
+  pub fn from_dry_run_report(report: &DryRunReport) -> Self {
+    Self {
+      java_path: report.java_path.clone(),
+      arguments: report.arguments.clone(),
+      directory: report.directory.clone(),
+      sandbox: report.sandbox,
+      capture_output: report.capture_output,
+      capture_output_timeout: Some(DEFAULT_CAPTURE_OUTPUT_TIMEOUT),
+      crash_report_archive_dir: report.crash_report_archive_dir.clone(),
+      version_argument_count: report.version_argument_count,
+      env: None,
+    }
+  }
+
+  pub fn dry_run(&self) -> DryRunReport {
+    DryRunReport {
+      java_path: self.java_path.clone(),
+      directory: self.directory.clone(),
+      arguments: self.get_args(),
+      sandbox: self.sandbox,
+      capture_output: self.capture_output,
+      crash_report_archive_dir: self.crash_report_archive_dir.clone(),
+      version_argument_count: self.version_argument_count,
+    }
+  }
+
+  pub fn with_java_path(&mut self, java_path: &Path) -> &mut Self {
+    self.java_path = Some(java_path.to_path_buf());
     self
   }
 
@@ -85,7 +558,49 @@ impl GameProcessBuilder {
     self
   }
 
-  pub fn spawn(self) -> Result<GameProcess, Box<dyn std::error::Error>> {
+  pub fn with_sandbox(&mut self, sandbox: bool) -> &mut Self {
+    self.sandbox = sandbox;
+    self
+  }
+
+  pub fn with_capture_output(&mut self, capture_output: bool) -> &mut Self {
+    self.capture_output = capture_output;
+    self
+  }
+
+  pub fn with_capture_output_timeout(&mut self, capture_output_timeout: Option<Duration>) -> &mut Self {
+    self.capture_output_timeout = capture_output_timeout;
+    self
+  }
+
+  pub fn with_crash_report_archive_dir(&mut self, crash_report_archive_dir: &Path) -> &mut Self {
+    self.crash_report_archive_dir = Some(crash_report_archive_dir.to_path_buf());
+    self
+  }
+
+  pub fn with_version_argument_count(&mut self, version_argument_count: usize) -> &mut Self {
+    self.version_argument_count = Some(version_argument_count);
+    self
+  }
+
+  // Starts from the launcher's environment without the given variables, matched case-insensitively on windows
+  pub fn inherit_env_except(&mut self, keys: &[&str]) -> &mut Self {
+    let ignore_case = OperatingSystem::get_current_platform() == OperatingSystem::Windows;
+    let is_excluded = |name: &OsString| {
+      let name = name.to_string_lossy();
+      keys.iter().any(|key| if ignore_case { name.eq_ignore_ascii_case(key) } else { name == *key })
+    };
+    self.env = Some(
+      env
+        ::vars_os()
+        .filter(|(name, _)| !is_excluded(name))
+        .collect()
+    );
+    self
+  }
+
+  // Program and arguments that spawn() would run
+  pub fn build_command(&self) -> Result<(PathBuf, Vec<String>), Box<dyn std::error::Error>> {
     let java_path = self.java_path.as_ref().ok_or("Java path not set")?;
     let directory = self.directory.as_ref().ok_or("Game directory not set")?;
     let mut args = self.get_args();
@@ -95,6 +610,273 @@ impl GameProcessBuilder {
         .map(|arg| arg.replace("\"", "\\\""))
         .collect();
     }
-    Ok(GameProcess::new(java_path, directory, args))
+
+    if self.sandbox {
+      match Self::get_sandbox_command(java_path, directory, &args) {
+        Ok(command) => {
+          return Ok(command);
+        }
+        Err(err) => warn!("{err}, launching the game without a sandbox"),
+      }
+    }
+    Ok((java_path.clone(), args))
+  }
+
+  pub fn spawn(self) -> Result<GameProcess, Box<dyn std::error::Error>> {
+    let directory = self.directory.as_ref().ok_or("Game directory not set")?;
+    let (program, args) = self.build_command()?;
+    let mut process = GameProcess::spawn(&program, directory, args, self.env.as_ref());
+    process.crash_report_archive_dir = self.crash_report_archive_dir.clone();
+    process.capture_output_timeout = self.capture_output_timeout;
+    Ok(if self.capture_output { process.with_output_capture() } else { process })
+  }
+
+  // Program and arguments that run java inside the platform's sandbox
+  fn get_sandbox_command(java_path: &Path, directory: &Path, args: &[String]) -> Result<(PathBuf, Vec<String>), SandboxError> {
+    let os = OperatingSystem::get_current_platform();
+    let binary = match os {
+      OperatingSystem::Linux => "bwrap",
+      OperatingSystem::Osx => "sandbox-exec",
+      OperatingSystem::Windows => "runas.exe",
+      _ => return Err(SandboxError::Unsupported(os.get_name())),
+    };
+    let sandbox_path = find_in_path(binary).ok_or_else(|| SandboxError::NotFound(binary.to_string()))?;
+    if os == OperatingSystem::Windows {
+      return Ok((sandbox_path, Self::get_runas_args(java_path, args)));
+    }
+
+    let mut sandbox_args = if os == OperatingSystem::Linux {
+      let runtime_dir = env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from);
+      // WAYLAND_DISPLAY is either a socket name in the runtime dir or an absolute path
+      let wayland_socket = env::var_os("WAYLAND_DISPLAY").and_then(|display| {
+        let display = PathBuf::from(display);
+        if display.is_absolute() { Some(display) } else { runtime_dir.as_ref().map(|runtime_dir| runtime_dir.join(display)) }
+      });
+      Self::get_bwrap_args(java_path, directory, runtime_dir.as_deref(), wayland_socket.as_deref())
+    } else {
+      let profile = format!(
+        "(version 1)(allow default)(deny file-write*)(allow file-write* (subpath \"{}\") (subpath \"/private/tmp\") (subpath \"/private/var/folders\"))",
+        directory.to_str().unwrap().replace("\"", "\\\"")
+      );
+      vec!["-p".to_string(), profile]
+    };
+    sandbox_args.push(java_path.to_str().unwrap().to_string());
+    sandbox_args.extend(args.iter().cloned());
+    Ok((sandbox_path, sandbox_args))
+  }
+
+  // Basic User trust level, runas starts the game with a restricted token (no admin group, no privileges).
+  // runas only takes the whole command line as a single argument
+  fn get_runas_args(java_path: &Path, args: &[String]) -> Vec<String> {
+    let quote = |arg: &str| {
+      if !arg.is_empty() && !arg.contains([' ', '\t']) {
+        return arg.to_string();
+      }
+      // Backslashes before the closing quote would escape it
+      let trailing_backslashes = arg.len() - arg.trim_end_matches('\\').len();
+      format!("\"{arg}{}\"", "\\".repeat(trailing_backslashes))
+    };
+    let command_line = std::iter
+      ::once(java_path.to_str().unwrap())
+      .chain(args.iter().map(String::as_str))
+      .map(quote)
+      .collect::<Vec<_>>()
+      .join(" ");
+    vec!["/trustlevel:0x20000".to_string(), command_line]
+  }
+
+  fn get_bwrap_args(java_path: &Path, directory: &Path, runtime_dir: Option<&Path>, wayland_socket: Option<&Path>) -> Vec<String> {
+    // bin/java -> java home
+    let java_home = java_path
+      .parent()
+      .and_then(|bin| bin.parent())
+      .unwrap_or(java_path)
+      .to_str()
+      .unwrap();
+    let directory = directory.to_str().unwrap();
+    let mut args = vec!["--unshare-all", "--share-net", "--die-with-parent", "--proc", "/proc", "--dev", "/dev", "--tmpfs", "/tmp"];
+    args.extend(["--ro-bind", "/usr/lib", "/usr/lib"]);
+    for path in ["/lib", "/lib64", "/etc/resolv.conf", "/etc/ssl", "/tmp/.X11-unix"] {
+      args.extend(["--ro-bind-try", path, path]);
+    }
+    // The wayland, pulseaudio and pipewire sockets live in the runtime dir
+    for path in runtime_dir.into_iter().chain(wayland_socket.filter(|socket| !runtime_dir.is_some_and(|dir| socket.starts_with(dir)))) {
+      let path = path.to_str().unwrap();
+      args.extend(["--bind-try", path, path]);
+    }
+    args.extend(["--ro-bind", java_home, java_home, "--bind", directory, directory, "--chdir", directory]);
+    args
+      .iter()
+      .map(|s| s.to_string())
+      .collect()
+  }
+}
+
+fn find_in_path(binary: &str) -> Option<PathBuf> {
+  env::var_os("PATH").and_then(|paths| {
+    env
+      ::split_paths(&paths)
+      .map(|dir| dir.join(binary))
+      .find(|path| path.is_file())
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_resource_usage() {
+    let mut process = spawn_exiting_with_output(0);
+    let usage = process.resource_usage().unwrap();
+    assert!(usage.rss_bytes > 0);
+    if OperatingSystem::get_current_platform() == OperatingSystem::Linux {
+      assert!(usage.thread_count.is_some_and(|threads| threads > 0));
+    }
+    process.exit_status_blocking().unwrap();
+    thread::sleep(RESOURCE_USAGE_CACHE_TIME);
+    assert!(process.resource_usage().is_none());
+  }
+
+  #[test]
+  fn test_capture_output_keeps_last_line() {
+    let (shell, args) = match OperatingSystem::get_current_platform() {
+      OperatingSystem::Windows => ("cmd", vec!["/C", "echo first& echo last"]),
+      _ => ("sh", vec!["-c", "echo first; echo last"]),
+    };
+    let mut builder = GameProcessBuilder::new();
+    builder.with_java_path(&PathBuf::from(shell));
+    builder.directory(&env::temp_dir());
+    builder.with_capture_output(true);
+    builder.with_arguments(args);
+
+    let mut process = builder.spawn().unwrap();
+    let output_log = Arc::clone(&process.output_log);
+    while process.try_wait().unwrap().is_none() {
+      thread::sleep(Duration::from_millis(10));
+    }
+    drop(process);
+    assert_eq!(output_log.lock().unwrap().back().map(String::as_str), Some("last"));
+  }
+
+  #[test]
+  fn test_capture_output_caps_log() {
+    let (shell, args) = match OperatingSystem::get_current_platform() {
+      OperatingSystem::Windows => ("powershell", vec!["-NoProfile", "-Command", "1..10005"]),
+      _ => ("sh", vec!["-c", "seq 1 10005"]),
+    };
+    let mut builder = GameProcessBuilder::new();
+    builder.with_java_path(&PathBuf::from(shell));
+    builder.directory(&env::temp_dir());
+    builder.with_capture_output(true);
+    builder.with_arguments(args);
+
+    let mut process = builder.spawn().unwrap();
+    let output_log = Arc::clone(&process.output_log);
+    process.exit_status_blocking().unwrap();
+    drop(process);
+    let output_log = output_log.lock().unwrap();
+    assert_eq!(output_log.len(), MAX_OUTPUT_LOG_LINES);
+    assert_eq!(output_log.front().map(String::as_str), Some("6"));
+    assert_eq!(output_log.back().map(String::as_str), Some("10005"));
+  }
+
+  fn spawn_exiting_with_output(code: u8) -> GameProcess {
+    // More output than fits in a pipe buffer, the process can only exit if it's read
+    let (shell, args) = match OperatingSystem::get_current_platform() {
+      OperatingSystem::Windows => ("powershell", vec!["-NoProfile".to_string(), "-Command".to_string(), format!("'a' * 300000; exit {code}")]),
+      _ => ("sh", vec!["-c".to_string(), format!("head -c 300000 /dev/zero; exit {code}")]),
+    };
+    let mut builder = GameProcessBuilder::new();
+    builder.with_java_path(&PathBuf::from(shell));
+    builder.directory(&env::temp_dir());
+    builder.with_arguments(args);
+    builder.spawn().unwrap()
+  }
+
+  #[test]
+  fn test_exit_status_blocking() {
+    assert_eq!(spawn_exiting_with_output(3).exit_status_blocking().unwrap().code(), Some(3));
+  }
+
+  #[tokio::test]
+  async fn test_wait() {
+    assert_eq!(spawn_exiting_with_output(4).wait().await.unwrap().code(), Some(4));
+  }
+
+  #[test]
+  fn test_attached_process() {
+    let mut process = spawn_exiting_with_output(0);
+    let attached = AttachedProcess::attach(process.pid()).unwrap();
+    assert!(attached.is_running());
+    process.exit_status_blocking().unwrap();
+    assert!(!attached.is_running());
+    assert!(AttachedProcess::attach(process.pid()).is_err());
+    assert_eq!(attached.detach(), process.pid());
+  }
+
+  #[test]
+  fn test_bwrap_args() {
+    let java_path = Path::new("/usr/lib/jvm/java-17/bin/java");
+    let directory = Path::new("/home/steve/.minecraft");
+    let runtime_dir = Path::new("/run/user/1000");
+    let has_bind = |args: &[String], path: &str| args.windows(3).any(|window| window[0] == "--bind-try" && window[1] == path && window[2] == path);
+
+    let args = GameProcessBuilder::get_bwrap_args(java_path, directory, Some(runtime_dir), Some(&runtime_dir.join("wayland-0")));
+    assert!(has_bind(&args, "/run/user/1000"));
+    assert!(!has_bind(&args, "/run/user/1000/wayland-0"));
+    assert!(args.windows(3).any(|window| window == ["--ro-bind", "/usr/lib/jvm/java-17", "/usr/lib/jvm/java-17"]));
+
+    let args = GameProcessBuilder::get_bwrap_args(java_path, directory, Some(runtime_dir), Some(Path::new("/tmp/wayland-1")));
+    assert!(has_bind(&args, "/run/user/1000"));
+    assert!(has_bind(&args, "/tmp/wayland-1"));
+
+    let args = GameProcessBuilder::get_bwrap_args(java_path, directory, None, None);
+    assert!(!args.iter().any(|arg| arg == "--bind-try"));
+  }
+
+  #[test]
+  fn test_runas_args() {
+    let java_path = Path::new(r"C:\Program Files\Java\bin\java.exe");
+    let args = vec!["-Xmx2G".to_string(), "--gameDir".to_string(), r"C:\Users\Steve\My Games\".to_string(), String::new()];
+    assert_eq!(GameProcessBuilder::get_runas_args(java_path, &args), [
+      "/trustlevel:0x20000",
+      r#""C:\Program Files\Java\bin\java.exe" -Xmx2G --gameDir "C:\Users\Steve\My Games\\" """#,
+    ]);
+  }
+
+  #[test]
+  fn test_sandbox_fallback() {
+    if OperatingSystem::get_current_platform() != OperatingSystem::Linux || find_in_path("bwrap").is_some() {
+      return;
+    }
+    let mut builder = GameProcessBuilder::new();
+    builder.with_java_path(Path::new("/usr/bin/java")).directory(&PathBuf::from("/tmp")).with_argument("-Xmx2G").with_sandbox(true);
+    assert_eq!(builder.build_command().unwrap(), (PathBuf::from("/usr/bin/java"), vec!["-Xmx2G".to_string()]));
+  }
+
+  #[test]
+  fn test_inherit_env_except() {
+    let mut builder = GameProcessBuilder::new();
+    builder.inherit_env_except(&["PATH"]);
+    let env = builder.env.as_ref().unwrap();
+    assert!(env.keys().all(|name| !name.to_string_lossy().eq_ignore_ascii_case("PATH")));
+    assert_eq!(env.len(), env::vars_os().filter(|(name, _)| !name.to_string_lossy().eq_ignore_ascii_case("PATH")).count());
+  }
+
+  #[test]
+  fn test_from_dry_run_report() {
+    let mut builder = GameProcessBuilder::new();
+    builder.with_java_path(&PathBuf::from("/usr/bin/java"));
+    builder.directory(&PathBuf::from("minecraft"));
+    builder.with_capture_output(true);
+    builder.with_arguments(vec!["-Xmx2G", "net.minecraft.client.main.Main", "--title", "\"Minecraft\""]);
+    builder.with_version_argument_count(2);
+
+    let report = builder.dry_run();
+    assert_eq!(report.version_argument_count, Some(2));
+    let rebuilt = GameProcessBuilder::from_dry_run_report(&report);
+    assert_eq!(rebuilt.dry_run(), report);
+    assert_eq!(rebuilt.build_command().unwrap(), builder.build_command().unwrap());
   }
 }
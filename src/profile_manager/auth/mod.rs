@@ -18,6 +18,9 @@ pub trait UserAuthentication: Send + Debug {
   fn auth_uuid(&self) -> Uuid;
   fn user_type(&self) -> String; // "legacy" - "mojang" - "msa"
   fn get_extra_substitutors(&self) -> HashMap<String, String>;
+  fn is_offline(&self) -> bool {
+    false
+  }
   // TODO: only on msa auth, or figure out how!
   // fn auth_xuid(&self) -> Option<String>;
 }
@@ -123,4 +126,8 @@ impl UserAuthentication for OfflineUserAuthentication {
   fn get_extra_substitutors(&self) -> HashMap<String, String> {
     HashMap::new()
   }
+
+  fn is_offline(&self) -> bool {
+    true
+  }
 }
@@ -1 +1,29 @@
-pub mod auth;
\ No newline at end of file
+pub mod auth;
+
+use std::path::PathBuf;
+
+use serde::{ Deserialize, Serialize };
+
+use crate::versions::info::MCVersion;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+  pub name: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub last_version_id: Option<MCVersion>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub game_dir: Option<PathBuf>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub java_dir: Option<PathBuf>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub java_args: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub resolution: Option<ProfileResolution>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ProfileResolution {
+  pub width: u32,
+  pub height: u32,
+}
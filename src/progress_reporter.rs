@@ -1,4 +1,4 @@
-use std::fmt::Debug;
+use std::{ fmt::Debug, sync::{ Arc, Mutex } };
 
 pub struct ProgressReporter(Box<dyn Fn(ProgressUpdate) + Send + Sync + 'static>);
 
@@ -34,6 +34,20 @@ impl ProgressReporter {
   pub fn clear(&self) {
     self.update(ProgressUpdate::Clear);
   }
+
+  pub fn section(&self, name: &str) -> SectionGuard<'_> {
+    self.set_status(name);
+    SectionGuard { reporter: self }
+  }
+
+  pub fn with_prefix(self: &Arc<Self>, prefix: &str) -> PrefixedReporter {
+    PrefixedReporter { inner: Arc::clone(self), prefix: prefix.to_string() }
+  }
+
+  // For tests, turn it into a reporter with ProgressReporter::from and check events() afterwards
+  pub fn collect() -> CollectingReporter {
+    CollectingReporter::default()
+  }
 }
 
 impl Default for ProgressReporter {
@@ -48,7 +62,76 @@ impl Debug for ProgressReporter {
   }
 }
 
+// Clears the progress once the section is over, however it's left
+pub struct SectionGuard<'a> {
+  reporter: &'a ProgressReporter,
+}
+
+impl SectionGuard<'_> {
+  // Ends the section early and leaves the progress as it is
+  pub fn cancel(self) {
+    std::mem::forget(self);
+  }
+}
+
+impl Drop for SectionGuard<'_> {
+  fn drop(&mut self) {
+    self.reporter.clear();
+  }
+}
+
+// Prepends a prefix to every status, everything else is passed through unchanged
 #[derive(Debug, Clone)]
+pub struct PrefixedReporter {
+  inner: Arc<ProgressReporter>,
+  prefix: String,
+}
+
+impl PrefixedReporter {
+  pub fn update(&self, update: ProgressUpdate) -> &Self {
+    let update = match update {
+      ProgressUpdate::SetStatus(status) => ProgressUpdate::SetStatus(self.prefixed(&status)),
+      ProgressUpdate::SetAll(status, progress, total) => ProgressUpdate::SetAll(self.prefixed(&status), progress, total),
+      update => update,
+    };
+    self.inner.update(update);
+    self
+  }
+
+  fn prefixed(&self, status: &str) -> String {
+    format!("{} {}", self.prefix, status)
+  }
+}
+
+impl From<PrefixedReporter> for ProgressReporter {
+  fn from(reporter: PrefixedReporter) -> Self {
+    ProgressReporter::new(move |update| {
+      reporter.update(update);
+    })
+  }
+}
+
+// Records every update, clones share the same list
+#[derive(Debug, Clone, Default)]
+pub struct CollectingReporter {
+  events: Arc<Mutex<Vec<ProgressUpdate>>>,
+}
+
+impl CollectingReporter {
+  pub fn events(&self) -> Vec<ProgressUpdate> {
+    self.events.lock().unwrap().clone()
+  }
+}
+
+impl From<CollectingReporter> for ProgressReporter {
+  fn from(reporter: CollectingReporter) -> Self {
+    ProgressReporter::new(move |update| {
+      reporter.events.lock().unwrap().push(update);
+    })
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ProgressUpdate {
   SetStatus(String),
   SetProgress(u32),
@@ -56,3 +139,34 @@ pub enum ProgressUpdate {
   SetAll(String, u32, u32),
   Clear,
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_prefixed_section() {
+    let collector = ProgressReporter::collect();
+    let reporter = Arc::new(ProgressReporter::from(collector.clone()));
+    {
+      let prefixed = ProgressReporter::from(reporter.with_prefix("[Assets]"));
+      let _guard = prefixed.section("Downloading");
+      prefixed.set_progress(1);
+    }
+    assert_eq!(collector.events(), [
+      ProgressUpdate::SetStatus("[Assets] Downloading".to_string()),
+      ProgressUpdate::SetProgress(1),
+      ProgressUpdate::Clear,
+    ]);
+  }
+
+  #[test]
+  fn test_cancelled_section() {
+    let collector = ProgressReporter::collect();
+    let reporter = ProgressReporter::from(collector.clone());
+    let guard = reporter.section("Downloading");
+    reporter.set_progress(1);
+    guard.cancel();
+    assert_eq!(collector.events(), [ProgressUpdate::SetStatus("Downloading".to_string()), ProgressUpdate::SetProgress(1)]);
+  }
+}
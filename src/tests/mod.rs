@@ -1,15 +1,20 @@
 use crate::{
   download_utils::ProxyOptions,
-  options::{ LauncherOptions, GameOptionsBuilder },
+  options::{ LauncherOptions, GameOptions, GameOptionsBuilder, JvmArgsPrecedence },
   profile_manager::auth::OfflineUserAuthentication,
   progress_reporter::{ ProgressReporter, ProgressUpdate },
-  versions::info::MCVersion,
+  versions::{ info::{ MCVersion, VersionInfo }, json::{ artifact::Artifact, library::LibrarySource, LocalVersionInfo }, ModLoaderInstaller, VersionManager },
+  process::GameProcessBuilder,
   MinecraftGameRunner,
+  ArgumentSubstitutorBuilder,
+  RestartReason,
 };
 
-use std::{ env::temp_dir, path::PathBuf, sync::{ Mutex, Arc } };
+use std::{ collections::HashMap, env::temp_dir, fs, io::{ Cursor, Read, Write }, path::{ Path, PathBuf }, sync::{ Mutex, Arc }, time::Duration };
+use async_trait::async_trait;
 use chrono::Utc;
 use log::{ debug, info, trace, LevelFilter };
+use reqwest::Url;
 use log4rs::{
   config::{ Appender, Root, Logger },
   append::{
@@ -19,7 +24,9 @@ use log4rs::{
   encode::pattern::PatternEncoder,
   Config,
 };
+use serde_json::{ json, Value };
 use uuid::Uuid;
+use zip::{ write::FileOptions, CompressionMethod, ZipWriter };
 
 #[derive(Debug)]
 struct StartupTrigger {
@@ -45,6 +52,324 @@ impl Trigger for StartupTrigger {
   }
 }
 
+#[test]
+fn test_add_path_normalizes_separators() {
+  let mut substitutor = ArgumentSubstitutorBuilder::new();
+  substitutor
+    .add_path("game_directory", &PathBuf::from(r"C:\Users\Steve\AppData\Roaming\.minecraft"))
+    .add_path("natives_directory", &PathBuf::from("/home/steve/.minecraft/versions/1.20.4/natives"))
+    .add_path("assets_root", &PathBuf::from(r"D:\games/minecraft\assets"));
+  let substitutor = substitutor.build();
+
+  assert_eq!(substitutor("--gameDir ${game_directory}".to_string()), "--gameDir C:/Users/Steve/AppData/Roaming/.minecraft");
+  assert_eq!(substitutor("-Djava.library.path=${natives_directory}".to_string()), "-Djava.library.path=/home/steve/.minecraft/versions/1.20.4/natives");
+  assert_eq!(substitutor("${assets_root}".to_string()), "D:/games/minecraft/assets");
+}
+
+#[test]
+fn test_add_from_hashmap_with_prefix() {
+  let mut substitutor = ArgumentSubstitutorBuilder::new();
+  substitutor
+    .add_from_hashmap_with_prefix("mod_loader", HashMap::from([("name".to_string(), "fabric".to_string())]))
+    .add_from_hashmap_with_prefix("asset=", HashMap::from([("icons/minecraft.icns".to_string(), "/assets/objects/ab/abcd".to_string())]));
+  let substitutor = substitutor.build();
+
+  assert_eq!(substitutor("${mod_loader.name}".to_string()), "fabric");
+  assert_eq!(substitutor("-Xdock:icon=${asset=icons/minecraft.icns}".to_string()), "-Xdock:icon=/assets/objects/ab/abcd");
+}
+
+fn game_options(game_dir: &Path) -> GameOptionsBuilder {
+  GameOptionsBuilder::default()
+    .version(MCVersion::new("1.7.10"))
+    .game_dir(game_dir.to_path_buf())
+    .java_path(std::env::current_exe().unwrap())
+    .authentication(Box::new(OfflineUserAuthentication::new("Steve")))
+}
+
+// Runner that already went through launch(), launching again only needs the version jar to be present
+fn launched_game_runner(game_options: GameOptions, libraries: Value, release_time: &str) -> Result<MinecraftGameRunner, Box<dyn std::error::Error>> {
+  let jar_path = game_options.get_data_dir().join("versions").join("1.7.10").join("1.7.10.jar");
+  fs::create_dir_all(jar_path.parent().unwrap())?;
+  fs::write(&jar_path, [])?;
+
+  let mut game_runner = MinecraftGameRunner::new(game_options);
+  game_runner.local_version = Some(
+    serde_json::from_value(
+      json!({
+      "id": "1.7.10",
+      "releaseTime": release_time,
+      "time": release_time,
+      "type": "release",
+      "mainClass": "net.minecraft.client.main.Main",
+      "libraries": libraries,
+      "assetIndex": {
+        "id": "1.7.10",
+        "sha1": "1863782e33ce7b584fc45b037325a1964e095d3e",
+        "size": 72996,
+        "totalSize": 112396854,
+        "url": "https://launchermeta.mojang.com/v1/packages/1863782e33ce7b584fc45b037325a1964e095d3e/1.7.10.json"
+      }
+    })
+    )?
+  );
+  Ok(game_runner)
+}
+
+// Launches with echo as the java binary, so the process prints the command line it was given
+#[cfg(unix)]
+async fn launch_args(game_options: GameOptionsBuilder, release_time: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+  let game_options = game_options.java_path(PathBuf::from("/bin/echo")).build()?;
+  let mut game_runner = launched_game_runner(game_options, json!([]), release_time)?;
+  let mut process = game_runner.launch_game().await?;
+  let mut output = String::new();
+  process.stdout().read_to_string(&mut output)?;
+  process.wait().await?;
+  Ok(output.split_whitespace().map(str::to_string).collect())
+}
+
+// The previous process is a child of the test, restart has to reap it instead of waiting for it to disappear
+#[cfg(unix)]
+#[tokio::test]
+async fn test_restart() -> Result<(), Box<dyn std::error::Error>> {
+  let game_dir = temp_dir().join("mclc-test-restart");
+  let game_options = game_options(&game_dir).java_path(PathBuf::from("/bin/true")).build()?;
+  let mut game_runner = launched_game_runner(game_options, json!([]), "2014-05-14T17:29:23+00:00")?;
+
+  let mut builder = GameProcessBuilder::new();
+  builder.with_java_path(&PathBuf::from("sh"));
+  builder.directory(&game_dir);
+  builder.with_arguments(vec!["-c", "sleep 30"]);
+  let mut previous = builder.spawn()?;
+
+  let restarted = tokio::time::timeout(Duration::from_secs(10), game_runner.restart(&mut previous, RestartReason::Requested)).await?;
+  assert!(previous.try_wait()?.is_some());
+  restarted?.wait().await?;
+  Ok(())
+}
+
+// 1.20.2 takes --screenshotPath, older versions only read the system property
+#[cfg(unix)]
+#[tokio::test]
+async fn test_screenshot_dir_arguments() -> Result<(), Box<dyn std::error::Error>> {
+  let game_dir = temp_dir().join("mclc-test-screenshot-dir");
+  let screenshot_dir = game_dir.join("screenshots");
+  let screenshot_dir_str = screenshot_dir.display().to_string();
+
+  let args = launch_args(game_options(&game_dir).screenshot_dir(screenshot_dir.clone()), "2014-05-14T17:29:23+00:00").await?;
+  assert!(screenshot_dir.is_dir());
+  assert!(args.contains(&format!("-Dminecraft.screenshotDir={screenshot_dir_str}")));
+  assert!(!args.contains(&"--screenshotPath".to_string()));
+
+  let args = launch_args(game_options(&game_dir).screenshot_dir(screenshot_dir.clone()), "2023-09-20T09:02:57+00:00").await?;
+  assert!(!args.iter().any(|arg| arg.starts_with("-Dminecraft.screenshotDir=")));
+  assert!(args.ends_with(&["--screenshotPath".to_string(), screenshot_dir_str]));
+
+  fs::remove_dir_all(&game_dir)?;
+  Ok(())
+}
+
+// --renderBackend is only passed to 1.16+, the "none" backend also runs the JVM headless
+#[cfg(unix)]
+#[tokio::test]
+async fn test_headless_arguments() -> Result<(), Box<dyn std::error::Error>> {
+  let game_dir = temp_dir().join("mclc-test-headless");
+  let headless_options = || game_options(&game_dir).no_gui(true).rendering_backend("none".to_string());
+
+  let args = launch_args(headless_options(), "2014-05-14T17:29:23+00:00").await?;
+  assert!(args.contains(&"-Djava.awt.headless=true".to_string()));
+  assert!(args.ends_with(&["--nogui".to_string()]));
+
+  let args = launch_args(headless_options(), "2020-06-23T16:20:52+00:00").await?;
+  assert!(args.ends_with(&["--nogui".to_string(), "--renderBackend".to_string(), "none".to_string()]));
+
+  let args = launch_args(game_options(&game_dir), "2020-06-23T16:20:52+00:00").await?;
+  assert!(!args.iter().any(|arg| arg == "-Djava.awt.headless=true" || arg == "--nogui" || arg == "--renderBackend"));
+
+  fs::remove_dir_all(&game_dir)?;
+  Ok(())
+}
+
+// The agent has to come before the main class, and is only added when both options are set
+#[cfg(unix)]
+#[tokio::test]
+async fn test_authlib_injector_argument() -> Result<(), Box<dyn std::error::Error>> {
+  let game_dir = temp_dir().join("mclc-test-authlib-injector");
+  let authlib_injector = game_dir.join("authlib-injector.jar");
+  fs::create_dir_all(&game_dir)?;
+  fs::write(&authlib_injector, [])?;
+  let auth_server = Url::parse("https://auth.example.com/api/yggdrasil")?;
+
+  let injected_options = game_options(&game_dir).authlib_injector_path(authlib_injector.clone()).auth_server(auth_server.clone());
+  let args = launch_args(injected_options, "2014-05-14T17:29:23+00:00").await?;
+  let agent = format!("-javaagent:{}={}", authlib_injector.display(), auth_server);
+  let main_class = args.iter().position(|arg| arg == "net.minecraft.client.main.Main").unwrap();
+  assert!(args[..main_class].contains(&agent));
+
+  let args = launch_args(game_options(&game_dir).authlib_injector_path(authlib_injector.clone()), "2014-05-14T17:29:23+00:00").await?;
+  assert!(!args.iter().any(|arg| arg.starts_with("-javaagent:")));
+
+  fs::remove_dir_all(&game_dir)?;
+  Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_user_jvm_args_substitution() -> Result<(), Box<dyn std::error::Error>> {
+  let game_dir = temp_dir().join("mclc-test-user-jvm-args");
+  let expected = format!("-Dgame.dir={}", game_dir.display());
+  for precedence in [JvmArgsPrecedence::UserFirst, JvmArgsPrecedence::VersionFirst, JvmArgsPrecedence::UserOnly] {
+    let options = game_options(&game_dir).jvm_args(vec!["-Dgame.dir=${game_directory}".to_string()]).jvm_args_precedence(precedence);
+    let args = launch_args(options, "2014-05-14T17:29:23+00:00").await?;
+    assert!(args.contains(&expected), "{precedence:?}: {args:?}");
+  }
+
+  fs::remove_dir_all(&game_dir)?;
+  Ok(())
+}
+
+#[test]
+fn test_extract_corrupt_natives() -> Result<(), Box<dyn std::error::Error>> {
+  // The archive itself is valid, the stored entry only fails its checksum once it's extracted
+  let mut jar = ZipWriter::new(Cursor::new(vec![]));
+  jar.start_file("liblwjgl.so", FileOptions::default().compression_method(CompressionMethod::Stored))?;
+  jar.write_all(b"native library")?;
+  let mut jar = jar.finish()?.into_inner();
+  let content_start = jar.windows(14).position(|window| window == b"native library").unwrap();
+  jar[content_start..content_start + 6].copy_from_slice(b"broken");
+
+  for preserve_natives_on_error in [false, true] {
+    let game_dir = temp_dir().join(format!("mclc-test-corrupt-natives-{preserve_natives_on_error}"));
+    let library_dir = game_dir.join("libraries").join("org").join("lwjgl").join("lwjgl").join("3.3.1");
+    fs::create_dir_all(&library_dir)?;
+    for os in ["linux", "windows", "osx"] {
+      fs::write(library_dir.join(format!("lwjgl-3.3.1-natives-{os}.jar")), &jar)?;
+    }
+
+    let game_options = game_options(&game_dir).preserve_natives_on_error(preserve_natives_on_error).build()?;
+    let libraries = json!([{
+      "name": "org.lwjgl:lwjgl:3.3.1",
+      "natives": { "linux": "natives-linux", "windows": "natives-windows", "osx": "natives-osx" }
+    }]);
+    let game_runner = launched_game_runner(game_options, libraries, "2014-05-14T17:29:23+00:00")?;
+
+    let natives_dir = game_dir.join("natives");
+    assert!(game_runner.extract_natives(&natives_dir).is_err());
+    assert_eq!(natives_dir.is_dir(), preserve_natives_on_error);
+    fs::remove_dir_all(&game_dir)?;
+  }
+  Ok(())
+}
+
+#[tokio::test]
+async fn test_cleanup() -> Result<(), Box<dyn std::error::Error>> {
+  let game_dir = temp_dir().join("mclc-test-cleanup");
+  let (natives_dir, virtual_dir, resources_dir) = (game_dir.join("natives"), game_dir.join("virtual"), game_dir.join("resources"));
+  for dir in [&natives_dir, &virtual_dir, &resources_dir] {
+    fs::create_dir_all(dir)?;
+  }
+
+  let mut game_runner = MinecraftGameRunner::new(game_options(&game_dir).build()?);
+  game_runner.natives_dir = Some(natives_dir.clone());
+  game_runner.virtual_dir = Some(virtual_dir.clone());
+  game_runner.cleanup().await?;
+  assert!(!natives_dir.exists() && !virtual_dir.exists());
+  assert!(game_runner.natives_dir.is_none() && game_runner.virtual_dir.is_none());
+
+  // Resource-mapped assets are kept, directories that are already gone aren't an error
+  game_runner.natives_dir = Some(natives_dir.clone());
+  game_runner.virtual_dir = Some(resources_dir.clone());
+  game_runner.cleanup().await?;
+  assert!(resources_dir.is_dir());
+
+  fs::remove_dir_all(&game_dir)?;
+  Ok(())
+}
+
+#[test]
+fn test_get_classpath_with_sources() -> Result<(), Box<dyn std::error::Error>> {
+  let game_dir = temp_dir().join("mclc-test-classpath-sources");
+  let version_dir = game_dir.join("versions").join("1.7.10");
+  fs::create_dir_all(&version_dir)?;
+  let version_json = json!({
+    "id": "1.7.10",
+    "releaseTime": "2014-05-14T17:29:23+00:00",
+    "time": "2014-05-14T17:29:23+00:00",
+    "type": "release",
+    "mainClass": "net.minecraft.client.main.Main",
+    "libraries": [{ "name": "com.google.guava:guava:15.0" }]
+  });
+  fs::write(version_dir.join("1.7.10.json"), version_json.to_string())?;
+
+  let extra = game_dir.join("extra.jar");
+  fs::write(&extra, [])?;
+  let game_options = game_options(&game_dir).classpath_extras(vec![extra.clone()]).build()?;
+  let game_runner = MinecraftGameRunner::new(game_options);
+  assert!(game_runner.get_classpath_with_sources().is_none());
+  game_runner.version_manager.refresh_local()?;
+
+  let sources: Vec<_> = game_runner
+    .get_classpath_with_sources()
+    .unwrap()
+    .into_iter()
+    .map(|(_, source)| source)
+    .collect();
+  assert_eq!(sources, [
+    LibrarySource::ClasspathExtra,
+    LibrarySource::VanillaLibrary(Artifact::try_from("com.google.guava:guava:15.0".to_string())?),
+    LibrarySource::VersionJar(MCVersion::new("1.7.10")),
+  ]);
+  fs::remove_dir_all(&game_dir)?;
+  Ok(())
+}
+
+struct TestModLoader;
+
+#[async_trait]
+impl ModLoaderInstaller for TestModLoader {
+  async fn install(&self, _manager: &VersionManager, mc_version: &MCVersion) -> Result<LocalVersionInfo, Box<dyn std::error::Error>> {
+    Ok(
+      serde_json::from_value(
+        json!({
+        "id": format!("test-loader-{}", mc_version.to_string()),
+        "inheritsFrom": mc_version.to_string(),
+        "releaseTime": "2024-01-01T00:00:00+00:00",
+        "time": "2024-01-01T00:00:00+00:00",
+        "type": "release",
+        "mainClass": "net.fabricmc.loader.impl.launch.knot.KnotClient",
+        "libraries": [{ "name": "net.fabricmc:fabric-loader:0.15.3" }]
+      })
+      )?
+    )
+  }
+}
+
+#[tokio::test]
+async fn test_resolve_with_mod_loader() -> Result<(), Box<dyn std::error::Error>> {
+  let game_dir = temp_dir().join("mclc-test-mod-loader");
+  let libraries = json!([{ "name": "com.google.guava:guava:15.0" }]);
+  let game_runner = launched_game_runner(game_options(&game_dir).build()?, libraries, "2014-05-14T17:29:23+00:00")?;
+  let vanilla = game_runner.local_version.clone().unwrap();
+  fs::write(game_dir.join("versions").join("1.7.10").join("1.7.10.json"), serde_json::to_string(&vanilla)?)?;
+  game_runner.version_manager.refresh_local()?;
+
+  // Without a mod loader the vanilla version is launched as is
+  assert_eq!(game_runner.resolve_with_mod_loader(vanilla.clone()).await?.get_id(), &MCVersion::new("1.7.10"));
+
+  let game_runner = game_runner.with_mod_loader(TestModLoader);
+  let resolved = game_runner.resolve_with_mod_loader(vanilla).await?;
+  assert_eq!(resolved.get_id(), &MCVersion::new("test-loader-1.7.10"));
+  assert!(resolved.get_inherits_from().is_none());
+  assert_eq!(resolved.get_main_class()?, "net.fabricmc.loader.impl.launch.knot.KnotClient");
+  let libraries: Vec<_> = resolved.get_libraries().iter().map(|library| library.name.clone()).collect();
+  assert_eq!(libraries, [
+    Artifact::try_from("net.fabricmc:fabric-loader:0.15.3".to_string())?,
+    Artifact::try_from("com.google.guava:guava:15.0".to_string())?,
+  ]);
+
+  fs::remove_dir_all(&game_dir)?;
+  Ok(())
+}
+
 #[tokio::test]
 async fn test_game() -> Result<(), Box<dyn std::error::Error>> {
   let stdout = ConsoleAppender::builder()
@@ -0,0 +1,367 @@
+use std::{
+  collections::HashMap,
+  env::temp_dir,
+  fs::{ self, create_dir_all, File },
+  io::Read,
+  path::{ Path, PathBuf, MAIN_SEPARATOR },
+};
+
+use log::{ info, debug };
+use serde::{ Deserialize, Serialize };
+use zip::ZipArchive;
+
+use crate::{
+  MinecraftLauncherError,
+  download_utils::{ ProxyOptions, Downloadable, HttpClientConfig, PreHashedDownloadable, read_body },
+  versions::json::rule::OperatingSystem,
+};
+
+use super::{ json::{ library::Library, artifact::{ Artifact, join_path_string }, Sha1Sum }, info::MCVersion };
+
+const FORGE_MAVEN_URL: &str = "https://maven.minecraftforge.net/net/minecraftforge/forge";
+// Installers are around 7 MB, anything much larger isn't one
+const MAX_INSTALLER_SIZE: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ForgeInstallProfile {
+  #[serde(default)]
+  pub spec: u32,
+  pub version: MCVersion,
+  pub minecraft: MCVersion,
+  pub json: String,
+  #[serde(default)]
+  pub data: HashMap<String, ForgeDataEntry>,
+  #[serde(default)]
+  pub processors: Vec<ForgeProcessor>,
+  #[serde(default)]
+  pub libraries: Vec<Library>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ForgeDataEntry {
+  pub client: String,
+  pub server: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ForgeProcessor {
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub sides: Option<Vec<String>>,
+  pub jar: Artifact,
+  #[serde(default)]
+  pub classpath: Vec<Artifact>,
+  #[serde(default)]
+  pub args: Vec<String>,
+  #[serde(default)]
+  pub outputs: HashMap<String, String>,
+}
+
+impl ForgeProcessor {
+  pub fn applies_to_client(&self) -> bool {
+    self.sides.as_ref().is_none_or(|sides| sides.iter().any(|side| side == "client"))
+  }
+}
+
+pub struct ForgeInstaller {
+  archive: ZipArchive<File>,
+  pub file: PathBuf,
+  pub profile: ForgeInstallProfile,
+}
+
+impl ForgeInstaller {
+  pub fn get_installer_url(mc_version: &MCVersion, forge_version: &str) -> String {
+    let full_version = format!("{}-{}", mc_version.to_string(), forge_version);
+    format!("{FORGE_MAVEN_URL}/{full_version}/forge-{full_version}-installer.jar")
+  }
+
+  // The installer is checked against the .sha1 file published next to it on the maven
+  pub async fn download(
+    proxy: &ProxyOptions,
+    http_config: &HttpClientConfig,
+    mc_version: &MCVersion,
+    forge_version: &str,
+    target_file: &Path
+  ) -> Result<Self, Box<dyn std::error::Error>> {
+    let url = Self::get_installer_url(mc_version, forge_version);
+    let http_client = proxy.create_http_client_with_config(http_config);
+    let sha1_url = format!("{url}.sha1");
+    let res = http_client.get(&sha1_url).send().await?.error_for_status()?;
+    let sha1 = String::from_utf8(read_body(&sha1_url, res, Some(1024)).await?)?;
+    let expected_hash = Sha1Sum::try_from(sha1.trim().to_string()).map_err(MinecraftLauncherError)?;
+
+    info!("Downloading forge installer from {url}");
+    PreHashedDownloadable::new(http_client, &url, &target_file.to_path_buf(), true, expected_hash)
+      .with_max_size(MAX_INSTALLER_SIZE)
+      .download().await
+      .map_err(|err| MinecraftLauncherError(format!("Couldn't download forge installer {url}: {err}")))?;
+    Self::open(target_file)
+  }
+
+  pub fn open(file: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+    let mut archive = ZipArchive::new(File::open(file)?)?;
+    let profile = serde_json::from_reader(archive.by_name("install_profile.json")?)?;
+    Ok(Self { archive, file: file.to_path_buf(), profile })
+  }
+
+  pub fn read_entry(&mut self, name: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut entry = self.archive.by_name(name.trim_start_matches("/"))?;
+    let mut bytes = vec![];
+    entry.read_to_end(&mut bytes)?;
+    Ok(bytes)
+  }
+
+  pub fn read_version_json(&mut self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let json = self.profile.json.clone();
+    self.read_entry(&json)
+  }
+
+  // Some libraries (like forge itself) are shipped inside the installer under maven/ instead of being downloadable
+  pub fn extract_bundled_library(&mut self, artifact: &Artifact, libraries_dir: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+    let bytes = match self.read_entry(&format!("maven/{}", artifact.get_path_string())) {
+      Ok(bytes) => bytes,
+      Err(_) => {
+        return Ok(false);
+      }
+    };
+    let target = libraries_dir.join(artifact.to_path(MAIN_SEPARATOR));
+    create_dir_all(target.parent().unwrap())?;
+    fs::write(target, bytes)?;
+    Ok(true)
+  }
+
+  pub fn create_library_downloads(
+    &mut self,
+    libraries: &[Library],
+    proxy: &ProxyOptions,
+    http_config: &HttpClientConfig,
+    libraries_dir: &Path
+  ) -> Result<Vec<Box<dyn Downloadable + Send + Sync>>, Box<dyn std::error::Error>> {
+    let mut downloadables = vec![];
+    for library in libraries {
+      let is_bundled = library.downloads
+        .as_ref()
+        .is_some_and(|downloads| downloads.artifact.url.is_empty());
+      if is_bundled {
+        if !self.extract_bundled_library(&library.name, libraries_dir)? {
+          debug!("Library {} has no download url, expecting a processor to create it", library.name);
+        }
+        continue;
+      }
+
+      let file = library.name.get_local_path(&libraries_dir.to_path_buf());
+      let artifact_path = library.name.get_path_string();
+      if let Some(downloadable) = library.create_download(proxy, http_config, &artifact_path, &file, false, None) {
+        downloadables.push(downloadable);
+      }
+    }
+    Ok(downloadables)
+  }
+
+  pub fn get_default_data(&self, game_dir: &Path, libraries_dir: &Path) -> HashMap<String, String> {
+    let mc_id = self.profile.minecraft.to_string();
+    let minecraft_jar = game_dir.join("versions").join(&mc_id).join(format!("{mc_id}.jar"));
+    HashMap::from([
+      ("SIDE".to_string(), "client".to_string()),
+      ("MINECRAFT_JAR".to_string(), minecraft_jar.to_str().unwrap().to_string()),
+      ("MINECRAFT_VERSION".to_string(), mc_id),
+      ("ROOT".to_string(), game_dir.to_str().unwrap().to_string()),
+      ("INSTALLER".to_string(), self.file.to_str().unwrap().to_string()),
+      ("LIBRARY_DIR".to_string(), libraries_dir.to_str().unwrap().to_string()),
+    ])
+  }
+
+  // Where the data files the processors read are extracted to
+  fn get_data_dir(&self) -> PathBuf {
+    temp_dir().join(format!("forge-installer-{}", self.profile.version.to_string()))
+  }
+
+  // Deletes the installer jar and the extracted data files
+  pub fn remove_files(self) -> Result<(), std::io::Error> {
+    let data_dir = self.get_data_dir();
+    let file = self.file.clone();
+    drop(self); // Windows can't delete the jar while the archive is open
+    fs::remove_file(file)?;
+    if data_dir.exists() {
+      fs::remove_dir_all(data_dir)?;
+    }
+    Ok(())
+  }
+
+  fn resolve_data(&mut self, base_data: HashMap<String, String>, libraries_dir: &Path) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+    let data_dir = self.get_data_dir();
+    let mut data = base_data;
+    let entries: Vec<(String, String)> = self.profile.data
+      .iter()
+      .map(|(key, entry)| (key.clone(), entry.client.clone()))
+      .collect();
+    for (key, value) in entries {
+      let resolved = if value.starts_with("[") && value.ends_with("]") {
+        get_artifact_file(&value[1..value.len() - 1], libraries_dir)?.to_str().unwrap().to_string()
+      } else if value.starts_with("'") && value.ends_with("'") {
+        value[1..value.len() - 1].to_string()
+      } else if value.starts_with("/") {
+        let target = join_path_string(&data_dir, &value);
+        create_dir_all(target.parent().unwrap())?;
+        fs::write(&target, self.read_entry(&value)?)?;
+        target.to_str().unwrap().to_string()
+      } else {
+        value
+      };
+      data.insert(key, resolved);
+    }
+    Ok(data)
+  }
+
+  pub async fn run_processors(
+    &mut self,
+    java_path: &Path,
+    base_data: HashMap<String, String>,
+    libraries_dir: &Path
+  ) -> Result<(), Box<dyn std::error::Error>> {
+    let data = self.resolve_data(base_data, libraries_dir)?;
+    let classpath_separator = if OperatingSystem::get_current_platform() == OperatingSystem::Windows { ";" } else { ":" };
+
+    let processors: Vec<ForgeProcessor> = self.profile.processors
+      .iter()
+      .filter(|processor| processor.applies_to_client())
+      .cloned()
+      .collect();
+    for (i, processor) in processors.iter().enumerate() {
+      let outputs = processor.outputs
+        .iter()
+        .map(|(file, sha1)| Ok((substitute_argument(file, &data, libraries_dir)?, substitute_argument(sha1, &data, libraries_dir)?)))
+        .collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()?;
+      if !outputs.is_empty() && outputs.iter().all(|(file, sha1)| matches_sha1(Path::new(file), sha1)) {
+        info!("Skipping forge processor {}/{} ({}), outputs are up to date", i + 1, processors.len(), processor.jar);
+        continue;
+      }
+
+      let jar_file = get_artifact_file(&processor.jar.get_descriptor(), libraries_dir)?;
+      let main_class = get_main_class(&jar_file)?;
+      let mut classpath = vec![jar_file.to_str().unwrap().to_string()];
+      for artifact in &processor.classpath {
+        classpath.push(get_artifact_file(&artifact.get_descriptor(), libraries_dir)?.to_str().unwrap().to_string());
+      }
+      let args = processor.args
+        .iter()
+        .map(|arg| substitute_argument(arg, &data, libraries_dir))
+        .collect::<Result<Vec<_>, _>>()?;
+
+      info!("Running forge processor {}/{} ({})", i + 1, processors.len(), processor.jar);
+      let output = tokio::process::Command
+        ::new(java_path)
+        .arg("-cp")
+        .arg(classpath.join(classpath_separator))
+        .arg(&main_class)
+        .args(&args)
+        .output().await?;
+      if !output.status.success() {
+        Err(
+          MinecraftLauncherError(
+            format!("Forge processor {} failed ({}): {}", processor.jar, output.status, String::from_utf8_lossy(&output.stderr))
+          )
+        )?;
+      }
+
+      for (file, sha1) in &outputs {
+        if !matches_sha1(Path::new(file), sha1) {
+          Err(MinecraftLauncherError(format!("Forge processor {} produced an invalid output: {}", processor.jar, file)))?;
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+fn get_artifact_file(descriptor: &str, libraries_dir: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+  let artifact = Artifact::try_from(descriptor.to_string()).map_err(MinecraftLauncherError)?;
+  Ok(artifact.get_local_path(&libraries_dir.to_path_buf()))
+}
+
+fn substitute_argument(arg: &str, data: &HashMap<String, String>, libraries_dir: &Path) -> Result<String, Box<dyn std::error::Error>> {
+  if arg.starts_with("[") && arg.ends_with("]") {
+    return Ok(get_artifact_file(&arg[1..arg.len() - 1], libraries_dir)?.to_str().unwrap().to_string());
+  }
+  if arg.starts_with("'") && arg.ends_with("'") {
+    return Ok(arg[1..arg.len() - 1].to_string());
+  }
+
+  let mut output = arg.to_string();
+  for (key, value) in data {
+    output = output.replace(&format!("{{{key}}}"), value);
+  }
+  Ok(output)
+}
+
+fn matches_sha1(file: &Path, sha1: &str) -> bool {
+  if !file.is_file() {
+    return false;
+  }
+  let Ok(expected) = Sha1Sum::try_from(sha1.to_string()) else {
+    return true; // Nothing to check against
+  };
+  Sha1Sum::from_file(file).is_ok_and(|local| local == expected)
+}
+
+fn get_main_class(jar_file: &Path) -> Result<String, Box<dyn std::error::Error>> {
+  let mut archive = ZipArchive::new(File::open(jar_file)?)?;
+  let mut manifest = String::new();
+  archive.by_name("META-INF/MANIFEST.MF")?.read_to_string(&mut manifest)?;
+  manifest
+    .lines()
+    .find_map(|line| line.strip_prefix("Main-Class:"))
+    .map(|main_class| main_class.trim().to_string())
+    .ok_or(MinecraftLauncherError(format!("No Main-Class found in {}", jar_file.display())).into())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Write;
+
+  use serde_json::json;
+  use zip::{ write::FileOptions, ZipWriter };
+
+  use super::*;
+
+  #[test]
+  fn test_remove_files() -> Result<(), Box<dyn std::error::Error>> {
+    let installer_file = temp_dir().join("mclc-test-forge-installer.jar");
+    let profile = json!({
+      "version": "1.20.1-forge-0.0.0",
+      "minecraft": "1.20.1",
+      "json": "/version.json",
+      "data": { "BINPATCH": { "client": "/data/client.lzma", "server": "/data/server.lzma" } }
+    });
+    let mut jar = ZipWriter::new(File::create(&installer_file)?);
+    jar.start_file("install_profile.json", FileOptions::default())?;
+    jar.write_all(profile.to_string().as_bytes())?;
+    jar.start_file("data/client.lzma", FileOptions::default())?;
+    jar.write_all(b"patches")?;
+    jar.finish()?;
+
+    let mut installer = ForgeInstaller::open(&installer_file)?;
+    let data = installer.resolve_data(HashMap::new(), Path::new("libraries"))?;
+    let data_dir = installer.get_data_dir();
+    assert_eq!(fs::read(&data["BINPATCH"])?, b"patches");
+
+    installer.remove_files()?;
+    assert!(!installer_file.exists() && !data_dir.exists());
+    Ok(())
+  }
+
+  #[test]
+  fn test_substitute_argument() -> Result<(), Box<dyn std::error::Error>> {
+    let libraries_dir = PathBuf::from("libraries");
+    let data = HashMap::from([("SIDE".to_string(), "client".to_string())]);
+
+    assert_eq!(substitute_argument("{SIDE}", &data, &libraries_dir)?, "client");
+    assert_eq!(substitute_argument("--side={SIDE}", &data, &libraries_dir)?, "--side=client");
+    assert_eq!(substitute_argument("'literal'", &data, &libraries_dir)?, "literal");
+    assert_eq!(
+      PathBuf::from(substitute_argument("[net.minecraftforge:forge:1.20.1-47.2.0:client]", &data, &libraries_dir)?),
+      libraries_dir.join("net").join("minecraftforge").join("forge").join("1.20.1-47.2.0").join("forge-1.20.1-47.2.0-client.jar")
+    );
+    Ok(())
+  }
+}
@@ -1,6 +1,7 @@
-use std::{ fmt::Debug, io::Cursor };
+use std::{ fmt::{ Debug, Display }, io::Cursor, str::FromStr, fs };
 
 use regex::Regex;
+use reqwest::Url;
 use serde::{ Deserialize, Serialize };
 
 use crate::MinecraftLauncherError;
@@ -215,6 +216,92 @@ impl ReleaseType {
       Self::OldAlpha => "old_alpha",
     }
   }
+
+  pub fn get_display_name(&self) -> &str {
+    match self {
+      Self::Release => "Release",
+      Self::Snapshot => "Snapshot",
+      Self::OldBeta => "Old Beta",
+      Self::OldAlpha => "Old Alpha",
+    }
+  }
+}
+
+impl FromStr for ReleaseType {
+  type Err = MinecraftLauncherError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().as_str() {
+      "release" => Ok(Self::Release),
+      "snapshot" => Ok(Self::Snapshot),
+      "old_beta" => Ok(Self::OldBeta),
+      "old_alpha" => Ok(Self::OldAlpha),
+      _ => Err(MinecraftLauncherError(format!("Unknown release type: {s}"))),
+    }
+  }
+}
+
+impl Display for ReleaseType {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.get_name())
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ModLoaderType {
+  Vanilla,
+  Forge,
+  NeoForge,
+  Fabric,
+  Quilt,
+  Other,
+}
+
+impl ModLoaderType {
+  // Libraries are the most reliable hint, then the id and finally the main class
+  pub fn from_version(local_version: &LocalVersionInfo) -> Self {
+    for library in local_version.get_libraries() {
+      let group = library.name.group_id.join(".");
+      match (group.as_str(), library.name.artifact_id.as_str()) {
+        ("net.fabricmc", "fabric-loader") => return Self::Fabric,
+        ("org.quiltmc", "quilt-loader") => return Self::Quilt,
+        ("net.neoforged" | "net.neoforged.fancymodloader", _) => return Self::NeoForge,
+        ("net.minecraftforge", "forge" | "fmlloader" | "fmlcore") => return Self::Forge,
+        _ => {}
+      }
+    }
+
+    let id = local_version.get_id().to_string().to_lowercase();
+    if id.starts_with("fabric-loader-") {
+      return Self::Fabric;
+    } else if id.starts_with("quilt-loader-") {
+      return Self::Quilt;
+    } else if id.contains("neoforge") {
+      return Self::NeoForge;
+    } else if id.contains("forge") {
+      return Self::Forge;
+    }
+
+    match local_version.main_class.as_deref() {
+      Some("net.fabricmc.loader.impl.launch.knot.KnotClient" | "net.fabricmc.loader.launch.knot.KnotClient") => Self::Fabric,
+      Some("org.quiltmc.loader.impl.launch.knot.KnotClient") => Self::Quilt,
+      Some("cpw.mods.modlauncher.Launcher" | "cpw.mods.bootstraplauncher.BootstrapLauncher") => Self::Forge,
+      _ if local_version.get_inherits_from().is_some() => Self::Other,
+      Some("net.minecraft.client.main.Main" | "net.minecraft.launchwrapper.Launch" | "net.minecraft.client.Minecraft") | None => Self::Vanilla,
+      _ => Self::Other,
+    }
+  }
+
+  pub fn get_name(&self) -> &str {
+    match self {
+      Self::Vanilla => "vanilla",
+      Self::Forge => "forge",
+      Self::NeoForge => "neoforge",
+      Self::Fabric => "fabric",
+      Self::Quilt => "quilt",
+      Self::Other => "other",
+    }
+  }
 }
 
 //
@@ -255,13 +342,27 @@ impl RemoteVersionInfo {
     self.compliance_level
   }
 
+  pub(crate) fn set_url(&mut self, url: &str) {
+    self.url = url.to_string();
+  }
+
   pub async fn fetch(&self) -> Result<LocalVersionInfo, Box<dyn std::error::Error>> {
-    let bytes = reqwest::get(&self.url).await?.bytes().await?;
+    let bytes = self.fetch_bytes().await?;
+    Ok(serde_json::from_slice(&bytes[..])?)
+  }
+
+  // Raw version json, already checked against the manifest's sha1
+  pub async fn fetch_bytes(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    // file:// urls come from imported manifests
+    let bytes = match Url::parse(&self.url).ok().filter(|url| url.scheme() == "file") {
+      Some(url) => fs::read(url.to_file_path().map_err(|_| MinecraftLauncherError(format!("Invalid file url: {url}")))?)?,
+      None => reqwest::get(&self.url).await?.bytes().await?.to_vec(),
+    };
     let sha1 = Sha1Sum::from_reader(&mut Cursor::new(&bytes))?;
     if sha1 != self.sha1 {
       Err(MinecraftLauncherError(format!("Sha1 mismatch: {sha1} != {}", self.sha1)))?;
     }
-    Ok(serde_json::from_slice(&bytes[..])?)
+    Ok(bytes)
   }
 }
 
@@ -294,6 +395,44 @@ mod tests {
 
   use super::*;
 
+  #[test]
+  fn test_release_type_from_str() {
+    assert_eq!("release".parse::<ReleaseType>().ok(), Some(ReleaseType::Release));
+    assert_eq!("OLD_ALPHA".parse::<ReleaseType>().ok(), Some(ReleaseType::OldAlpha));
+    assert_eq!("Old_Beta".parse::<ReleaseType>().ok(), Some(ReleaseType::OldBeta));
+    assert!("beta".parse::<ReleaseType>().is_err());
+    for release_type in [ReleaseType::Release, ReleaseType::Snapshot, ReleaseType::OldBeta, ReleaseType::OldAlpha] {
+      assert_eq!(release_type.to_string().parse::<ReleaseType>().ok(), Some(release_type));
+    }
+  }
+
+  #[test]
+  fn test_mod_loader_type() {
+    let mod_loader_type = |json: serde_json::Value| ModLoaderType::from_version(&serde_json::from_value(json).unwrap());
+    let version = |id: &str, main_class: &str, libraries: Vec<&str>| {
+      serde_json::json!({
+        "id": id,
+        "releaseTime": "2023-06-12T13:25:51+00:00",
+        "time": "2023-06-12T13:25:51+00:00",
+        "type": "release",
+        "mainClass": main_class,
+        "libraries": libraries.into_iter().map(|name| serde_json::json!({ "name": name })).collect::<Vec<_>>()
+      })
+    };
+    assert_eq!(mod_loader_type(version("1.20.1", "net.minecraft.client.main.Main", vec!["com.mojang:brigadier:1.1.8"])), ModLoaderType::Vanilla);
+    assert_eq!(
+      mod_loader_type(version("fabric-loader-0.15.3-1.20.1", "net.fabricmc.loader.impl.launch.knot.KnotClient", vec!["net.fabricmc:fabric-loader:0.15.3"])),
+      ModLoaderType::Fabric
+    );
+    assert_eq!(mod_loader_type(version("custom", "org.quiltmc.loader.impl.launch.knot.KnotClient", vec![])), ModLoaderType::Quilt);
+    assert_eq!(mod_loader_type(version("1.20.1-forge-47.2.0", "cpw.mods.bootstraplauncher.BootstrapLauncher", vec![])), ModLoaderType::Forge);
+    assert_eq!(
+      mod_loader_type(version("custom", "cpw.mods.bootstraplauncher.BootstrapLauncher", vec!["net.neoforged.fancymodloader:loader:2.0.7"])),
+      ModLoaderType::NeoForge
+    );
+    assert_eq!(mod_loader_type(version("custom", "com.example.Main", vec![])), ModLoaderType::Other);
+  }
+
   #[tokio::test]
   async fn test_version_id_parsing() -> Result<(), Box<dyn std::error::Error>> {
     let json: Value = Client::new().get("https://piston-meta.mojang.com/mc/game/version_manifest_v2.json").send().await?.json().await?;
@@ -308,7 +447,7 @@ mod tests {
 
   #[tokio::test]
   async fn test_full_version_parsing() -> Result<(), Box<dyn std::error::Error>> {
-    let version_list = RawVersionList::fetch().await?;
+    let version_list = RawVersionList::fetch(&Client::new()).await?;
     for ver in version_list.versions {
       println!("Processing {}", ver.id.to_string());
       let ver = ver.fetch().await?;
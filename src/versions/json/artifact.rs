@@ -1,7 +1,15 @@
-use std::{ fmt::{ Debug, Display }, path::PathBuf };
+use std::{ fmt::{ Debug, Display }, hash::{ Hash, Hasher }, path::{ Path, PathBuf } };
 
 use serde::{ Deserialize, Serialize };
 
+// For '/' separated paths that aren't artifacts, like zip entries, urls and file lists
+pub fn join_path_string(root: &Path, path: &str) -> PathBuf {
+  path
+    .split('/')
+    .filter(|s| !s.is_empty())
+    .fold(root.to_path_buf(), |root, s| root.join(s))
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(try_from = "String", into = "String")]
 pub struct Artifact {
@@ -19,6 +27,29 @@ impl Debug for Artifact {
   }
 }
 
+// The original descriptor is only kept for serialization, "a:b:1" and "a:b:1@jar" are the same artifact
+impl PartialEq for Artifact {
+  fn eq(&self, other: &Self) -> bool {
+    self.group_id == other.group_id &&
+      self.artifact_id == other.artifact_id &&
+      self.version == other.version &&
+      self.classifier == other.classifier &&
+      self.ext == other.ext
+  }
+}
+
+impl Eq for Artifact {}
+
+impl Hash for Artifact {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.group_id.hash(state);
+    self.artifact_id.hash(state);
+    self.version.hash(state);
+    self.classifier.hash(state);
+    self.ext.hash(state);
+  }
+}
+
 impl Display for Artifact {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     write!(f, "{}", self.get_descriptor())
@@ -26,6 +57,11 @@ impl Display for Artifact {
 }
 
 impl Artifact {
+  // Same library, possibly in another version
+  pub fn matches_without_version(&self, other: &Artifact) -> bool {
+    self.group_id == other.group_id && self.artifact_id == other.artifact_id && self.classifier == other.classifier
+  }
+
   pub fn get_file(&self) -> String {
     let mut name = format!("{}-{}", self.artifact_id, self.version);
     if let Some(classifier) = &self.classifier {
@@ -44,7 +80,12 @@ impl Artifact {
   }
 
   pub fn get_path_string(&self) -> String {
-    self.get_path_vec().join("/")
+    self.to_path('/')
+  }
+
+  // Use MAIN_SEPARATOR for local files, '/' for urls and maven paths
+  pub fn to_path(&self, separator: char) -> String {
+    self.get_path_vec().join(&separator.to_string())
   }
 
   pub fn get_local_path(&self, root: &PathBuf) -> PathBuf {
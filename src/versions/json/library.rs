@@ -1,9 +1,13 @@
-use std::{ collections::HashMap, path::PathBuf };
+use std::{ collections::HashMap, path::{ Path, PathBuf, MAIN_SEPARATOR }, time::Duration };
 
+use regex::Regex;
 use reqwest::Url;
-use serde::{ Deserialize, Serialize };
+use serde::{ Deserialize, Serialize, Serializer, Deserializer };
 
-use crate::download_utils::{ ProxyOptions, Downloadable, ChecksummedDownloadable, PreHashedDownloadable };
+use crate::{
+  download_utils::{ ProxyOptions, Downloadable, ChecksummedDownloadable, PreHashedDownloadable, HttpClientConfig },
+  versions::{ VersionManager, info::MCVersion },
+};
 
 use super::{ rule::{ Rule, OperatingSystem, RuleAction, FeatureMatcher }, DownloadInfo, artifact::Artifact };
 
@@ -21,6 +25,8 @@ pub struct Library {
   pub url: Option<String>,
   #[serde(default, skip_serializing_if = "Option::is_none")]
   pub downloads: Option<LibraryDownloadInfo>,
+  #[serde(default, skip_serializing_if = "Option::is_none", with = "duration_secs")]
+  pub download_timeout: Option<Duration>, // Not part of the vanilla format, in seconds
 }
 
 impl Library {
@@ -39,45 +45,112 @@ impl Library {
     }
   }
 
+  // Native libraries for the given os are zips to extract, not jars to load
+  pub fn applies_for_classpath(&self, os: &OperatingSystem, matcher: &dyn FeatureMatcher) -> bool {
+    self.applies_to_current_environment(matcher) && !self.is_native(os)
+  }
+
+  pub fn is_native(&self, os: &OperatingSystem) -> bool {
+    self.natives.contains_key(os)
+  }
+
+  pub fn native_classifier(&self, os: &OperatingSystem) -> Option<String> {
+    self.natives.get(os).map(|classifier| Self::substitute_arch(classifier))
+  }
+
+  fn substitute_arch(classifier: &str) -> String {
+    classifier.replace("${arch}", OperatingSystem::architecture())
+  }
+
   pub fn get_artifact_path(&self, classifier: Option<&str>) -> String {
+    self.get_artifact(classifier).get_path_string()
+  }
+
+  fn get_artifact(&self, classifier: Option<&str>) -> Artifact {
     let mut new_artifact = self.name.clone();
     if let Some(classifier) = classifier {
-      new_artifact.classifier = Some(classifier.to_string());
+      new_artifact.classifier = Some(Self::substitute_arch(classifier));
+    }
+    new_artifact
+  }
+
+  pub fn get_path(&self, game_dir: &Path) -> PathBuf {
+    VersionManager::get_library_dir(game_dir).join(self.get_artifact(None).to_path(MAIN_SEPARATOR))
+  }
+
+  pub fn get_native_path(&self, game_dir: &Path, os: &OperatingSystem) -> Option<PathBuf> {
+    let classifier = self.native_classifier(os)?;
+    Some(VersionManager::get_library_dir(game_dir).join(self.get_artifact(Some(&classifier)).to_path(MAIN_SEPARATOR)))
+  }
+
+  // Main artifact (as OperatingSystem::Unknown) followed by the native artifact of every platform
+  pub fn get_all_download_paths(&self, game_dir: &Path) -> Vec<(OperatingSystem, PathBuf)> {
+    let mut paths = vec![(OperatingSystem::Unknown, self.get_path(game_dir))];
+    for os in OperatingSystem::values() {
+      if let Some(path) = self.get_native_path(game_dir, &os) {
+        paths.push((os, path));
+      }
     }
-    new_artifact.get_path_string()
+    paths
+  }
+
+  // Main artifact and the natives of every platform, e.g. to prepare an installation for another os
+  pub fn create_download_all_platforms(&self, proxy: &ProxyOptions, game_dir: &Path, force_download: bool) -> Vec<Box<dyn Downloadable + Send + Sync>> {
+    self
+      .get_all_download_paths(game_dir)
+      .into_iter()
+      .filter_map(|(os, path)| {
+        let classifier = self.native_classifier(&os);
+        self.create_download(proxy, &HttpClientConfig::default(), &self.get_artifact_path(classifier.as_deref()), &path, force_download, classifier.as_deref())
+      })
+      .collect()
   }
 
   pub fn create_download(
     &self,
     proxy: &ProxyOptions,
+    http_config: &HttpClientConfig,
     artifact_path: &str,
     target_file: &PathBuf,
     force_download: bool,
     classifier: Option<&str>
   ) -> Option<Box<dyn Downloadable + Send + Sync>> {
-    let http_client = proxy.create_http_client();
+    let http_client = proxy.create_http_client_with_config(http_config);
 
     if let Some(url) = &self.url {
       let mut url = Url::parse(url).ok()?;
       url.set_path(&self.get_artifact_path(classifier));
-      Some(Box::new(ChecksummedDownloadable::new(http_client, url.as_str(), target_file, force_download)))
+      Some(
+        Box::new(ChecksummedDownloadable::new(http_client, url.as_str(), target_file, force_download).with_timeout(self.download_timeout))
+      )
     } else if let Some(downloads) = &self.downloads {
       if let Some(info) = downloads.get_download_info(classifier) {
-        Some(Box::new(PreHashedDownloadable::new(http_client, &info.url, target_file, force_download, info.sha1)))
+        Some(
+          Box::new(
+            PreHashedDownloadable::new(http_client, &info.url, target_file, force_download, info.sha1)
+              .with_timeout(self.download_timeout)
+              .with_expected_size(info.size as u64)
+          )
+        )
       } else {
         None
       }
     } else {
       let mut url = Url::parse("https://libraries.minecraft.net/").ok()?;
       url.set_path(artifact_path);
-      Some(Box::new(ChecksummedDownloadable::new(http_client, url.as_str(), target_file, force_download)))
+      Some(
+        Box::new(ChecksummedDownloadable::new(http_client, url.as_str(), target_file, force_download).with_timeout(self.download_timeout))
+      )
     }
   }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ExtractRules {
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
   pub exclude: Vec<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub include: Option<Vec<String>>, // Not part of the vanilla format, when set only the matching files are extracted
 }
 
 impl ExtractRules {
@@ -87,8 +160,30 @@ impl ExtractRules {
         return false;
       }
     }
+    if let Some(include) = &self.include {
+      return include.iter().any(|pattern| Self::matches_pattern(zip_path, pattern));
+    }
     return true;
   }
+
+  // Patterns with a '*' are globs over the whole path, anything else is a path prefix like the exclude entries
+  fn matches_pattern(zip_path: &Path, pattern: &str) -> bool {
+    if !pattern.contains('*') {
+      return zip_path.starts_with(pattern);
+    }
+    let pattern = format!("^{}$", regex::escape(pattern).replace("\\*", ".*"));
+    let zip_path = zip_path.to_string_lossy().replace("\\", "/");
+    Regex::new(&pattern).is_ok_and(|regex| regex.is_match(&zip_path))
+  }
+}
+
+// Where a classpath entry comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LibrarySource {
+  VanillaLibrary(Artifact), // Listed by the launched version itself
+  InheritedLibrary(Artifact, MCVersion), // Listed by a parent version
+  VersionJar(MCVersion),
+  ClasspathExtra, // Added by the launcher, e.g. GameOptions::classpath_extras
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -103,3 +198,135 @@ impl LibraryDownloadInfo {
     if let Some(classifier) = classifier { self.classifiers.get(classifier).cloned() } else { Some(self.artifact.clone()) }
   }
 }
+
+mod duration_secs {
+  use std::time::Duration;
+
+  use super::{ Serializer, Deserializer, Serialize, Deserialize };
+
+  pub fn serialize<S>(value: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+    value.map(|duration| duration.as_secs()).serialize(serializer)
+  }
+
+  pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error> where D: Deserializer<'de> {
+    Ok(Option::<u64>::deserialize(deserializer)?.map(Duration::from_secs))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::MAIN_SEPARATOR_STR;
+
+  use crate::versions::json::rule::RuleFeatureType;
+
+  use super::*;
+
+  fn library() -> Library {
+    serde_json::from_str(r#"{"name": "org.lwjgl:lwjgl:3.3.1", "natives": {"windows": "natives-windows", "linux": "natives-linux"}}"#).unwrap()
+  }
+
+  fn components(path: &Path) -> Vec<String> {
+    path
+      .components()
+      .map(|component| component.as_os_str().to_str().unwrap().to_string())
+      .collect()
+  }
+
+  #[test]
+  fn test_get_path() {
+    let path = library().get_path(&PathBuf::from("minecraft"));
+    assert_eq!(components(&path), ["minecraft", "libraries", "org", "lwjgl", "lwjgl", "3.3.1", "lwjgl-3.3.1.jar"]);
+    assert_eq!(
+      path.to_str().unwrap(),
+      ["minecraft", "libraries", "org", "lwjgl", "lwjgl", "3.3.1", "lwjgl-3.3.1.jar"].join(MAIN_SEPARATOR_STR)
+    );
+  }
+
+  #[test]
+  fn test_get_native_path() {
+    let library = library();
+    for (os, file_name) in [
+      (OperatingSystem::Windows, "lwjgl-3.3.1-natives-windows.jar"),
+      (OperatingSystem::Linux, "lwjgl-3.3.1-natives-linux.jar"),
+    ] {
+      let path = library.get_native_path(&PathBuf::from("minecraft"), &os).unwrap();
+      assert_eq!(components(&path), ["minecraft", "libraries", "org", "lwjgl", "lwjgl", "3.3.1", file_name]);
+      assert!(!path.to_str().unwrap().contains(if MAIN_SEPARATOR_STR == "/" { "\\" } else { "/" }));
+    }
+    assert!(library.get_native_path(&PathBuf::from("minecraft"), &OperatingSystem::Osx).is_none());
+  }
+
+  struct NoFeatures;
+
+  impl FeatureMatcher for NoFeatures {
+    fn has_feature(&self, _feature_type: &RuleFeatureType, _value: &serde_json::Value) -> bool {
+      false
+    }
+  }
+
+  #[test]
+  fn test_applies_for_classpath() {
+    let library = library();
+    assert!(!library.applies_for_classpath(&OperatingSystem::Windows, &NoFeatures));
+    assert!(!library.applies_for_classpath(&OperatingSystem::Linux, &NoFeatures));
+    assert!(library.applies_for_classpath(&OperatingSystem::Osx, &NoFeatures));
+
+    let disallowed: Library = serde_json::from_str(r#"{"name": "org.lwjgl:lwjgl:3.3.1", "rules": [{"action": "disallow"}]}"#).unwrap();
+    assert!(!disallowed.applies_for_classpath(&OperatingSystem::Osx, &NoFeatures));
+  }
+
+  #[test]
+  fn test_get_all_download_paths() {
+    let paths = library().get_all_download_paths(&PathBuf::from("minecraft"));
+    let file_names: Vec<_> = paths
+      .iter()
+      .map(|(os, path)| (os.clone(), path.file_name().unwrap().to_str().unwrap().to_string()))
+      .collect();
+    assert_eq!(
+      file_names,
+      [
+        (OperatingSystem::Unknown, "lwjgl-3.3.1.jar".to_string()),
+        (OperatingSystem::Linux, "lwjgl-3.3.1-natives-linux.jar".to_string()),
+        (OperatingSystem::Windows, "lwjgl-3.3.1-natives-windows.jar".to_string()),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_create_download_all_platforms() {
+    let downloads = library().create_download_all_platforms(&ProxyOptions::NoProxy, &PathBuf::from("minecraft"), false);
+    let urls: Vec<_> = downloads
+      .iter()
+      .map(|download| download.url().as_str())
+      .collect();
+    assert_eq!(urls, [
+      "https://libraries.minecraft.net/org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1.jar",
+      "https://libraries.minecraft.net/org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1-natives-linux.jar",
+      "https://libraries.minecraft.net/org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1-natives-windows.jar",
+    ]);
+    assert_eq!(downloads[2].get_target_file(), &library().get_native_path(&PathBuf::from("minecraft"), &OperatingSystem::Windows).unwrap());
+  }
+
+  #[test]
+  fn test_native_classifier_substitutes_arch() {
+    let library: Library = serde_json::from_str(r#"{"name": "org.lwjgl:lwjgl:3.3.1", "natives": {"osx": "natives-osx-${arch}"}}"#).unwrap();
+    let classifier = library.native_classifier(&OperatingSystem::Osx).unwrap();
+    assert_eq!(classifier, format!("natives-osx-{}", OperatingSystem::architecture()));
+    assert_eq!(library.get_artifact_path(Some("natives-osx-${arch}")), format!("org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1-{classifier}.jar"));
+  }
+
+  #[test]
+  fn test_extract_rules() {
+    let exclude_only: ExtractRules = serde_json::from_str(r#"{"exclude": ["META-INF/"]}"#).unwrap();
+    assert!(!exclude_only.should_extract(&PathBuf::from("META-INF/MANIFEST.MF")));
+    assert!(exclude_only.should_extract(&PathBuf::from("lwjgl.dll")));
+
+    let include: ExtractRules = serde_json::from_str(r#"{"exclude": ["linux/x64/debug/"], "include": ["*.so", "*.dll", "macos/"]}"#).unwrap();
+    assert!(include.should_extract(&PathBuf::from("linux/x64/org/lwjgl/liblwjgl.so")));
+    assert!(include.should_extract(&PathBuf::from("lwjgl.dll")));
+    assert!(include.should_extract(&PathBuf::from("macos/arm64/liblwjgl.dylib")));
+    assert!(!include.should_extract(&PathBuf::from("liblwjgl.so.sha1")));
+    assert!(!include.should_extract(&PathBuf::from("META-INF/MANIFEST.MF")));
+    assert!(!include.should_extract(&PathBuf::from("linux/x64/debug/liblwjgl.so")));
+  }
+}
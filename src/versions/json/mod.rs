@@ -2,21 +2,37 @@ pub mod rule;
 pub mod library;
 pub mod date;
 pub mod artifact;
+pub mod runtime;
 
-use std::{ collections::{ HashMap, HashSet }, io::Read, fmt::{ Debug, Display }, path::{ PathBuf, MAIN_SEPARATOR_STR } };
+use std::{ collections::{ HashMap, HashSet }, io::Read, fmt::{ Debug, Display }, path::{ Path, PathBuf }, fs::File, sync::Arc };
 
 use async_recursion::async_recursion;
-use reqwest::Client;
+use reqwest::{ Client, Url };
 use serde::{ Serialize, Deserialize };
 use sha1::{ Digest, Sha1 };
+use thiserror::Error;
+use tokio::io::AsyncReadExt;
+use uuid::Uuid;
 
-use crate::{ MinecraftLauncherError, download_utils::{ Downloadable, ProxyOptions } };
+use crate::{
+  MinecraftLauncherError,
+  LaunchError,
+  download_utils::{ Downloadable, ProxyOptions, HttpClientConfig, AssetDownloadable, download_job::DownloadJob, read_body },
+  profile_manager::auth::UserAuthentication,
+  progress_reporter::ProgressReporter,
+};
 
-use self::{ rule::{ Rule, OperatingSystem, FeatureMatcher, RuleAction }, library::Library, date::Date };
+use self::{ rule::{ Rule, OperatingSystem, FeatureMatcher, RuleAction, RuleFeatureType, is_windows_ten }, library::{ Library, LibrarySource }, date::Date, artifact::{ Artifact, join_path_string } };
 
-use super::{ info::{ ReleaseType, MCVersion, RemoteVersionInfo, VersionInfo }, VersionManager };
+use super::{ info::{ ReleaseType, MCVersion, RemoteVersionInfo, VersionInfo }, VersionManager, get_asset_object_path };
 
 const VERSION_MANIFEST_URL: &str = "https://piston-meta.mojang.com/mc/game/version_manifest_v2.json";
+const PATCH_NOTES_URL: &str = "https://launchercontent.mojang.com/javaPatchNotes.json";
+pub const LAUNCHER_CONTENT_URL: &str = "https://launchercontent.mojang.com/";
+const NEWS_URL: &str = "https://launchercontent.mojang.com/news.json";
+pub const ASSET_CDN_BASE_URL: &str = "https://resources.download.minecraft.net/";
+const SESSION_PROFILE_URL: &str = "https://sessionserver.mojang.com/session/minecraft/profile/";
+const MAX_VERSION_MANIFEST_SIZE: u64 = 10 * 1024 * 1024;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RawVersionList {
@@ -25,8 +41,98 @@ pub struct RawVersionList {
 }
 
 impl RawVersionList {
-  pub async fn fetch() -> Result<RawVersionList, reqwest::Error> {
-    Client::new().get(VERSION_MANIFEST_URL).send().await?.json::<RawVersionList>().await
+  pub async fn fetch(client: &Client) -> Result<RawVersionList, Box<dyn std::error::Error>> {
+    let res = client.get(VERSION_MANIFEST_URL).send().await?.error_for_status()?;
+    let body = read_body(VERSION_MANIFEST_URL, res, Some(MAX_VERSION_MANIFEST_SIZE)).await?;
+    Ok(serde_json::from_slice(&body)?)
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RawPatchNotes {
+  pub entries: Vec<PatchNotesEntry>, // Newest first
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PatchNotesEntry {
+  pub title: String,
+  pub version: MCVersion,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub body: Option<String>, // HTML
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub content_path: Option<String>,
+  // Kept as strings so one odd entry doesn't fail the whole list
+  #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+  pub release_type: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub date: Option<String>,
+}
+
+impl RawPatchNotes {
+  pub async fn fetch(client: &Client) -> Result<RawPatchNotes, reqwest::Error> {
+    client.get(PATCH_NOTES_URL).send().await?.json::<RawPatchNotes>().await
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RawNews {
+  pub entries: Vec<RawNewsEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RawNewsEntry {
+  pub title: String,
+  pub date: String, // yyyy-mm-dd
+  pub text: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub news_page_image: Option<RawNewsImage>,
+  pub read_more_link: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RawNewsImage {
+  pub url: String, // Relative to the launcher content url
+}
+
+impl RawNews {
+  pub async fn fetch(client: &Client) -> Result<RawNews, reqwest::Error> {
+    client.get(NEWS_URL).send().await?.json::<RawNews>().await
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RawPlayerProfile {
+  pub id: String,
+  pub name: String,
+  #[serde(default)]
+  pub properties: Vec<RawProfileProperty>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RawProfileProperty {
+  pub name: String,
+  pub value: String, // Base64 encoded json for "textures"
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RawTexturesPayload {
+  #[serde(default)]
+  pub textures: HashMap<String, RawTexture>, // SKIN and CAPE
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RawTexture {
+  pub url: String,
+  #[serde(default)]
+  pub metadata: HashMap<String, String>, // model = slim for slim skins
+}
+
+impl RawPlayerProfile {
+  pub async fn fetch(client: &Client, uuid: &Uuid) -> Result<RawPlayerProfile, reqwest::Error> {
+    let url = format!("{SESSION_PROFILE_URL}{}", uuid.simple());
+    client.get(url).send().await?.error_for_status()?.json::<RawPlayerProfile>().await
   }
 }
 
@@ -48,6 +154,16 @@ impl Sha1Sum {
     sha1_hasher.update(&buf);
     Ok(Sha1Sum(sha1_hasher.finalize().into()))
   }
+
+  pub fn from_file(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+    Self::from_reader(&mut File::open(path)?)
+  }
+
+  pub async fn from_file_async(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+    let mut buf = vec![];
+    tokio::fs::File::open(path).await?.read_to_end(&mut buf).await?;
+    Ok(Sha1Sum(Sha1::digest(&buf).into()))
+  }
 }
 
 impl TryFrom<String> for Sha1Sum {
@@ -97,7 +213,7 @@ pub enum Argument {
 }
 
 impl Argument {
-  pub fn apply(&self, matcher: &impl FeatureMatcher) -> Option<Vec<&String>> {
+  pub fn apply(&self, matcher: &dyn FeatureMatcher) -> Option<Vec<&String>> {
     if self.applies_to_current_environment(matcher) { Some(self.value()) } else { None }
   }
 
@@ -108,7 +224,7 @@ impl Argument {
     }
   }
 
-  pub fn applies_to_current_environment(&self, matcher: &impl FeatureMatcher) -> bool {
+  pub fn applies_to_current_environment(&self, matcher: &dyn FeatureMatcher) -> bool {
     match self {
       Argument::Value(_) => true,
       Argument::Object { rules, .. } => {
@@ -151,6 +267,14 @@ pub struct AssetIndexInfo {
   pub url: String,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MainClassType {
+  LegacyClient,
+  ModernClient,
+  Server,
+  Other(String), // Mod loaders, custom wrappers
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
 #[serde(rename_all = "snake_case")]
 pub enum DownloadType {
@@ -214,6 +338,8 @@ pub struct LocalVersionInfo {
   pub asset_index: Option<AssetIndexInfo>,
   #[serde(default, skip_serializing_if = "Option::is_none")]
   assets: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub asset_base_url: Option<String>, // Not part of the vanilla format, see VersionManager::get_asset_cdn_base_url
   #[serde(default, skip_serializing_if = "Vec::is_empty")]
   compatibility_rules: Vec<Rule>,
   #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -230,7 +356,7 @@ pub struct LocalVersionInfo {
   #[serde(default, skip_serializing_if = "HashMap::is_empty")]
   logging: HashMap<DownloadType, LoggingEntry>,
   #[serde(default, skip_serializing_if = "Option::is_none")]
-  main_class: Option<String>,
+  pub main_class: Option<String>,
   #[serde(default, skip_serializing_if = "Option::is_none")]
   jar: Option<MCVersion>,
   #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -272,15 +398,16 @@ impl LocalVersionInfo {
     &self,
     os: &OperatingSystem,
     proxy: &ProxyOptions,
+    http_config: &HttpClientConfig,
     mc_dir: &PathBuf,
     force_download: bool,
-    matcher: &impl FeatureMatcher
+    matcher: &dyn FeatureMatcher
   ) -> Vec<Box<dyn Downloadable + Send + Sync>> {
     let mut vec = vec![];
     for lib in self.get_relevant_libraries(matcher) {
       let classifier = if !lib.natives.is_empty() {
-        if let Some(native) = lib.natives.get(os) {
-          Some(native.as_str())
+        if let Some(native) = lib.native_classifier(os) {
+          Some(native)
         } else {
           continue;
         }
@@ -289,12 +416,12 @@ impl LocalVersionInfo {
       };
 
       let mut name = lib.name.clone();
-      if let Some(classifier) = classifier {
-        name.classifier = Some(classifier.to_string());
+      if let Some(classifier) = &classifier {
+        name.classifier = Some(classifier.clone());
       }
 
-      let file = name.get_local_path(&mc_dir.join("libraries"));
-      let downloadable = lib.create_download(proxy, &name.get_path_string(), &file, force_download, classifier);
+      let file = name.get_local_path(&VersionManager::get_library_dir(mc_dir));
+      let downloadable = lib.create_download(proxy, http_config, &name.get_path_string(), &file, force_download, classifier.as_deref());
       if let Some(downloadable) = downloadable {
         vec.push(downloadable);
       }
@@ -307,8 +434,8 @@ impl LocalVersionInfo {
     let libraries = self.get_relevant_libraries(matcher);
     for library in libraries {
       if !library.natives.is_empty() {
-        if let Some(native) = library.natives.get(os) {
-          set.insert(format!("libraries/{}", library.get_artifact_path(Some(native.as_str()))));
+        if let Some(native) = library.native_classifier(os) {
+          set.insert(format!("libraries/{}", library.get_artifact_path(Some(&native))));
         }
       } else {
         set.insert(format!("libraries/{}", library.get_artifact_path(None)));
@@ -321,14 +448,211 @@ impl LocalVersionInfo {
     self.jar.as_ref().unwrap_or(self.get_id())
   }
 
-  pub fn get_main_class(&self) -> &String {
-    self.main_class.as_ref().unwrap()
+  pub fn get_libraries(&self) -> &Vec<Library> {
+    &self.libraries
+  }
+
+  pub fn get_compatibility_rules(&self) -> &Vec<Rule> {
+    &self.compatibility_rules
+  }
+
+  pub fn get_java_version(&self) -> Option<&JavaVersionInfo> {
+    self.java_version.as_ref()
+  }
+
+  // Component of Mojang's java runtime distribution, e.g. "java-runtime-gamma"
+  pub fn get_runtime_id(&self) -> Option<&str> {
+    self.java_version.as_ref().map(|java_version| java_version.component.as_str())
+  }
+
+  pub fn get_runtime_major_version(&self) -> Option<u8> {
+    self.java_version.as_ref().and_then(|java_version| u8::try_from(java_version.major_version).ok())
+  }
+
+  pub fn get_inherits_from(&self) -> Option<&MCVersion> {
+    self.inherits_from.as_ref()
+  }
+
+  // Only changes the in-memory value, call write_to_file to persist it
+  pub fn get_type_mut(&mut self) -> &mut ReleaseType {
+    &mut self.release_type
+  }
+
+  // Only changes the in-memory value, call write_to_file to persist it
+  pub fn set_type(&mut self, release_type: ReleaseType) {
+    self.release_type = release_type;
+  }
+
+  pub fn write_to_file(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(parent) = path.parent() {
+      std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_vec_pretty(self)?)?;
+    Ok(())
+  }
+
+  pub fn get_type_display_name(&self) -> &str {
+    self.release_type.get_display_name()
+  }
+
+  pub fn get_main_class(&self) -> Result<&String, LaunchError> {
+    self.main_class
+      .as_ref()
+      .filter(|main_class| !main_class.trim().is_empty())
+      .ok_or_else(|| LaunchError::MissingMainClass(self.id.clone()))
+  }
+
+  // Only changes the in-memory version, meant to be applied after resolve() to wrap the game with a mod loader.
+  // get_main_class_type() reports the new class afterwards, e.g. Other(KnotClient) instead of ModernClient
+  pub fn patch_main_class(&mut self, new_main: &str) {
+    self.main_class = Some(new_main.to_string());
+  }
+
+  pub fn get_main_class_type(&self) -> MainClassType {
+    match self.main_class.as_deref().unwrap_or_default() {
+      "net.minecraft.client.main.Main" => MainClassType::ModernClient,
+      "net.minecraft.client.Main" | "net.minecraft.client.Minecraft" | "net.minecraft.launchwrapper.Launch" => MainClassType::LegacyClient,
+      "net.minecraft.server.Main" | "net.minecraft.server.MinecraftServer" | "net.minecraft.bundler.Main" => MainClassType::Server,
+      main_class => MainClassType::Other(main_class.to_string()),
+    }
+  }
+
+  // 1.6-1.12 use the modern main class with legacy arguments, so the argument data still has the last word
+  fn uses_modern_arguments(&self) -> bool {
+    self.get_main_class_type() != MainClassType::LegacyClient && !self.arguments.is_empty()
+  }
+
+  // Arguments that apply to the current environment, falling back to the legacy minecraftArguments format
+  pub fn get_arguments_flat(&self, arg_type: ArgumentType, matcher: &dyn FeatureMatcher) -> Vec<String> {
+    if self.uses_modern_arguments() {
+      return self.arguments
+        .get(&arg_type)
+        .map(|arguments| {
+          arguments
+            .iter()
+            .filter_map(|argument| argument.apply(matcher))
+            .flatten()
+            .cloned()
+            .collect()
+        })
+        .unwrap_or_default();
+    }
+    match arg_type {
+      ArgumentType::Jvm => self.get_legacy_jvm_arguments(),
+      ArgumentType::Game => self.get_legacy_game_arguments(matcher),
+    }
+  }
+
+  pub fn get_jvm_arguments(&self, matcher: &dyn FeatureMatcher) -> Vec<String> {
+    self.get_arguments_flat(ArgumentType::Jvm, matcher)
+  }
+
+  pub fn get_game_arguments(&self, matcher: &dyn FeatureMatcher) -> Vec<String> {
+    self.get_arguments_flat(ArgumentType::Game, matcher)
+  }
+
+  fn get_legacy_jvm_arguments(&self) -> Vec<String> {
+    if self.minecraft_arguments.is_none() {
+      return vec![];
+    }
+
+    let mut args = vec![];
+    let os = OperatingSystem::get_current_platform();
+    if os == OperatingSystem::Windows {
+      args.push("-XX:HeapDumpPath=MojangTricksIntelDriversForPerformance_javaw.exe_minecraft.exe.heapdump");
+      if is_windows_ten() {
+        args.extend(["-Dos.name=Windows 10", "-Dos.version=10.0"]);
+      }
+    } else if os == OperatingSystem::Osx {
+      args.extend(["-Xdock:icon=${asset=icons/minecraft.icns}", "-Xdock:name=Minecraft"]);
+    }
+    args.extend([
+      "-Djava.library.path=${natives_directory}",
+      "-Dminecraft.launcher.brand=${launcher_name}",
+      "-Dminecraft.launcher.version=${launcher_version}",
+      "-Dminecraft.client.jar=${primary_jar}",
+      "-cp",
+      "${classpath}",
+    ]);
+    args
+      .into_iter()
+      .map(|arg| arg.to_string())
+      .collect()
+  }
+
+  fn get_legacy_game_arguments(&self, matcher: &dyn FeatureMatcher) -> Vec<String> {
+    let Some(minecraft_arguments) = &self.minecraft_arguments else {
+      return vec![];
+    };
+
+    let mut args: Vec<String> = minecraft_arguments
+      .split(" ")
+      .map(|s| s.to_string())
+      .collect();
+    if matcher.has_feature(&RuleFeatureType::IsDemoUser, &serde_json::Value::Bool(true)) {
+      args.push("--demo".to_string());
+    }
+    if matcher.has_feature(&RuleFeatureType::HasCustomResolution, &serde_json::Value::Bool(true)) {
+      args.extend(["--width", "${resolution_width}", "--height", "${resolution_height}"].map(|s| s.to_string()));
+    }
+    args
+  }
+
+  // Every feature checked by the argument and library rules of this version
+  pub fn get_referenced_features(&self) -> HashSet<RuleFeatureType> {
+    let argument_rules = self.arguments
+      .values()
+      .flatten()
+      .filter_map(|argument| {
+        match argument {
+          Argument::Object { rules, .. } => Some(rules),
+          Argument::Value(_) => None,
+        }
+      })
+      .flatten();
+    let library_rules = self.libraries.iter().flat_map(|library| &library.rules);
+    argument_rules
+      .chain(library_rules)
+      .filter_map(|rule| rule.features.as_ref())
+      .flat_map(|features| features.keys().cloned())
+      .collect()
+  }
+
+  pub fn applicable_arguments_count(&self, matcher: &dyn FeatureMatcher) -> usize {
+    self.get_jvm_arguments(matcher).len() + self.get_game_arguments(matcher).len()
   }
 
   pub fn get_download_url(&self, download_type: DownloadType) -> Option<&DownloadInfo> {
     self.downloads.get(&download_type)
   }
 
+  pub fn compute_client_sha1(&self, game_dir: &Path) -> Result<Sha1Sum, Box<dyn std::error::Error>> {
+    let jar_id = self.get_jar().to_string();
+    Sha1Sum::from_file(&game_dir.join("versions").join(&jar_id).join(format!("{jar_id}.jar")))
+  }
+
+  // Sha1, size and url of the index json itself
+  pub fn get_asset_index_download_info(&self) -> Option<DownloadInfo> {
+    self.asset_index.as_ref().map(|asset_index| DownloadInfo {
+      sha1: asset_index.sha1.clone(),
+      size: asset_index.size,
+      url: asset_index.url.clone(),
+    })
+  }
+
+  pub fn get_asset_index_url(&self) -> Option<&str> {
+    self.asset_index.as_ref().map(|asset_index| asset_index.url.as_str())
+  }
+
+  pub fn get_asset_index_sha1(&self) -> Option<&Sha1Sum> {
+    self.asset_index.as_ref().map(|asset_index| &asset_index.sha1)
+  }
+
+  // None for a missing index or a negative size
+  pub fn get_asset_index_size(&self) -> Option<u64> {
+    self.asset_index.as_ref().and_then(|asset_index| u64::try_from(asset_index.size).ok())
+  }
+
   pub fn applies_to_current_environment(&self, matcher: &impl FeatureMatcher) -> bool {
     if !self.compatibility_rules.is_empty() {
       let mut action = RuleAction::Disallow;
@@ -344,13 +668,16 @@ impl LocalVersionInfo {
     }
   }
 
-  pub fn get_classpath(&self, _os: &OperatingSystem, mc_dir: &PathBuf, matcher: &impl FeatureMatcher) -> Vec<PathBuf> {
+  pub fn get_classpath(&self, os: &OperatingSystem, mc_dir: &Path, matcher: &impl FeatureMatcher) -> Vec<PathBuf> {
     let mut vec = vec![];
-    let libraries = self.get_relevant_libraries(matcher);
-    for library in libraries {
-      if library.natives.is_empty() {
-        vec.push(mc_dir.join("libraries").join(library.get_artifact_path(None).replace("/", MAIN_SEPARATOR_STR)));
+    let mut added: Vec<&Library> = vec![];
+    for library in self.libraries.iter().filter(|library| library.applies_for_classpath(os, matcher)) {
+      // Inheriting versions list their libraries first, so their version of a library wins over the parent's
+      if added.iter().any(|other| other.name.matches_without_version(&library.name)) {
+        continue;
       }
+      added.push(library);
+      vec.push(library.get_path(mc_dir));
     }
 
     let jar_id = self.get_jar().to_string();
@@ -358,6 +685,73 @@ impl LocalVersionInfo {
     vec
   }
 
+  // Same entries and order as get_classpath after resolving, parents are read from the installed version jsons
+  pub fn get_classpath_with_sources(&self, os: &OperatingSystem, mc_dir: &Path, matcher: &impl FeatureMatcher) -> Vec<(PathBuf, LibrarySource)> {
+    let mut entries = vec![];
+    let mut added: Vec<Artifact> = vec![];
+    let mut jar = None;
+    let mut visited = HashSet::new();
+    let mut current = Some(self.clone());
+    while let Some(version) = current {
+      if !visited.insert(version.id.clone()) {
+        break;
+      }
+      for library in version.libraries.iter().filter(|library| library.applies_for_classpath(os, matcher)) {
+        if added.iter().any(|other| other.matches_without_version(&library.name)) {
+          continue;
+        }
+        added.push(library.name.clone());
+        let source = if version.id == self.id {
+          LibrarySource::VanillaLibrary(library.name.clone())
+        } else {
+          LibrarySource::InheritedLibrary(library.name.clone(), version.id.clone())
+        };
+        entries.push((library.get_path(mc_dir), source));
+      }
+      jar = jar.or(version.jar.clone());
+
+      // A parent that isn't installed just ends the chain
+      current = version.inherits_from.as_ref().and_then(|parent| {
+        let parent = parent.to_string();
+        let file = File::open(mc_dir.join("versions").join(&parent).join(format!("{parent}.json"))).ok()?;
+        serde_json::from_reader::<_, LocalVersionInfo>(file).ok()
+      });
+    }
+
+    let jar = jar.unwrap_or(self.id.clone());
+    let jar_id = jar.to_string();
+    entries.push((mc_dir.join("versions").join(&jar_id).join(format!("{jar_id}.jar")), LibrarySource::VersionJar(jar)));
+    entries
+  }
+
+  pub fn requires_internet(&self, mc_dir: &Path, matcher: &impl FeatureMatcher, authentication: &dyn UserAuthentication) -> bool {
+    if !authentication.is_offline() {
+      return true;
+    }
+
+    let os = OperatingSystem::get_current_platform();
+    let has_libraries = self
+      .get_required_files(&os, matcher)
+      .iter()
+      .all(|file| join_path_string(mc_dir, file).is_file());
+    let jar_id = self.get_jar().to_string();
+    if !has_libraries || !mc_dir.join("versions").join(&jar_id).join(format!("{jar_id}.jar")).is_file() {
+      return true;
+    }
+
+    let assets_dir = VersionManager::get_assets_dir(mc_dir);
+    let asset_index = self.asset_index
+      .as_ref()
+      .and_then(|info| AssetIndex::from_file(&assets_dir.join("indexes").join(format!("{}.json", info.id))).ok());
+    if let Some(asset_index) = asset_index {
+      !asset_index.objects
+        .values()
+        .all(|obj| get_asset_object_path(&assets_dir, &obj.hash).is_file())
+    } else {
+      true
+    }
+  }
+
   #[async_recursion]
   pub async fn resolve(
     &self,
@@ -407,6 +801,10 @@ impl LocalVersionInfo {
       local_version.assets = Some(assets.clone());
     }
 
+    if let Some(asset_base_url) = &self.asset_base_url {
+      local_version.asset_base_url = Some(asset_base_url.clone());
+    }
+
     if let Some(jar) = &self.jar {
       local_version.jar = Some(jar.clone());
     }
@@ -458,7 +856,33 @@ pub struct AssetIndex {
   pub is_virtual: bool
 }
 
+// Virtual paths, sorted
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AssetDiff {
+  pub added: Vec<String>,
+  pub removed: Vec<String>,
+  pub changed: Vec<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum AssetIndexError {
+  #[error("Asset index not found: {0}")]
+  NotFound(PathBuf),
+  #[error("Failed to read asset index: {0}")]
+  Io(#[from] std::io::Error),
+  #[error("Failed to parse asset index: {0}")]
+  ParseFailed(serde_json::Error),
+}
+
 impl AssetIndex {
+  pub fn from_file(path: &Path) -> Result<AssetIndex, AssetIndexError> {
+    let file = File::open(path).map_err(|err| match err.kind() {
+      std::io::ErrorKind::NotFound => AssetIndexError::NotFound(path.to_path_buf()),
+      _ => AssetIndexError::Io(err),
+    })?;
+    serde_json::from_reader(file).map_err(AssetIndexError::ParseFailed)
+  }
+
   pub fn get_file_map(&self) -> HashMap<&String, &AssetObject> {
     self.objects.iter().collect()
   }
@@ -469,6 +893,81 @@ impl AssetIndex {
       .map(|(k, v)| (v, k))
       .collect()
   }
+
+  // Changes needed to go from self to other
+  pub fn diff(&self, other: &AssetIndex) -> AssetDiff {
+    let mut diff = AssetDiff::default();
+    for (name, obj) in &other.objects {
+      match self.objects.get(name) {
+        None => diff.added.push(name.clone()),
+        Some(old_obj) if old_obj.hash != obj.hash => diff.changed.push(name.clone()),
+        Some(_) => {}
+      }
+    }
+    diff.removed = self.objects
+      .keys()
+      .filter(|name| !other.objects.contains_key(*name))
+      .cloned()
+      .collect();
+
+    diff.added.sort();
+    diff.removed.sort();
+    diff.changed.sort();
+    diff
+  }
+
+  pub fn get_downloadables(
+    &self,
+    proxy: &ProxyOptions,
+    http_config: &HttpClientConfig,
+    objects_dir: &Path,
+    url_base: &Url,
+    force_download: bool
+  ) -> Vec<Box<dyn Downloadable + Send + Sync>> {
+    let mut vec: Vec<Box<dyn Downloadable + Send + Sync>> = vec![];
+    for (obj, name) in self.get_unique_objects() {
+      let downloadable = Box::new(
+        AssetDownloadable::new(
+          proxy.create_http_client_with_config(http_config),
+          name,
+          obj,
+          url_base.as_str(),
+          objects_dir,
+          force_download
+        )
+      );
+      downloadable.monitor.set_total(obj.size as usize);
+      vec.push(downloadable);
+    }
+    vec
+  }
+
+  // Only objects that aren't in objects_dir yet are queued, works without a VersionManager.
+  // url_base is the asset CDN, e.g. VersionManager::get_asset_cdn_base_url or a mirror
+  pub fn build_download_job(
+    &self,
+    proxy: &ProxyOptions,
+    http_config: &HttpClientConfig,
+    objects_dir: &Path,
+    url_base: &Url,
+    max_concurrent: usize,
+    max_attempts: u8,
+    reporter: &Arc<ProgressReporter>
+  ) -> DownloadJob {
+    let mut job = DownloadJob::builder()
+      .name("Resources".to_string())
+      .max_concurrent(max_concurrent.min(u16::MAX as usize) as u16)
+      .max_attempts(max_attempts)
+      .progress_reporter(Arc::clone(reporter))
+      .build();
+    let downloadables = self
+      .get_downloadables(proxy, http_config, objects_dir, url_base, false)
+      .into_iter()
+      .filter(|downloadable| !downloadable.get_target_file().is_file())
+      .collect();
+    job.add_downloadables(downloadables);
+    job
+  }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
@@ -502,6 +1001,439 @@ mod tests {
 
   use super::*;
 
+  #[test]
+  fn test_asset_index_from_file() -> Result<(), Box<dyn std::error::Error>> {
+    let path = std::env::temp_dir().join("mclc-test-asset-index.json");
+    std::fs::write(
+      &path,
+      r#"{"objects": {"icons/icon_16x16.png": {"hash": "bdf48ef6b5d0d23bbb02e17d04865216179f510a", "size": 3665}}, "virtual": true}"#
+    )?;
+    let asset_index = AssetIndex::from_file(&path)?;
+    std::fs::remove_file(&path)?;
+
+    assert!(asset_index.is_virtual);
+    assert!(!asset_index.map_to_resources);
+    assert_eq!(asset_index.objects["icons/icon_16x16.png"].size, 3665);
+
+    assert!(matches!(AssetIndex::from_file(&path), Err(AssetIndexError::NotFound(_))));
+    // A file in place of a directory isn't a missing index
+    #[cfg(unix)]
+    assert!(matches!(AssetIndex::from_file(Path::new("/dev/null/index.json")), Err(AssetIndexError::Io(_))));
+    Ok(())
+  }
+
+  #[test]
+  fn test_set_type_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    let mut local_version: LocalVersionInfo = serde_json::from_value(
+      serde_json::json!({
+        "id": "my-modpack",
+        "inheritsFrom": "23w46a",
+        "releaseTime": "2023-11-15T12:41:33+00:00",
+        "time": "2023-11-15T12:41:33+00:00",
+        "type": "snapshot"
+      })
+    )?;
+    local_version.set_type(ReleaseType::Release);
+    assert_eq!(local_version.get_type(), &ReleaseType::Release);
+
+    let path = std::env::temp_dir().join("mclc-test-set-type").join("my-modpack.json");
+    local_version.write_to_file(&path)?;
+    let written: LocalVersionInfo = serde_json::from_slice(&std::fs::read(&path)?)?;
+    std::fs::remove_dir_all(path.parent().unwrap())?;
+    assert_eq!(written.get_type(), &ReleaseType::Release);
+    Ok(())
+  }
+
+  #[test]
+  fn test_asset_index_diff() -> Result<(), Box<dyn std::error::Error>> {
+    let old: AssetIndex = serde_json::from_value(
+      serde_json::json!({ "objects": {
+        "icons/icon_16x16.png": { "hash": "bdf48ef6b5d0d23bbb02e17d04865216179f510a", "size": 3 },
+        "lang/en_us.json": { "hash": "9040a7d6cdf7a0d6cab1823831c6ceb7d01af97f", "size": 3 },
+        "sounds/old.ogg": { "hash": "f92e777f4341930bad9b2422283c4680d00dbc06", "size": 3 }
+      } })
+    )?;
+    let new: AssetIndex = serde_json::from_value(
+      serde_json::json!({ "objects": {
+        "icons/icon_16x16.png": { "hash": "bdf48ef6b5d0d23bbb02e17d04865216179f510a", "size": 3 },
+        "lang/en_us.json": { "hash": "f92e777f4341930bad9b2422283c4680d00dbc06", "size": 3 },
+        "sounds/new.ogg": { "hash": "f92e777f4341930bad9b2422283c4680d00dbc06", "size": 3 }
+      } })
+    )?;
+
+    let diff = old.diff(&new);
+    assert_eq!(diff.added, ["sounds/new.ogg"]);
+    assert_eq!(diff.removed, ["sounds/old.ogg"]);
+    assert_eq!(diff.changed, ["lang/en_us.json"]);
+    assert_eq!(new.diff(&new), AssetDiff::default());
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_build_download_job() -> Result<(), Box<dyn std::error::Error>> {
+    let objects_dir = std::env::temp_dir().join("mclc-test-build-download-job").join("objects");
+    let _ = std::fs::remove_dir_all(&objects_dir);
+    let asset_index: AssetIndex = serde_json::from_value(
+      serde_json::json!({ "objects": {
+        "icons/icon_16x16.png": { "hash": "bdf48ef6b5d0d23bbb02e17d04865216179f510a", "size": 3 },
+        "lang/en_us.json": { "hash": "9040a7d6cdf7a0d6cab1823831c6ceb7d01af97f", "size": 3 },
+        "lang/en_gb.json": { "hash": "9040a7d6cdf7a0d6cab1823831c6ceb7d01af97f", "size": 3 }
+      } })
+    )?;
+    let cached = get_asset_object_path(objects_dir.parent().unwrap(), &Sha1Sum::try_from("9040a7d6cdf7a0d6cab1823831c6ceb7d01af97f".to_string())?);
+    std::fs::create_dir_all(cached.parent().unwrap())?;
+    std::fs::write(&cached, "png")?;
+
+    let reporter = Arc::new(ProgressReporter::new(|_| {}));
+    let mirror = Url::parse("https://assets.example.com/")?;
+    let job = asset_index.build_download_job(&ProxyOptions::NoProxy, &HttpClientConfig::default(), &objects_dir, &mirror, 4, 5, &reporter);
+    assert_eq!(job.summary().queued, 1);
+    assert_eq!(job.dry_run().await[0].url, "https://assets.example.com/bd/bdf48ef6b5d0d23bbb02e17d04865216179f510a");
+
+    std::fs::remove_dir_all(objects_dir.parent().unwrap())?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_get_classpath_deduplicates_libraries() {
+    let version = version_info(
+      serde_json::json!({
+        "libraries": [
+          { "name": "org.ow2.asm:asm:9.6" },
+          { "name": "org.ow2.asm:asm-tree:9.3" },
+          { "name": "org.ow2.asm:asm:9.3" }
+        ]
+      })
+    );
+    let mc_dir = PathBuf::from("mc");
+    let classpath = version.get_classpath(&OperatingSystem::get_current_platform(), &mc_dir, &DemoFeatureMatcher);
+    let libraries_dir = VersionManager::get_library_dir(&mc_dir);
+    assert_eq!(classpath, [
+      libraries_dir.join("org").join("ow2").join("asm").join("asm").join("9.6").join("asm-9.6.jar"),
+      libraries_dir.join("org").join("ow2").join("asm").join("asm-tree").join("9.3").join("asm-tree-9.3.jar"),
+      mc_dir.join("versions").join("1.7.10").join("1.7.10.jar"),
+    ]);
+  }
+
+  #[test]
+  fn test_get_classpath_with_sources() -> Result<(), Box<dyn std::error::Error>> {
+    let mc_dir = std::env::temp_dir().join("mclc-test-classpath-sources");
+    let _ = std::fs::remove_dir_all(&mc_dir);
+    let parent_dir = mc_dir.join("versions").join("1.7.10");
+    std::fs::create_dir_all(&parent_dir)?;
+    let parent = serde_json::json!({
+      "id": "1.7.10",
+      "releaseTime": "2014-05-14T17:29:23+00:00",
+      "time": "2014-05-14T17:29:23+00:00",
+      "type": "release",
+      "libraries": [{ "name": "org.ow2.asm:asm:9.3" }, { "name": "com.google.guava:guava:15.0" }]
+    });
+    std::fs::write(parent_dir.join("1.7.10.json"), parent.to_string())?;
+
+    let mut version = version_info(
+      serde_json::json!({
+        "inheritsFrom": "1.7.10",
+        "libraries": [{ "name": "org.ow2.asm:asm:9.6" }]
+      })
+    );
+    version.id = MCVersion::new("1.7.10-forge");
+    let classpath = version.get_classpath_with_sources(&OperatingSystem::get_current_platform(), &mc_dir, &DemoFeatureMatcher);
+    let libraries_dir = VersionManager::get_library_dir(&mc_dir);
+    assert_eq!(classpath, [
+      (
+        libraries_dir.join("org").join("ow2").join("asm").join("asm").join("9.6").join("asm-9.6.jar"),
+        LibrarySource::VanillaLibrary(Artifact::try_from("org.ow2.asm:asm:9.6".to_string())?),
+      ),
+      (
+        libraries_dir.join("com").join("google").join("guava").join("guava").join("15.0").join("guava-15.0.jar"),
+        LibrarySource::InheritedLibrary(Artifact::try_from("com.google.guava:guava:15.0".to_string())?, MCVersion::new("1.7.10")),
+      ),
+      (mc_dir.join("versions").join("1.7.10-forge").join("1.7.10-forge.jar"), LibrarySource::VersionJar(MCVersion::new("1.7.10-forge"))),
+    ]);
+
+    std::fs::remove_dir_all(mc_dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_compute_client_sha1() -> Result<(), Box<dyn std::error::Error>> {
+    let local_version: LocalVersionInfo = serde_json::from_value(
+      serde_json::json!({
+        "id": "my-modpack",
+        "jar": "1.20.2",
+        "releaseTime": "2023-11-15T12:41:33+00:00",
+        "time": "2023-11-15T12:41:33+00:00",
+        "type": "release"
+      })
+    )?;
+    let game_dir = std::env::temp_dir().join("mclc-test-client-sha1");
+    let jar_dir = game_dir.join("versions").join("1.20.2");
+    std::fs::create_dir_all(&jar_dir)?;
+    std::fs::write(jar_dir.join("1.20.2.jar"), "jar")?;
+    let sha1 = local_version.compute_client_sha1(&game_dir);
+    std::fs::remove_dir_all(&game_dir)?;
+    assert_eq!(sha1?.to_string(), "f92e777f4341930bad9b2422283c4680d00dbc06");
+    assert!(local_version.compute_client_sha1(&game_dir).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn test_get_main_class_type() {
+    let main_class_type = |main_class: &str| {
+      let local_version: LocalVersionInfo = serde_json::from_value(
+        serde_json::json!({
+          "id": "1.12.2",
+          "releaseTime": "2017-09-18T08:39:46+00:00",
+          "time": "2017-09-18T08:39:46+00:00",
+          "type": "release",
+          "mainClass": main_class
+        })
+      ).unwrap();
+      local_version.get_main_class_type()
+    };
+    assert_eq!(main_class_type("net.minecraft.client.main.Main"), MainClassType::ModernClient);
+    assert_eq!(main_class_type("net.minecraft.launchwrapper.Launch"), MainClassType::LegacyClient);
+    assert_eq!(main_class_type("net.minecraft.server.Main"), MainClassType::Server);
+    assert_eq!(
+      main_class_type("net.fabricmc.loader.impl.launch.knot.KnotClient"),
+      MainClassType::Other("net.fabricmc.loader.impl.launch.knot.KnotClient".to_string())
+    );
+  }
+
+  #[test]
+  fn test_patch_main_class() {
+    let mut local_version = version_info(serde_json::json!({ "mainClass": "net.minecraft.client.main.Main" }));
+    local_version.patch_main_class("net.fabricmc.loader.impl.launch.knot.KnotClient");
+    assert_eq!(local_version.get_main_class().unwrap(), "net.fabricmc.loader.impl.launch.knot.KnotClient");
+    assert_eq!(local_version.get_main_class_type(), MainClassType::Other("net.fabricmc.loader.impl.launch.knot.KnotClient".to_string()));
+  }
+
+  struct DemoFeatureMatcher;
+
+  impl FeatureMatcher for DemoFeatureMatcher {
+    fn has_feature(&self, feature_type: &RuleFeatureType, _value: &Value) -> bool {
+      feature_type == &RuleFeatureType::IsDemoUser
+    }
+  }
+
+  fn version_info(extra: Value) -> LocalVersionInfo {
+    let mut json = serde_json::json!({
+      "id": "1.7.10",
+      "releaseTime": "2014-05-14T17:29:23+00:00",
+      "time": "2014-05-14T17:29:23+00:00",
+      "type": "release"
+    });
+    json.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+    serde_json::from_value(json).unwrap()
+  }
+
+  #[test]
+  fn test_asset_index_accessors() {
+    let version = version_info(serde_json::json!({}));
+    assert_eq!(version.get_asset_index_url(), None);
+    assert_eq!(version.get_asset_index_size(), None);
+
+    let version = version_info(
+      serde_json::json!({
+      "assetIndex": {
+        "id": "1.7.10",
+        "sha1": "1863782e33ce7b584fc45b037325a1964e095d3e",
+        "size": 72996,
+        "totalSize": 112396854,
+        "url": "https://launchermeta.mojang.com/v1/packages/1863782e33ce7b584fc45b037325a1964e095d3e/1.7.10.json"
+      }
+    })
+    );
+    assert_eq!(
+      version.get_asset_index_url(),
+      Some("https://launchermeta.mojang.com/v1/packages/1863782e33ce7b584fc45b037325a1964e095d3e/1.7.10.json")
+    );
+    assert_eq!(version.get_asset_index_sha1().map(|sha1| sha1.to_string()), Some("1863782e33ce7b584fc45b037325a1964e095d3e".to_string()));
+    assert_eq!(version.get_asset_index_size(), Some(72996));
+    assert_eq!(version.get_asset_index_download_info().map(|info| info.size), version.asset_index.as_ref().map(|asset_index| asset_index.size));
+  }
+
+  #[test]
+  fn test_legacy_arguments() {
+    let version = version_info(serde_json::json!({ "minecraftArguments": "--username ${auth_player_name} --version ${version_name}" }));
+    assert_eq!(version.get_game_arguments(&DemoFeatureMatcher), ["--username", "${auth_player_name}", "--version", "${version_name}", "--demo"]);
+    assert!(version.get_jvm_arguments(&DemoFeatureMatcher).ends_with(&["-cp".to_string(), "${classpath}".to_string()]));
+  }
+
+  #[test]
+  fn test_modern_arguments() {
+    let version = version_info(
+      serde_json::json!({
+        "arguments": {
+          "game": ["--username", "${auth_player_name}", { "rules": [{ "action": "allow", "features": { "is_demo_user": true } }], "value": "--demo" }],
+          "jvm": ["-cp", "${classpath}"]
+        }
+      })
+    );
+    assert_eq!(version.get_game_arguments(&DemoFeatureMatcher), ["--username", "${auth_player_name}", "--demo"]);
+    assert_eq!(version.get_jvm_arguments(&DemoFeatureMatcher), ["-cp", "${classpath}"]);
+    assert_eq!(version.applicable_arguments_count(&DemoFeatureMatcher), 5);
+  }
+
+  #[test]
+  fn test_get_arguments_flat() {
+    let version = version_info(
+      serde_json::json!({
+        "arguments": {
+          "game": [
+            "--username",
+            { "rules": [{ "action": "allow", "features": { "has_custom_resolution": true } }], "value": ["--width", "${resolution_width}"] },
+            { "rules": [{ "action": "allow", "features": { "is_demo_user": true } }], "value": ["--demo"] }
+          ]
+        }
+      })
+    );
+    assert_eq!(version.get_arguments_flat(ArgumentType::Game, &DemoFeatureMatcher), ["--username", "--demo"]);
+    assert!(version.get_arguments_flat(ArgumentType::Jvm, &DemoFeatureMatcher).is_empty());
+
+    let legacy = version_info(serde_json::json!({ "minecraftArguments": "--username ${auth_player_name}" }));
+    assert_eq!(legacy.get_arguments_flat(ArgumentType::Game, &DemoFeatureMatcher), legacy.get_game_arguments(&DemoFeatureMatcher));
+    assert_eq!(legacy.get_arguments_flat(ArgumentType::Jvm, &DemoFeatureMatcher), legacy.get_jvm_arguments(&DemoFeatureMatcher));
+
+    let empty = version_info(serde_json::json!({}));
+    assert!(empty.get_arguments_flat(ArgumentType::Game, &DemoFeatureMatcher).is_empty());
+    assert!(empty.get_arguments_flat(ArgumentType::Jvm, &DemoFeatureMatcher).is_empty());
+  }
+
+  struct FeaturesMatcher(Vec<RuleFeatureType>);
+
+  impl FeatureMatcher for FeaturesMatcher {
+    fn has_feature(&self, feature_type: &RuleFeatureType, value: &Value) -> bool {
+      self.0.contains(feature_type) == (value == &Value::Bool(true))
+    }
+  }
+
+  fn argument(json: Value) -> Argument {
+    serde_json::from_value(json).unwrap()
+  }
+
+  fn other_platform() -> OperatingSystem {
+    match OperatingSystem::get_current_platform() {
+      OperatingSystem::Windows => OperatingSystem::Linux,
+      _ => OperatingSystem::Windows,
+    }
+  }
+
+  #[test]
+  fn test_argument_single_value() {
+    let arg = argument(serde_json::json!({ "rules": [{ "action": "allow", "features": { "is_demo_user": true } }], "value": "--demo" }));
+    assert_eq!(arg.apply(&FeaturesMatcher(vec![RuleFeatureType::IsDemoUser])), Some(vec![&"--demo".to_string()]));
+    assert_eq!(arg.apply(&FeaturesMatcher(vec![])), None);
+
+    let plain = argument(serde_json::json!("--username"));
+    assert_eq!(plain.apply(&FeaturesMatcher(vec![])), Some(vec![&"--username".to_string()]));
+  }
+
+  #[test]
+  fn test_argument_multi_value() {
+    let arg = argument(
+      serde_json::json!({
+        "rules": [{ "action": "allow", "features": { "has_custom_resolution": true } }],
+        "value": ["--width", "${resolution_width}", "--height", "${resolution_height}"]
+      })
+    );
+    let values: Vec<&str> = arg.apply(&FeaturesMatcher(vec![RuleFeatureType::HasCustomResolution])).unwrap().into_iter().map(String::as_str).collect();
+    assert_eq!(values, ["--width", "${resolution_width}", "--height", "${resolution_height}"]);
+    assert_eq!(arg.apply(&FeaturesMatcher(vec![RuleFeatureType::IsDemoUser])), None);
+  }
+
+  #[test]
+  fn test_argument_allow_rules() {
+    let current = argument(
+      serde_json::json!({ "rules": [{ "action": "allow", "os": { "name": OperatingSystem::get_current_platform() } }], "value": "-Dcurrent" })
+    );
+    let other = argument(serde_json::json!({ "rules": [{ "action": "allow", "os": { "name": other_platform() } }], "value": "-Dother" }));
+    assert!(current.applies_to_current_environment(&FeaturesMatcher(vec![])));
+    assert!(!other.applies_to_current_environment(&FeaturesMatcher(vec![])));
+
+    // Every feature of a rule has to match
+    let quick_play = argument(
+      serde_json::json!({
+        "rules": [{ "action": "allow", "features": { "has_quick_plays_support": true, "is_quick_play_singleplayer": true } }],
+        "value": ["--quickPlaySingleplayer", "${quickPlaySingleplayer}"]
+      })
+    );
+    assert!(!quick_play.applies_to_current_environment(&FeaturesMatcher(vec![RuleFeatureType::HasQuickPlaysSupport])));
+    assert!(
+      quick_play.applies_to_current_environment(
+        &FeaturesMatcher(vec![RuleFeatureType::HasQuickPlaysSupport, RuleFeatureType::IsQuickPlaySingleplayer])
+      )
+    );
+  }
+
+  #[test]
+  fn test_argument_disallow_rules() {
+    // The last matching rule wins
+    let not_current = argument(
+      serde_json::json!({
+        "rules": [{ "action": "allow" }, { "action": "disallow", "os": { "name": OperatingSystem::get_current_platform() } }],
+        "value": "-XstartOnFirstThread"
+      })
+    );
+    let not_other = argument(
+      serde_json::json!({ "rules": [{ "action": "allow" }, { "action": "disallow", "os": { "name": other_platform() } }], "value": "-Xss1M" })
+    );
+    assert!(!not_current.applies_to_current_environment(&FeaturesMatcher(vec![])));
+    assert!(not_other.applies_to_current_environment(&FeaturesMatcher(vec![])));
+
+    let not_demo = argument(
+      serde_json::json!({ "rules": [{ "action": "allow" }, { "action": "disallow", "features": { "is_demo_user": true } }], "value": "--fullAccess" })
+    );
+    assert!(not_demo.applies_to_current_environment(&FeaturesMatcher(vec![])));
+    assert!(!not_demo.applies_to_current_environment(&FeaturesMatcher(vec![RuleFeatureType::IsDemoUser])));
+  }
+
+  #[test]
+  fn test_conditional_jvm_and_game_arguments() {
+    let version = version_info(
+      serde_json::json!({
+        "arguments": {
+          "game": [
+            "--version", "${version_name}",
+            { "rules": [{ "action": "allow", "features": { "has_custom_resolution": true } }], "value": ["--width", "${resolution_width}", "--height", "${resolution_height}"] }
+          ],
+          "jvm": [
+            { "rules": [{ "action": "allow", "os": { "name": OperatingSystem::get_current_platform() } }], "value": ["-Dos.current=true"] },
+            { "rules": [{ "action": "allow", "os": { "name": other_platform() } }], "value": "-Dos.other=true" },
+            "-cp", "${classpath}"
+          ]
+        }
+      })
+    );
+
+    let resolution = FeaturesMatcher(vec![RuleFeatureType::HasCustomResolution]);
+    assert_eq!(version.get_game_arguments(&resolution), ["--version", "${version_name}", "--width", "${resolution_width}", "--height", "${resolution_height}"]);
+    assert_eq!(version.get_game_arguments(&FeaturesMatcher(vec![])), ["--version", "${version_name}"]);
+    assert_eq!(version.get_jvm_arguments(&resolution), ["-Dos.current=true", "-cp", "${classpath}"]);
+  }
+
+  #[test]
+  fn test_get_referenced_features() {
+    let version = version_info(
+      serde_json::json!({
+        "arguments": {
+          "game": [
+            "--demo-less",
+            { "rules": [{ "action": "allow", "features": { "is_demo_user": true } }], "value": "--demo" },
+            { "rules": [{ "action": "allow", "features": { "has_custom_resolution": true } }], "value": ["--width", "${resolution_width}"] }
+          ],
+          "jvm": [{ "rules": [{ "action": "allow", "os": { "name": "osx" } }], "value": "-XstartOnFirstThread" }]
+        },
+        "libraries": [{ "name": "com.example:lib:1.0", "rules": [{ "action": "allow", "features": { "is_quick_play_realms": true } }] }]
+      })
+    );
+    let features = version.get_referenced_features();
+    assert_eq!(
+      features,
+      HashSet::from([RuleFeatureType::IsDemoUser, RuleFeatureType::HasCustomResolution, RuleFeatureType::IsQuickPlayRealms])
+    );
+  }
+
   #[tokio::test]
   async fn test_version_parsing() -> Result<(), Box<dyn std::error::Error>> {
     let json: Value = Client::new().get("https://piston-meta.mojang.com/mc/game/version_manifest_v2.json").send().await?.json().await?;
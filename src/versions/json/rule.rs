@@ -1,4 +1,4 @@
-use std::{collections::HashMap, env::consts::{OS, ARCH}, fmt::Debug};
+use std::{collections::HashMap, env::{self, consts::{OS, ARCH}}, fmt::Debug, path::PathBuf};
 
 use os_info::Version;
 use regex::Regex;
@@ -26,7 +26,11 @@ pub struct Rule {
 
 impl Rule {
   pub fn get_applied_action(&self, feature_matcher: Option<&dyn FeatureMatcher>) -> Option<RuleAction> {
-    if self.os.is_some() && !&self.os.as_ref().unwrap().is_current_operating_system() {
+    self.get_applied_action_for_os(&OperatingSystem::get_current_platform(), feature_matcher)
+  }
+
+  pub fn get_applied_action_for_os(&self, os: &OperatingSystem, feature_matcher: Option<&dyn FeatureMatcher>) -> Option<RuleAction> {
+    if self.os.is_some() && !&self.os.as_ref().unwrap().matches_operating_system(os) {
       return None;
     } else {
       if let Some(features) = &self.features {
@@ -65,6 +69,19 @@ pub enum RuleFeatureType {
   IsQuickPlayRealms,
 }
 
+impl RuleFeatureType {
+  pub fn get_name(&self) -> &'static str {
+    match self {
+      Self::IsDemoUser => "is_demo_user",
+      Self::HasCustomResolution => "has_custom_resolution",
+      Self::HasQuickPlaysSupport => "has_quick_plays_support",
+      Self::IsQuickPlaySingleplayer => "is_quick_play_singleplayer",
+      Self::IsQuickPlayMultiplayer => "is_quick_play_multiplayer",
+      Self::IsQuickPlayRealms => "is_quick_play_realms",
+    }
+  }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub struct OsRestriction {
@@ -77,6 +94,14 @@ pub struct OsRestriction {
 }
 
 impl OsRestriction {
+  // The arch and version can only be checked for the current platform
+  pub fn matches_operating_system(&self, os: &OperatingSystem) -> bool {
+    if os == &OperatingSystem::get_current_platform() {
+      return self.is_current_operating_system();
+    }
+    self.name.as_ref().is_none_or(|name| name == os)
+  }
+
   pub fn is_current_operating_system(&self) -> bool {
     let OsRestriction { name, arch, version } = &self;
 
@@ -130,6 +155,25 @@ impl OperatingSystem {
         name.to_string()
     }
 
+    pub fn get_natives_classifier_string(&self) -> &'static str {
+        match self {
+            OperatingSystem::Linux => "natives-linux",
+            OperatingSystem::Windows => "natives-windows",
+            OperatingSystem::Osx => "natives-osx",
+            OperatingSystem::Unknown => "natives-unknown",
+        }
+    }
+
+    pub fn get_lwjgl3_classifier_string(&self, lwjgl_major_version: u32) -> &'static str {
+        if lwjgl_major_version < 3 {
+            return self.get_natives_classifier_string();
+        }
+        match self {
+            OperatingSystem::Osx => "natives-macos",
+            _ => self.get_natives_classifier_string(),
+        }
+    }
+
     pub fn get_aliases(&self) -> Vec<&str> {
         match self {
             OperatingSystem::Linux => vec!["linux", "unix"],
@@ -139,6 +183,16 @@ impl OperatingSystem {
         }
     }
 
+    // Value of ${arch} in natives classifiers (e.g. "natives-windows-${arch}", "natives-osx-${arch}")
+    pub fn architecture() -> &'static str {
+        match ARCH {
+            "x86_64" => "64",
+            "x86" => "32",
+            "aarch64" => "arm64",
+            arch => arch,
+        }
+    }
+
     pub fn is_supported(&self) -> bool {
         self != &Self::Unknown
     }
@@ -156,6 +210,16 @@ impl OperatingSystem {
         }
         Self::Unknown
     }
+
+    // Default location used by the official launcher
+    pub fn get_game_dir() -> PathBuf {
+        let home_dir = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE")).map(PathBuf::from).unwrap_or_default();
+        match Self::get_current_platform() {
+            OperatingSystem::Windows => env::var_os("APPDATA").map(PathBuf::from).unwrap_or(home_dir).join(".minecraft"),
+            OperatingSystem::Osx => home_dir.join("Library").join("Application Support").join("minecraft"),
+            _ => home_dir.join(".minecraft"),
+        }
+    }
 }
 
 pub fn get_arch() -> String {
@@ -173,4 +237,92 @@ pub fn get_os_version() -> String {
       Version::Custom(version) => version.clone(),
       _ => "unknown".to_string(),
   }
-}
\ No newline at end of file
+}
+pub fn is_windows_ten() -> bool {
+  let os = os_info::get();
+  os.os_type() == os_info::Type::Windows && os.edition().is_some_and(|edition| edition.contains("Windows 10"))
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+
+  fn assert_round_trip(rule: &Rule) {
+    let json = serde_json::to_string(rule).unwrap();
+    let parsed: Rule = serde_json::from_str(&json).unwrap();
+    assert_eq!(&parsed, rule, "round trip through {json}");
+  }
+
+  fn feature_types() -> [RuleFeatureType; 6] {
+    [
+      RuleFeatureType::IsDemoUser,
+      RuleFeatureType::HasCustomResolution,
+      RuleFeatureType::HasQuickPlaysSupport,
+      RuleFeatureType::IsQuickPlaySingleplayer,
+      RuleFeatureType::IsQuickPlayMultiplayer,
+      RuleFeatureType::IsQuickPlayRealms,
+    ]
+  }
+
+  #[test]
+  fn test_action_only_rule_round_trip() {
+    for action in [RuleAction::Allow, RuleAction::Disallow] {
+      assert_round_trip(&Rule { action, features: None, os: None });
+    }
+    let parsed: Rule = serde_json::from_value(json!({ "action": "allow" })).unwrap();
+    assert_eq!(parsed, Rule { action: RuleAction::Allow, features: None, os: None });
+  }
+
+  #[test]
+  fn test_feature_rule_round_trip() {
+    for action in [RuleAction::Allow, RuleAction::Disallow] {
+      for feature_type in feature_types() {
+        let features = HashMap::from([(feature_type, Value::Bool(true))]);
+        assert_round_trip(&Rule { action: action.clone(), features: Some(features), os: None });
+      }
+    }
+
+    let all_features = feature_types().into_iter().map(|feature_type| (feature_type, Value::Bool(false))).collect();
+    assert_round_trip(&Rule { action: RuleAction::Allow, features: Some(all_features), os: None });
+    assert_round_trip(&Rule { action: RuleAction::Allow, features: Some(HashMap::new()), os: None });
+  }
+
+  #[test]
+  fn test_os_rule_round_trip() {
+    for os in OperatingSystem::values() {
+      let restriction = OsRestriction { name: Some(os), arch: Some("x86".to_string()), version: Some("^10\\.".to_string()) };
+      assert_round_trip(&Rule { action: RuleAction::Disallow, features: None, os: Some(restriction) });
+    }
+    let restriction = OsRestriction { name: None, arch: None, version: None };
+    assert_round_trip(&Rule { action: RuleAction::Allow, features: None, os: Some(restriction) });
+  }
+
+  #[test]
+  fn test_os_and_feature_rule_round_trip() {
+    let rule = Rule {
+      action: RuleAction::Allow,
+      features: Some(HashMap::from([(RuleFeatureType::HasCustomResolution, Value::Bool(true))])),
+      os: Some(OsRestriction { name: Some(OperatingSystem::Osx), arch: None, version: None }),
+    };
+    assert_round_trip(&rule);
+
+    let parsed: Rule = serde_json::from_value(
+      json!({ "action": "allow", "features": { "has_custom_resolution": true }, "os": { "name": "osx" } })
+    ).unwrap();
+    assert_eq!(parsed, rule);
+  }
+
+  #[test]
+  fn test_natives_classifier_strings() {
+    assert_eq!(OperatingSystem::Linux.get_natives_classifier_string(), "natives-linux");
+    assert_eq!(OperatingSystem::Windows.get_natives_classifier_string(), "natives-windows");
+    assert_eq!(OperatingSystem::Osx.get_natives_classifier_string(), "natives-osx");
+
+    assert_eq!(OperatingSystem::Osx.get_lwjgl3_classifier_string(2), "natives-osx");
+    assert_eq!(OperatingSystem::Osx.get_lwjgl3_classifier_string(3), "natives-macos");
+    assert_eq!(OperatingSystem::Linux.get_lwjgl3_classifier_string(3), "natives-linux");
+    assert_eq!(OperatingSystem::Windows.get_lwjgl3_classifier_string(3), "natives-windows");
+  }
+}
@@ -0,0 +1,97 @@
+use std::{ collections::HashMap, env::consts::ARCH };
+
+use reqwest::Client;
+use serde::{ Deserialize, Serialize };
+
+use super::{ rule::OperatingSystem, DownloadInfo };
+
+const JAVA_RUNTIME_INDEX_URL: &str =
+  "https://launchermeta.mojang.com/v1/products/java-runtime/2ec0cc96c44e5a76b9c8b7c39df7210883d12871/all.json";
+
+// platform -> component -> available builds
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RawJavaRuntimeIndex(pub HashMap<String, HashMap<String, Vec<JavaRuntimeEntry>>>);
+
+impl RawJavaRuntimeIndex {
+  pub async fn fetch(client: &Client) -> Result<RawJavaRuntimeIndex, reqwest::Error> {
+    client.get(JAVA_RUNTIME_INDEX_URL).send().await?.json::<RawJavaRuntimeIndex>().await
+  }
+
+  pub fn get_entry(&self, platform: &str, component: &str) -> Option<&JavaRuntimeEntry> {
+    self.0.get(platform)?.get(component)?.first()
+  }
+
+  // Platform names used by the index, e.g. "windows-x64" or "mac-os-arm64"
+  pub fn get_current_platform() -> Option<&'static str> {
+    let platform = match (OperatingSystem::get_current_platform(), ARCH) {
+      (OperatingSystem::Windows, "x86_64") => "windows-x64",
+      (OperatingSystem::Windows, "x86") => "windows-x86",
+      (OperatingSystem::Windows, "aarch64") => "windows-arm64",
+      (OperatingSystem::Osx, "aarch64") => "mac-os-arm64",
+      (OperatingSystem::Osx, _) => "mac-os",
+      (OperatingSystem::Linux, "x86") => "linux-i386",
+      (OperatingSystem::Linux, _) => "linux",
+      _ => {
+        return None;
+      }
+    };
+    Some(platform)
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JavaRuntimeEntry {
+  pub manifest: DownloadInfo,
+  pub version: JavaRuntimeVersion,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JavaRuntimeVersion {
+  pub name: String,
+  pub released: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JavaRuntimeManifest {
+  pub files: HashMap<String, JavaRuntimeFile>, // Relative to the runtime directory
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JavaRuntimeFile {
+  Directory,
+  File {
+    #[serde(default)]
+    executable: bool,
+    downloads: JavaRuntimeFileDownloads,
+  },
+  Link {
+    target: String,
+  },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JavaRuntimeFileDownloads {
+  pub raw: DownloadInfo,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub lzma: Option<DownloadInfo>,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_java_runtime_manifest() {
+    let manifest: JavaRuntimeManifest = serde_json::from_str(
+      r#"{"files": {
+        "bin": {"type": "directory"},
+        "bin/java": {"type": "file", "executable": true, "downloads": {"raw": {"sha1": "bdf48ef6b5d0d23bbb02e17d04865216179f510a", "size": 3, "url": "https://example.com/java"}}},
+        "lib/libjli.so": {"type": "link", "target": "../libjli.so"}
+      }}"#
+    ).unwrap();
+    assert!(matches!(manifest.files["bin"], JavaRuntimeFile::Directory));
+    assert!(matches!(&manifest.files["bin/java"], JavaRuntimeFile::File { executable: true, downloads } if downloads.raw.size == 3));
+    assert!(matches!(&manifest.files["lib/libjli.so"], JavaRuntimeFile::Link { target } if target == "../libjli.so"));
+  }
+}
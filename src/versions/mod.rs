@@ -1,35 +1,392 @@
 pub mod info;
 pub mod json;
+pub mod forge;
 
 use std::{
-  path::{ PathBuf, MAIN_SEPARATOR_STR },
+  path::{ Path, PathBuf },
+  env::temp_dir,
   fs::{ read_dir, File, create_dir_all, self },
-  collections::HashSet,
+  io::{ self, Read },
+  collections::{ HashMap, HashSet },
   sync::{ Mutex, Arc },
-  io::Cursor,
   ops::Deref,
+  time::{ UNIX_EPOCH, Duration, Instant },
 };
 
+use async_trait::async_trait;
+use base64::{ Engine, engine::general_purpose::STANDARD };
+use ed25519_dalek::{ Signature, Signer, SigningKey, Verifier, VerifyingKey };
+use futures::{ Stream, StreamExt, stream::FuturesUnordered };
 use log::{ info, warn, error };
-use reqwest::Client;
+use rayon::prelude::*;
+use notify::{ Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher, event::ModifyKind };
+use chrono::{ DateTime, NaiveDate, TimeZone, Utc };
+use reqwest::{ Client, Url };
+use serde::{ Deserialize, Serialize };
+use serde_json::Value;
+use thiserror::Error;
+use tokio::sync::Semaphore;
+use sha2::{ Digest, Sha256 };
+use uuid::Uuid;
+use zip::ZipArchive;
 
 use crate::{
   MinecraftLauncherError,
-  download_utils::{ ProxyOptions, Downloadable, AssetDownloadable, download_job::DownloadJob, PreHashedDownloadable, EtagDownloadable },
+  download_utils::{
+    ProxyOptions,
+    HttpClientConfig,
+    Downloadable,
+    AssetDownloadable,
+    download_job::DownloadJob,
+    PreHashedDownloadable,
+    EtagDownloadable,
+  },
   MinecraftGameRunner,
+  progress_reporter::ProgressReporter,
+  java::{ JavaInstallation, JavaAgent, JavaAgentInfo, JavaAgentError },
 };
 
 use self::{
-  info::{ RemoteVersionInfo, MCVersion, VersionInfo },
-  json::{ RawVersionList, LocalVersionInfo, rule::{ OperatingSystem, FeatureMatcher }, AssetIndex, DownloadType },
+  forge::ForgeInstaller,
+  info::{ RemoteVersionInfo, MCVersion, VersionInfo, ReleaseType, ModLoaderType },
+  json::{ RawVersionList, RawPatchNotes, PatchNotesEntry, RawNews, RawNewsEntry, RawPlayerProfile, RawTexturesPayload, LAUNCHER_CONTENT_URL, date::Date, LocalVersionInfo, rule::{ OperatingSystem, FeatureMatcher, Rule, RuleAction }, runtime::{ RawJavaRuntimeIndex, JavaRuntimeManifest, JavaRuntimeFile }, artifact::{ Artifact, join_path_string }, AssetIndex, DownloadType, ASSET_CDN_BASE_URL, DownloadInfo, Sha1Sum },
 };
 
+// assets/objects/<first two hex digits>/<hash>
+pub fn get_asset_object_path(assets_dir: &Path, hash: &Sha1Sum) -> PathBuf {
+  let hash = hash.to_string();
+  assets_dir.join("objects").join(&hash[0..2]).join(&hash)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AssetIndexStats {
+  pub object_count: usize,
+  pub total_size: u64,
+  pub cached_count: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiskSpaceEstimate {
+  pub libraries_bytes: u64,
+  pub client_jar_bytes: u64,
+  pub assets_bytes: u64, // Includes the asset index json
+  pub total_bytes: u64,
+  pub already_cached_bytes: u64, // Part of total_bytes that is already on disk
+}
+
+// Errors prevent launching, warnings are things the launcher fixes by itself
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HealthStatus {
+  pub ok: bool,
+  pub warnings: Vec<String>,
+  pub errors: Vec<String>,
+}
+
+// Named apart from json::AssetIndexInfo, which describes the index referenced by a version json
+#[derive(Debug, Clone)]
+pub struct LocalAssetIndex {
+  pub id: String,
+  pub file_path: PathBuf,
+  pub object_count: Option<usize>, // Filled by read_object_count
+}
+
+impl LocalAssetIndex {
+  pub fn read_object_count(&mut self) -> Option<usize> {
+    if self.object_count.is_none() {
+      self.object_count = AssetIndex::from_file(&self.file_path)
+        .ok()
+        .map(|asset_index| asset_index.objects.len());
+    }
+    self.object_count
+  }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VersionSizeOptions {
+  pub include_assets: bool,
+  pub include_libraries: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChangelogInfo {
+  pub url: String,
+  pub title: String,
+  pub html_content: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchNote {
+  pub version: MCVersion,
+  pub release_type: ReleaseType,
+  pub url: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewsItem {
+  pub title: String,
+  pub date: Date,
+  pub summary: String,
+  pub image_url: Option<Url>,
+  pub article_url: Url,
+}
+
+impl NewsItem {
+  fn from_raw(entry: &RawNewsEntry) -> Result<NewsItem, Box<dyn std::error::Error>> {
+    let date = NaiveDate::parse_from_str(&entry.date, "%Y-%m-%d")?.and_hms_opt(0, 0, 0).unwrap();
+    let content_url = Url::parse(LAUNCHER_CONTENT_URL)?;
+    Ok(NewsItem {
+      title: entry.title.clone(),
+      date: Date::from(Utc.from_utc_datetime(&date).fixed_offset()),
+      summary: entry.text.clone(),
+      image_url: entry.news_page_image.as_ref().and_then(|image| content_url.join(&image.url).ok()),
+      article_url: Url::parse(&entry.read_more_link)?,
+    })
+  }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlayerProfile {
+  pub skin_url: Option<Url>,
+  pub cape_url: Option<Url>,
+  pub is_slim: bool,
+}
+
+impl PlayerProfile {
+  // Profiles without a textures property use the default skin
+  fn from_raw(raw: &RawPlayerProfile) -> Result<PlayerProfile, Box<dyn std::error::Error>> {
+    let Some(textures) = raw.properties.iter().find(|property| property.name == "textures") else {
+      return Ok(PlayerProfile::default());
+    };
+    let payload: RawTexturesPayload = serde_json::from_slice(&STANDARD.decode(&textures.value)?)?;
+    let texture_url = |name: &str| payload.textures.get(name).map(|texture| Url::parse(&texture.url)).transpose();
+    let skin = payload.textures.get("SKIN");
+    Ok(PlayerProfile {
+      skin_url: texture_url("SKIN")?,
+      cape_url: texture_url("CAPE")?,
+      is_slim: skin.and_then(|skin| skin.metadata.get("model")).is_some_and(|model| model == "slim"),
+    })
+  }
+}
+
+const NEWS_CACHE_DURATION: Duration = Duration::from_secs(15 * 60);
+const PROFILE_CACHE_DURATION: Duration = Duration::from_secs(5 * 60);
+const PATCH_NOTES_CACHE_DURATION: Duration = Duration::from_secs(10 * 60);
+const MAX_ASSET_INDEX_SIZE: u64 = 50 * 1024 * 1024;
+const CLIENT_SHA1_LOOKUP_CONCURRENCY: usize = 16;
+
+#[derive(Debug, Clone, Default)]
+pub struct MigrationReport {
+  pub copied: usize,
+  pub skipped: usize,
+  pub failed: usize,
+}
+
+#[derive(Error, Debug)]
+pub enum VersionError {
+  #[error("Version {0:?} is not in the version manifest")]
+  VersionNotFoundInManifest(MCVersion),
+  #[error("Version {0:?} has no server download")]
+  NoServerDownload(MCVersion),
+  #[error("Version {0:?} has no {1:?} download")]
+  NoMappings(MCVersion, DownloadType),
+  #[error("Game directory is locked by another launcher ({0})")]
+  GameDirLocked(PathBuf),
+  #[error("Version {0:?} is not installed")]
+  VersionNotInstalled(MCVersion),
+}
+
+// Holds the exclusive lock on <game_dir>/.lock until dropped
+#[derive(Debug)]
+pub struct VersionManagerGuard {
+  file: File,
+}
+
+impl Drop for VersionManagerGuard {
+  fn drop(&mut self) {
+    let _ = self.file.unlock();
+  }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VerificationStatus {
+  pub missing_files: usize,
+  pub corrupt_files: usize,
+  pub total_checked: usize,
+}
+
+impl VerificationStatus {
+  fn add(&mut self, other: VerificationStatus) {
+    self.missing_files += other.missing_files;
+    self.corrupt_files += other.corrupt_files;
+    self.total_checked += other.total_checked;
+  }
+
+  // Files without a known hash are only checked for presence
+  async fn check_file(&mut self, path: &Path, expected: Option<&Sha1Sum>) {
+    self.total_checked += 1;
+    if !path.is_file() {
+      self.missing_files += 1;
+    } else if let Some(expected) = expected {
+      if Sha1Sum::from_file_async(path).await.ok().as_ref() != Some(expected) {
+        self.corrupt_files += 1;
+      }
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryRecommendation {
+  pub min_mb: u64,
+  pub recommended_mb: u64,
+}
+
+impl MemoryRecommendation {
+  pub fn for_release_time(release_time: &Date) -> Self {
+    // 1.16 and 1.12 release dates
+    let modern = DateTime::parse_from_rfc3339("2020-06-23T16:20:52+00:00").unwrap();
+    let legacy = DateTime::parse_from_rfc3339("2017-06-02T13:50:27+00:00").unwrap();
+    let release_time = release_time.inner();
+    if release_time >= &modern {
+      Self { min_mb: 1024, recommended_mb: 2048 }
+    } else if release_time >= &legacy {
+      Self { min_mb: 512, recommended_mb: 1024 }
+    } else {
+      Self { min_mb: 256, recommended_mb: 512 }
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct ModEntry {
+  pub name: String, // Maven descriptor or plain name
+  pub url: String,
+  pub sha1: Sha1Sum,
+  pub size: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ServerInfo {
+  pub jar_download: DownloadInfo,
+  pub java_version_required: u8,
+  pub supports_bundler: bool, // Needs --bundlerMainClass instead of -jar
+}
+
+// Archive written by save_version_snapshot, the version json is kept byte for byte
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionSnapshot {
+  pub format_version: u32,
+  pub id: MCVersion,
+  pub saved_at: DateTime<Utc>,
+  pub sha1: Sha1Sum, // Of version_json
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub remote_version: Option<RemoteVersionInfo>,
+  pub version_json: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PruneReport {
+  pub deleted: Vec<PathBuf>,
+  pub bytes_freed: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionSummary {
+  pub id: MCVersion,
+  pub release_type: ReleaseType,
+  pub mod_loader: ModLoaderType,
+  pub base_version: Option<MCVersion>, // Root of the inheritance chain, None for versions that don't inherit
+  pub installed_size_bytes: u64, // Same as get_total_installation_size
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GcReport {
+  pub inspected: Vec<PathBuf>,
+  pub deleted: Vec<PathBuf>,
+  pub bytes_freed: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LibraryChange {
+  Added(Artifact),
+  Removed(Artifact),
+  Updated {
+    from: Artifact,
+    to: Artifact,
+  },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VersionChangeEvent {
+  Added(MCVersion),
+  Removed(MCVersion),
+}
+
+// Watching stops when the handle is dropped
+pub struct WatchHandle {
+  _watcher: RecommendedWatcher,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct VersionCache {
+  versions: Vec<VersionCacheEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VersionCacheEntry {
+  id: String,
+  #[serde(rename = "type")]
+  release_type: ReleaseType,
+  modified: u64,
+  version: LocalVersionInfo,
+}
+
+// What get_compatible_versions needs from a version json, so it only has to be fetched once
+#[derive(Debug, Clone)]
+struct VersionRequirements {
+  java_major_version: i64,
+  compatibility_rules: Vec<Rule>,
+}
+
+impl VersionRequirements {
+  fn from_version(local_version: &LocalVersionInfo) -> Self {
+    Self {
+      java_major_version: local_version.get_java_version().cloned().unwrap_or_default().major_version,
+      compatibility_rules: local_version.get_compatibility_rules().clone(),
+    }
+  }
+
+  fn is_compatible(&self, java_version: u8, os: &OperatingSystem, matcher: &dyn FeatureMatcher) -> bool {
+    if self.java_major_version > (java_version as i64) {
+      return false;
+    }
+    let mut action = if self.compatibility_rules.is_empty() { RuleAction::Allow } else { RuleAction::Disallow };
+    for rule in &self.compatibility_rules {
+      if let Some(applied_action) = rule.get_applied_action_for_os(os, Some(matcher)) {
+        action = applied_action;
+      }
+    }
+    action == RuleAction::Allow
+  }
+}
+
+// Extension point for Fabric, Forge, Quilt and other loaders. Installs the loader on top of `mc_version` and returns its
+// version json, which should inherit from `mc_version`
+#[async_trait]
+pub trait ModLoaderInstaller: Send + Sync {
+  async fn install(&self, manager: &VersionManager, mc_version: &MCVersion) -> Result<LocalVersionInfo, Box<dyn std::error::Error>>;
+}
+
 #[derive(Debug)]
 pub struct VersionManager {
   pub game_dir: PathBuf,
   pub feature_matcher: Box<dyn FeatureMatcher + Send + Sync>,
   remote_versions_cache: Arc<Mutex<Vec<RemoteVersionInfo>>>,
   local_versions_cache: Arc<Mutex<Vec<LocalVersionInfo>>>,
+  news_cache: Mutex<Option<(Instant, Vec<NewsItem>)>>,
+  patch_notes_cache: Mutex<Option<(Instant, Vec<PatchNotesEntry>)>>,
+  profile_cache: Mutex<HashMap<Uuid, (Instant, PlayerProfile)>>,
+  requirements_cache: Mutex<HashMap<MCVersion, VersionRequirements>>,
 }
 
 impl VersionManager {
@@ -39,9 +396,47 @@ impl VersionManager {
       feature_matcher,
       remote_versions_cache: Arc::new(Mutex::new(vec![])),
       local_versions_cache: Arc::new(Mutex::new(vec![])),
+      news_cache: Mutex::new(None),
+      patch_notes_cache: Mutex::new(None),
+      profile_cache: Mutex::new(HashMap::new()),
+      requirements_cache: Mutex::new(HashMap::new()),
+    }
+  }
+
+  // Fails instead of waiting when another instance is already writing to the same game dir
+  pub fn lock(&self) -> Result<VersionManagerGuard, Box<dyn std::error::Error>> {
+    create_dir_all(&self.game_dir)?;
+    let lock_file = self.game_dir.join(".lock");
+    let file = File::options().create(true).truncate(false).write(true).open(&lock_file)?;
+    match file.try_lock() {
+      Ok(()) => Ok(VersionManagerGuard { file }),
+      Err(fs::TryLockError::WouldBlock) => Err(VersionError::GameDirLocked(lock_file))?,
+      Err(fs::TryLockError::Error(err)) => Err(err)?,
     }
   }
 
+  pub fn get_library_dir(game_dir: &Path) -> PathBuf {
+    game_dir.join("libraries")
+  }
+
+  // Old versions may list their assets on another CDN through the non-standard assetBaseUrl field
+  pub fn get_asset_cdn_base_url(local_version: &LocalVersionInfo) -> Url {
+    let default_url = || Url::parse(ASSET_CDN_BASE_URL).unwrap();
+    let Some(asset_base_url) = &local_version.asset_base_url else {
+      return default_url();
+    };
+    // A trailing slash keeps the last path segment when the object path is joined to it
+    let asset_base_url = if asset_base_url.ends_with('/') { asset_base_url.clone() } else { format!("{asset_base_url}/") };
+    Url::parse(&asset_base_url).unwrap_or_else(|err| {
+      warn!("Invalid asset base url {asset_base_url}, using the default one: {err}");
+      default_url()
+    })
+  }
+
+  pub fn get_assets_dir(game_dir: &Path) -> PathBuf {
+    game_dir.join("assets")
+  }
+
   pub fn get_local_versions(&self) -> Vec<LocalVersionInfo> {
     let mutex_guard = self.local_versions_cache.lock().unwrap();
     mutex_guard.to_vec()
@@ -53,46 +448,379 @@ impl VersionManager {
   }
 
   pub async fn refresh(&self) -> Result<(), Box<dyn std::error::Error>> {
-    // Clear cache
-    // let mut remote_versions_cache = self.remote_versions_cache.lock().unwrap();
-    // let mut local_versions_cache = self.local_versions_cache.lock().unwrap();
+    self.refresh_remote().await?;
+    self.refresh_local()
+  }
+
+  pub async fn refresh_remote(&self) -> Result<(), Box<dyn std::error::Error>> {
     let remote_versions_cache = Arc::clone(&self.remote_versions_cache);
-    let local_versions_cache = Arc::clone(&self.local_versions_cache);
     remote_versions_cache.lock().unwrap().clear();
-    local_versions_cache.lock().unwrap().clear();
 
-    // Refresh remote
-    {
-      let raw_version_list = RawVersionList::fetch().await?;
-      remote_versions_cache.lock().unwrap().extend(raw_version_list.versions);
-    }
+    let raw_version_list = RawVersionList::fetch(&Client::new()).await?;
+    remote_versions_cache.lock().unwrap().extend(raw_version_list.versions);
+    Ok(())
+  }
+
+  pub fn refresh_local(&self) -> Result<(), Box<dyn std::error::Error>> {
+    let local_versions_cache = Arc::clone(&self.local_versions_cache);
+    local_versions_cache.lock().unwrap().clear();
 
-    // Refresh local
     let versions_dir = &self.game_dir.join("versions");
-    match read_dir(versions_dir) {
-      Ok(dir) => {
-        let dir_names: Vec<String> = dir
+    let dir_names: Vec<String> = match read_dir(versions_dir) {
+      Ok(dir) =>
+        dir
           .filter_map(|entry| entry.ok())
           .filter(|entry| entry.path().is_dir())
           .map(|entry| entry.file_name().to_str().unwrap().to_string())
-          .collect();
+          .collect(),
+      Err(err) => {
+        warn!("Failed to read version directory: {}", err);
+        return Ok(());
+      }
+    };
 
-        for version_id in dir_names {
-          info!("Scanning local version versions/{}", &version_id);
-          let version_json = &versions_dir.join(&version_id).join(format!("{}.json", &version_id));
-          if !version_json.is_file() {
-            warn!("Version file not found! Skipping. (versions/{}/{}.json)", &version_id, &version_id);
-            continue;
-          }
-          match serde_json::from_reader(File::open(version_json)?) {
-            Ok(json) => local_versions_cache.lock().unwrap().push(json),
-            Err(e) => warn!("Failed to parse version file! Skipping. (versions/{}/{}.json): {}", &version_id, &version_id, e),
+    let mut modified_times = HashMap::new();
+    for version_id in dir_names {
+      let version_json = versions_dir.join(&version_id).join(format!("{}.json", &version_id));
+      if !version_json.is_file() {
+        warn!("Version file not found! Skipping. (versions/{}/{}.json)", &version_id, &version_id);
+        continue;
+      }
+      modified_times.insert(version_id, Self::get_modified_time(&version_json));
+    }
+
+    if let Some(cache) = self.read_version_cache() {
+      let is_valid =
+        cache.versions.len() == modified_times.len() &&
+        cache.versions.iter().all(|entry| modified_times.get(&entry.id) == Some(&entry.modified));
+      if is_valid {
+        info!("Loaded {} local versions from version cache", cache.versions.len());
+        local_versions_cache
+          .lock()
+          .unwrap()
+          .extend(cache.versions.into_iter().map(|entry| entry.version));
+        return Ok(());
+      }
+    }
+
+    let mut cache = VersionCache::default();
+    for (version_id, modified) in modified_times {
+      info!("Scanning local version versions/{}", &version_id);
+      let version_json = versions_dir.join(&version_id).join(format!("{}.json", &version_id));
+      match serde_json::from_reader::<_, LocalVersionInfo>(File::open(version_json)?) {
+        Ok(json) => {
+          local_versions_cache.lock().unwrap().push(json.clone());
+          cache.versions.push(VersionCacheEntry {
+            id: version_id,
+            release_type: json.get_type().clone(),
+            modified,
+            version: json,
+          });
+        }
+        Err(e) => warn!("Failed to parse version file! Skipping. (versions/{}/{}.json): {}", &version_id, &version_id, e),
+      }
+    }
+
+    if let Err(err) = self.write_version_cache(&cache) {
+      warn!("Failed to save version cache: {}", err);
+    }
+    Ok(())
+  }
+
+  // Version directories without a readable <id>.json, nothing is deleted
+  pub fn get_orphaned_version_dirs(&self) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let versions_dir = self.game_dir.join("versions");
+    if !versions_dir.is_dir() {
+      return Ok(vec![]);
+    }
+
+    let mut orphaned = vec![];
+    for entry in read_dir(&versions_dir)? {
+      let path = entry?.path();
+      if !path.is_dir() {
+        continue;
+      }
+      let Some(version_id) = path.file_name().and_then(|name| name.to_str()) else {
+        orphaned.push(path);
+        continue;
+      };
+      let version_json = path.join(format!("{version_id}.json"));
+      let is_valid = File::open(&version_json)
+        .ok()
+        .and_then(|file| serde_json::from_reader::<_, LocalVersionInfo>(file).ok())
+        .is_some();
+      if !is_valid {
+        orphaned.push(path);
+      }
+    }
+    orphaned.sort();
+    Ok(orphaned)
+  }
+
+  pub fn invalidate_cache(&self) -> Result<(), Box<dyn std::error::Error>> {
+    let cache_file = self.get_version_cache_file();
+    if cache_file.is_file() {
+      fs::remove_file(cache_file)?;
+    }
+    Ok(())
+  }
+
+  // Startup diagnostics, only looks at local state. Fails when the versions directory can't be scanned
+  pub fn health_check(&self, java_path: &Path) -> Result<HealthStatus, Box<dyn std::error::Error>> {
+    let mut warnings = vec![];
+    let mut errors = vec![];
+
+    let cache_file = self.get_version_cache_file();
+    if cache_file.is_file() && self.read_version_cache().is_none() {
+      warnings.push(format!("Version cache {} is corrupt and will be rebuilt", cache_file.display()));
+    }
+
+    self.refresh_local()?;
+    if self.get_local_versions().is_empty() {
+      warnings.push("No versions are installed".to_string());
+    }
+
+    let assets_dir = Self::get_assets_dir(&self.game_dir);
+    for dir in [assets_dir.clone(), assets_dir.join("indexes"), assets_dir.join("objects")] {
+      if !dir.is_dir() {
+        warnings.push(format!("Assets directory {} is missing", dir.display()));
+      }
+    }
+
+    if !java_path.is_file() {
+      errors.push(format!("Java executable not found: {}", java_path.display()));
+    } else if JavaInstallation::from_executable(java_path).is_none() {
+      errors.push(format!("Java executable doesn't run: {}", java_path.display()));
+    }
+
+    Ok(HealthStatus { ok: errors.is_empty(), warnings, errors })
+  }
+
+  pub fn migrate_old_game_dir(&self, old_dir: &Path) -> Result<MigrationReport, Box<dyn std::error::Error>> {
+    let mut report = MigrationReport::default();
+    let mut migrated_hashes: HashMap<Sha1Sum, PathBuf> = HashMap::new();
+
+    for dir_name in ["versions", "libraries", "assets"] {
+      let source_dir = old_dir.join(dir_name);
+      if !source_dir.is_dir() {
+        continue;
+      }
+      info!("Migrating {} from {}", dir_name, source_dir.display());
+      for source in Self::collect_files(&source_dir)? {
+        let relative = source.strip_prefix(old_dir)?;
+        if relative == Path::new("versions").join("version_cache.json") {
+          continue;
+        }
+        let target = self.game_dir.join(relative);
+        match Self::migrate_file(&source, &target, &mut migrated_hashes) {
+          Ok(true) => report.copied += 1,
+          Ok(false) => report.skipped += 1,
+          Err(err) => {
+            warn!("Failed to migrate {}: {}", relative.display(), err);
+            report.failed += 1;
           }
         }
       }
-      Err(err) => warn!("Failed to read version directory: {}", err),
     }
 
+    info!("Migration finished: {} copied, {} skipped, {} failed", report.copied, report.skipped, report.failed);
+    Ok(report)
+  }
+
+  fn migrate_file(source: &Path, target: &Path, migrated_hashes: &mut HashMap<Sha1Sum, PathBuf>) -> Result<bool, Box<dyn std::error::Error>> {
+    let source_hash = Sha1Sum::from_file(source)?;
+    if target.is_file() {
+      if Sha1Sum::from_file(target)? == source_hash {
+        return Ok(false);
+      }
+      fs::remove_file(target)?;
+    }
+    if let Some(parent) = target.parent() {
+      create_dir_all(parent)?;
+    }
+
+    let link_source = migrated_hashes.get(&source_hash).map(PathBuf::as_path).unwrap_or(source);
+    if fs::hard_link(link_source, target).is_err() {
+      fs::copy(source, target)?;
+    }
+
+    if Sha1Sum::from_file(target)? != source_hash {
+      let _ = fs::remove_file(target);
+      Err(MinecraftLauncherError(format!("Checksum mismatch after copying {}", target.display())))?;
+    }
+    migrated_hashes.entry(source_hash).or_insert_with(|| target.to_path_buf());
+    Ok(true)
+  }
+
+  // Only stale versions of libraries used by an installed version are deleted, anything else in libraries/ may have been
+  // placed there manually or by an installer (e.g. the files generated by forge) and is kept
+  pub fn prune_orphaned_libraries(&self) -> Result<PruneReport, Box<dyn std::error::Error>> {
+    let libraries_dir = Self::get_library_dir(&self.game_dir);
+    let mut report = PruneReport::default();
+    if !libraries_dir.is_dir() {
+      return Ok(report);
+    }
+
+    let mut artifact_dirs = HashSet::new();
+    let mut referenced_dirs = HashSet::new();
+    for local_version in self.get_local_versions() {
+      for library in local_version.get_libraries() {
+        let artifact_dir = library.name.group_id
+          .iter()
+          .chain([&library.name.artifact_id])
+          .fold(libraries_dir.clone(), |dir, segment| dir.join(segment));
+        referenced_dirs.insert(artifact_dir.join(&library.name.version));
+        artifact_dirs.insert(artifact_dir);
+      }
+    }
+
+    for file in Self::collect_files(&libraries_dir)? {
+      let is_orphaned =
+        artifact_dirs.iter().any(|dir| file.starts_with(dir)) && !referenced_dirs.iter().any(|dir| file.starts_with(dir));
+      if !is_orphaned {
+        continue;
+      }
+
+      let size = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+      fs::remove_file(&file)?;
+      info!("Deleted orphaned library {}", file.display());
+      report.bytes_freed += size;
+
+      // Clean up the emptied version directories
+      let mut parent = file.parent();
+      while let Some(dir) = parent.filter(|dir| *dir != libraries_dir && fs::remove_dir(dir).is_ok()) {
+        parent = dir.parent();
+      }
+      report.deleted.push(file);
+    }
+    Ok(report)
+  }
+
+  // Removes the per launch `<id>-natives-<n>` directories of installed versions that weren't touched for max_age
+  pub fn gc_old_natives(&self, max_age: Duration) -> Result<GcReport, Box<dyn std::error::Error>> {
+    let mut report = GcReport::default();
+    for local_version in self.get_local_versions() {
+      let version_id = local_version.get_id().to_string();
+      let version_dir = self.game_dir.join("versions").join(&version_id);
+      if !version_dir.is_dir() {
+        continue;
+      }
+      let prefix = format!("{version_id}-natives-");
+      let natives_dirs: Vec<PathBuf> = read_dir(&version_dir)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+        .map(|entry| entry.path())
+        .collect();
+
+      for natives_dir in natives_dirs {
+        report.inspected.push(natives_dir.clone());
+        // Modification times in the future count as just modified
+        let age = natives_dir.metadata()?.modified()?.elapsed().unwrap_or_default();
+        if age < max_age {
+          continue;
+        }
+
+        let size: u64 = Self::collect_files(&natives_dir)?
+          .iter()
+          .map(|file| file.metadata().map(|metadata| metadata.len()).unwrap_or(0))
+          .sum();
+        if let Err(err) = fs::remove_dir_all(&natives_dir) {
+          warn!("Failed to delete {}: {}", natives_dir.display(), err);
+          continue;
+        }
+        info!("Deleted old natives {}", natives_dir.display());
+        report.bytes_freed += size;
+        report.deleted.push(natives_dir);
+      }
+    }
+    Ok(report)
+  }
+
+  fn collect_files(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut files = vec![];
+    for entry in read_dir(dir)? {
+      let path = entry?.path();
+      if path.is_dir() {
+        files.extend(Self::collect_files(&path)?);
+      } else if path.is_file() {
+        files.push(path);
+      }
+    }
+    Ok(files)
+  }
+
+  pub fn watch_for_changes(
+    &self,
+    callback: impl Fn(VersionChangeEvent) + Send + 'static
+  ) -> Result<WatchHandle, Box<dyn std::error::Error>> {
+    let versions_dir = self.game_dir.join("versions");
+    create_dir_all(&versions_dir)?;
+
+    let root = versions_dir.clone();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+      let event = match event {
+        Ok(event) => event,
+        Err(err) => {
+          warn!("Failed to watch versions directory: {}", err);
+          return;
+        }
+      };
+      for path in &event.paths {
+        let Some(version_id) = Self::get_version_id_from_json(&root, path) else {
+          continue;
+        };
+        let added = match event.kind {
+          EventKind::Create(_) => true,
+          EventKind::Remove(_) => false,
+          EventKind::Modify(ModifyKind::Name(_)) => path.is_file(),
+          _ => continue,
+        };
+        callback(if added { VersionChangeEvent::Added(version_id) } else { VersionChangeEvent::Removed(version_id) });
+      }
+    })?;
+    watcher.watch(&versions_dir, RecursiveMode::Recursive)?;
+    Ok(WatchHandle { _watcher: watcher })
+  }
+
+  // versions/<id>/<id>.json -> <id>
+  fn get_version_id_from_json(versions_dir: &Path, path: &Path) -> Option<MCVersion> {
+    let relative = path.strip_prefix(versions_dir).ok()?;
+    let mut components = relative.iter();
+    let version_id = components.next()?.to_str()?;
+    let file_name = components.next()?.to_str()?;
+    if components.next().is_some() || file_name != format!("{version_id}.json") {
+      return None;
+    }
+    Some(MCVersion::from(version_id.to_string()))
+  }
+
+  fn get_version_cache_file(&self) -> PathBuf {
+    self.game_dir.join("versions").join("version_cache.json")
+  }
+
+  fn get_modified_time(path: &Path) -> u64 {
+    fs::metadata(path)
+      .and_then(|metadata| metadata.modified())
+      .ok()
+      .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+      .map_or(0, |duration| duration.as_millis() as u64)
+  }
+
+  fn read_version_cache(&self) -> Option<VersionCache> {
+    let file = File::open(self.get_version_cache_file()).ok()?;
+    match serde_json::from_reader(file) {
+      Ok(cache) => Some(cache),
+      Err(err) => {
+        warn!("Failed to parse version cache, rebuilding: {}", err);
+        None
+      }
+    }
+  }
+
+  fn write_version_cache(&self, cache: &VersionCache) -> Result<(), Box<dyn std::error::Error>> {
+    let file = File::create(self.get_version_cache_file())?;
+    serde_json::to_writer(file, cache)?;
     Ok(())
   }
 
@@ -123,6 +851,9 @@ impl VersionManager {
   }
 
   pub async fn is_up_to_date(/*mut*/ &self, local_version: &LocalVersionInfo) -> bool {
+    if self.is_pinned(local_version.get_id()) {
+      return true;
+    }
     if let Some(remote_version) = self.get_remote_version(local_version.get_id()) {
       if remote_version.get_updated_time().inner() > local_version.get_updated_time().inner() {
         return false;
@@ -135,89 +866,1578 @@ impl VersionManager {
         }
       };
 
+      // A modified or partially written client jar needs to be downloaded again
+      if let Some(client) = resolved.get_download_url(DownloadType::Client) {
+        if resolved.compute_client_sha1(&self.game_dir).is_ok_and(|sha1| sha1 != client.sha1) {
+          return false;
+        }
+      }
+
       return self.has_all_files(&resolved, &OperatingSystem::get_current_platform());
     } else {
       true
     }
   }
 
-  pub async fn install_version(&self, version_id: &MCVersion) -> Result<LocalVersionInfo, Box<dyn std::error::Error>> {
-    let remote_version = &self
-      .get_remote_version(version_id)
-      .ok_or(MinecraftLauncherError(format!("Version not found in remote list: {}", &version_id.to_string())))?;
+  // Offline check against the last fetched manifest. Versions missing from it, or installed without a recorded
+  // manifest sha1 (e.g. by another launcher), are unknown and considered up to date
+  pub fn is_up_to_date_cached(&self, local_version: &LocalVersionInfo) -> bool {
+    let Some(remote_version) = self.get_remote_version(local_version.get_id()) else {
+      return true;
+    };
+    let Ok(installed_sha1) = fs::read_to_string(self.get_installed_sha1_file(local_version.get_id())) else {
+      return true;
+    };
+    installed_sha1.trim() == remote_version.get_sha1().to_string()
+  }
 
-    let local_version = remote_version.fetch().await?;
-    let target_dir = &self.game_dir.join("versions").join(&local_version.get_id().to_string());
-    create_dir_all(&target_dir)?;
-    let target_json = target_dir.join(format!("{}.json", &local_version.get_id().to_string()));
-    serde_json::to_writer_pretty(&File::create(&target_json)?, &local_version)?;
+  // Manifest sha1 of the version json, recorded when it's installed
+  fn get_installed_sha1_file(&self, id: &MCVersion) -> PathBuf {
+    let id = id.to_string();
+    self.game_dir.join("versions").join(&id).join(format!("{id}.sha1"))
+  }
 
-    self.local_versions_cache.lock().unwrap().push(local_version);
-    Ok(self.get_local_version(version_id).unwrap())
+  // Returns true when the version json was (re)installed. The manifest is only fetched if it wasn't loaded yet,
+  // versions that aren't part of it (e.g. modpacks) and pinned versions are never updated
+  pub async fn auto_update_if_needed(&self, id: &MCVersion) -> Result<bool, Box<dyn std::error::Error>> {
+    if self.is_pinned(id) {
+      return Ok(false);
+    }
+    if self.remote_versions_cache.lock().unwrap().is_empty() {
+      self.refresh_remote().await?;
+    }
+    if let Some(local_version) = self.get_local_version(id) {
+      if self.is_up_to_date_cached(&local_version) {
+        return Ok(false);
+      }
+      info!("Version {} is outdated, updating it", id.to_string());
+    }
+    self.redownload_version_json(id).await?;
+    Ok(true)
   }
 
-  pub fn download_version(
-    &self,
-    game_runner: &MinecraftGameRunner,
-    local_version: &LocalVersionInfo,
-    download_job: &mut DownloadJob
-  ) -> Result<(), Box<dyn std::error::Error>> {
-    download_job.add_downloadables(
-      local_version.get_required_downloadables(
-        &OperatingSystem::get_current_platform(),
-        &game_runner.options.proxy,
-        &game_runner.options.game_dir,
-        false,
-        game_runner.feature_matcher.deref()
-      )
-    );
-    let jar_id = local_version.get_jar().to_string();
-    let jar_path = format!("versions/{}/{}.jar", &jar_id, &jar_id);
-    let jar_file_path = game_runner.options.game_dir.join(&jar_path.replace("/", MAIN_SEPARATOR_STR));
+  fn get_pin_file(&self, id: &MCVersion) -> PathBuf {
+    let id = id.to_string();
+    self.game_dir.join("versions").join(&id).join(format!("{id}.pinned"))
+  }
 
-    let info = local_version.get_download_url(DownloadType::Client);
-    let http_client = game_runner.options.proxy.create_http_client();
-    if let Some(info) = info {
-      download_job.add_downloadables(vec![Box::new(PreHashedDownloadable::new(http_client, &info.url, &jar_file_path, false, info.sha1.clone()))]);
-    } else {
-      let url = format!("https://s3.amazonaws.com/Minecraft.Download/{jar_path}");
-      download_job.add_downloadables(vec![Box::new(EtagDownloadable::new(http_client, &url, &jar_file_path, false))]);
+  // Pinned versions are always reported as up to date, so they're never updated automatically
+  pub fn pin_version(&self, id: &MCVersion) -> Result<(), Box<dyn std::error::Error>> {
+    let pin_file = self.get_pin_file(id);
+    if !pin_file.parent().is_some_and(|version_dir| version_dir.is_dir()) {
+      Err(VersionError::VersionNotInstalled(id.clone()))?;
     }
+    fs::write(pin_file, "")?;
+    Ok(())
+  }
 
+  pub fn unpin_version(&self, id: &MCVersion) -> Result<(), Box<dyn std::error::Error>> {
+    let pin_file = self.get_pin_file(id);
+    if pin_file.is_file() {
+      fs::remove_file(pin_file)?;
+    }
     Ok(())
   }
 
-  pub async fn get_resource_files(
-    &self,
-    proxy: &ProxyOptions,
-    game_dir: &PathBuf,
-    local_version: &LocalVersionInfo
+  pub fn is_pinned(&self, id: &MCVersion) -> bool {
+    self.get_pin_file(id).is_file()
+  }
+
+  pub async fn install_version(&self, version_id: &MCVersion) -> Result<LocalVersionInfo, Box<dyn std::error::Error>> {
+    let remote_version = &self
+      .get_remote_version(version_id)
+      .ok_or(MinecraftLauncherError(format!("Version not found in remote list: {}", &version_id.to_string())))?;
+
+    let local_version = self.write_version_json(remote_version).await?;
+    self.local_versions_cache.lock().unwrap().push(local_version);
+    Ok(self.get_local_version(version_id).unwrap())
+  }
+
+  // Replaces a (possibly corrupted) version json with the one from the manifest
+  pub async fn redownload_version_json(&self, version_id: &MCVersion) -> Result<LocalVersionInfo, Box<dyn std::error::Error>> {
+    if self.remote_versions_cache.lock().unwrap().is_empty() {
+      self.refresh_remote().await?;
+    }
+    let remote_version = self.get_remote_version(version_id).ok_or_else(|| VersionError::VersionNotFoundInManifest(version_id.clone()))?;
+
+    let local_version = self.write_version_json(&remote_version).await?;
+    let mut local_versions = self.local_versions_cache.lock().unwrap();
+    local_versions.retain(|version| version.get_id() != version_id);
+    local_versions.push(local_version.clone());
+    Ok(local_version)
+  }
+
+  pub fn save_version_snapshot(&self, id: &MCVersion, dest: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let version_id = id.to_string();
+    let version_json = self.game_dir.join("versions").join(&version_id).join(format!("{version_id}.json"));
+    let bytes = fs::read(&version_json).map_err(|err| MinecraftLauncherError(format!("Version {version_id} is not installed: {err}")))?;
+    let snapshot = VersionSnapshot {
+      format_version: 1,
+      id: id.clone(),
+      saved_at: Utc::now(),
+      sha1: Sha1Sum::from_reader(&mut bytes.as_slice())?,
+      remote_version: self.get_remote_version(id),
+      version_json: String::from_utf8(bytes)?,
+    };
+    if let Some(parent) = dest.parent() {
+      create_dir_all(parent)?;
+    }
+    fs::write(dest, serde_json::to_vec_pretty(&snapshot)?)?;
+    Ok(())
+  }
+
+  // Installs the archived version json as-is, the remote manifest is not consulted
+  pub fn restore_version_snapshot(&self, archive: &Path) -> Result<LocalVersionInfo, Box<dyn std::error::Error>> {
+    let snapshot: VersionSnapshot = serde_json::from_slice(&fs::read(archive)?)?;
+    let sha1 = Sha1Sum::from_reader(&mut snapshot.version_json.as_bytes())?;
+    if sha1 != snapshot.sha1 {
+      Err(MinecraftLauncherError(format!("Version snapshot {} is corrupted (expected sha1 {}, got {})", archive.display(), snapshot.sha1, sha1)))?;
+    }
+    let local_version: LocalVersionInfo = serde_json::from_str(&snapshot.version_json)?;
+    if local_version.get_id() != &snapshot.id {
+      Err(MinecraftLauncherError(format!("Version snapshot {} contains {} instead of {}", archive.display(), local_version.get_id().to_string(), snapshot.id.to_string())))?;
+    }
+
+    let version_id = snapshot.id.to_string();
+    let target_dir = self.game_dir.join("versions").join(&version_id);
+    create_dir_all(&target_dir)?;
+    fs::write(target_dir.join(format!("{version_id}.json")), snapshot.version_json.as_bytes())?;
+    let mut local_versions = self.local_versions_cache.lock().unwrap();
+    local_versions.retain(|version| version.get_id() != &snapshot.id);
+    local_versions.push(local_version.clone());
+    Ok(local_version)
+  }
+
+  // Mods are added as libraries of a version inheriting from base, so no mod loader installer is needed
+  pub fn create_modpack_version(&self, name: &str, base: &MCVersion, mods: &[ModEntry]) -> Result<LocalVersionInfo, Box<dyn std::error::Error>> {
+    let release_type = self.get_local_version(base).map(|base_version| base_version.get_type().clone()).unwrap_or(ReleaseType::Release);
+    let libraries: Vec<_> = mods
+      .iter()
+      .map(|mod_entry| {
+        // Plain names get a synthetic descriptor so every mod has its own path under libraries/
+        let descriptor = if mod_entry.name.split(':').count() >= 3 {
+          mod_entry.name.clone()
+        } else {
+          format!("modpack.{name}:{}:{}", mod_entry.name, &mod_entry.sha1.to_string()[..8])
+        };
+        serde_json::json!({
+          "name": descriptor,
+          "downloads": { "artifact": { "url": mod_entry.url, "sha1": mod_entry.sha1, "size": mod_entry.size } }
+        })
+      })
+      .collect();
+    let now = Utc::now().to_rfc3339();
+    let local_version: LocalVersionInfo = serde_json::from_value(
+      serde_json::json!({
+        "id": name,
+        "inheritsFrom": base,
+        "releaseTime": now,
+        "time": now,
+        "type": release_type,
+        "libraries": libraries
+      })
+    )?;
+
+    local_version.write_to_file(&self.game_dir.join("versions").join(name).join(format!("{name}.json")))?;
+    let mut local_versions = self.local_versions_cache.lock().unwrap();
+    local_versions.retain(|version| version.get_id() != local_version.get_id());
+    local_versions.push(local_version.clone());
+    Ok(local_version)
+  }
+
+  // Uses the installed json when present, otherwise the one from the manifest
+  async fn get_or_fetch_version(&self, version_id: &MCVersion) -> Result<LocalVersionInfo, Box<dyn std::error::Error>> {
+    if let Some(local_version) = self.get_local_version(version_id) {
+      return Ok(local_version);
+    }
+    if self.remote_versions_cache.lock().unwrap().is_empty() {
+      self.refresh_remote().await?;
+    }
+    let remote_version = self.get_remote_version(version_id).ok_or_else(|| VersionError::VersionNotFoundInManifest(version_id.clone()))?;
+    remote_version.fetch().await
+  }
+
+  pub async fn fetch_server_info(&self, version_id: &MCVersion) -> Result<ServerInfo, Box<dyn std::error::Error>> {
+    let local_version = self.get_or_fetch_version(version_id).await?;
+    let jar_download = local_version.get_download_url(DownloadType::Server).ok_or_else(|| VersionError::NoServerDownload(version_id.clone()))?;
+    let java_version_required = local_version.get_java_version().cloned().unwrap_or_default().major_version as u8;
+    // Servers are shipped as a bundler jar since 1.18 (first snapshot 21w39a)
+    let bundler_added_at = DateTime::parse_from_rfc3339("2021-09-29T16:27:05+00:00").unwrap();
+    Ok(ServerInfo {
+      jar_download: jar_download.clone(),
+      java_version_required,
+      supports_bundler: local_version.get_release_time().inner() >= &bundler_added_at,
+    })
+  }
+
+  pub async fn download_client_mappings(
+    &self,
+    version_id: &MCVersion,
+    dest: &Path,
+    proxy: &ProxyOptions,
+    http_config: &HttpClientConfig
+  ) -> Result<(), Box<dyn std::error::Error>> {
+    self.download_mappings(version_id, dest, DownloadType::ClientMappings, proxy, http_config).await
+  }
+
+  pub async fn download_server_mappings(
+    &self,
+    version_id: &MCVersion,
+    dest: &Path,
+    proxy: &ProxyOptions,
+    http_config: &HttpClientConfig
+  ) -> Result<(), Box<dyn std::error::Error>> {
+    self.download_mappings(version_id, dest, DownloadType::ServerMappings, proxy, http_config).await
+  }
+
+  async fn download_mappings(
+    &self,
+    version_id: &MCVersion,
+    dest: &Path,
+    download_type: DownloadType,
+    proxy: &ProxyOptions,
+    http_config: &HttpClientConfig
+  ) -> Result<(), Box<dyn std::error::Error>> {
+    let local_version = self.get_or_fetch_version(version_id).await?;
+    let Some(info) = local_version.get_download_url(download_type.clone()) else {
+      Err(VersionError::NoMappings(version_id.clone(), download_type))?
+    };
+    if let Some(parent) = dest.parent() {
+      create_dir_all(parent)?;
+    }
+    PreHashedDownloadable::new(proxy.create_http_client_with_config(http_config), &info.url, &dest.to_path_buf(), false, info.sha1.clone())
+      .with_expected_size(info.size as u64)
+      .download().await
+      .map_err(|err| MinecraftLauncherError(format!("Couldn't download mappings for {}: {err}", version_id.to_string())))?;
+    Ok(())
+  }
+
+  // Installs one of Mojang's java runtimes (e.g. "java-runtime-gamma") and returns its java executable
+  pub async fn download_mojang_java_runtime(
+    &self,
+    component: &str,
+    dest_dir: &Path,
+    proxy: &ProxyOptions,
+    http_config: &HttpClientConfig
+  ) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let Some(platform) = RawJavaRuntimeIndex::get_current_platform() else {
+      Err(MinecraftLauncherError("Mojang's java runtimes aren't available for this platform".to_string()))?
+    };
+    let http_client = proxy.create_http_client_with_config(http_config);
+    let index = RawJavaRuntimeIndex::fetch(&http_client).await?;
+    let Some(entry) = index.get_entry(platform, component) else {
+      Err(MinecraftLauncherError(format!("Java runtime {component} is not available for {platform}")))?
+    };
+
+    let manifest_bytes = http_client.get(&entry.manifest.url).send().await?.bytes().await?;
+    let sha1 = Sha1Sum::from_reader(&mut manifest_bytes.as_ref())?;
+    if sha1 != entry.manifest.sha1 {
+      Err(MinecraftLauncherError(format!("Sha1 mismatch for the {component} manifest: {sha1} != {}", entry.manifest.sha1)))?;
+    }
+    let manifest: JavaRuntimeManifest = serde_json::from_slice(&manifest_bytes)?;
+    info!("Installing java runtime {component} {} to {}", entry.version.name, dest_dir.display());
+
+    let mut job = DownloadJob::builder().name(format!("Java runtime {component}")).build();
+    let mut executables = vec![];
+    let mut links = vec![];
+    for (path, file) in &manifest.files {
+      let target = join_path_string(dest_dir, path);
+      match file {
+        JavaRuntimeFile::Directory => create_dir_all(&target)?,
+        JavaRuntimeFile::File { executable, downloads } => {
+          let info = &downloads.raw;
+          let downloadable = PreHashedDownloadable::new(http_client.clone(), &info.url, &target, false, info.sha1.clone());
+          job.add_downloadables(vec![Box::new(downloadable.with_expected_size(info.size as u64))]);
+          if *executable {
+            executables.push(target);
+          }
+        }
+        JavaRuntimeFile::Link { target: link_target } => links.push((target, link_target.clone())),
+      }
+    }
+    job.start().await?;
+
+    Self::finish_runtime_files(&executables, &links)?;
+
+    let executable_name = if OperatingSystem::get_current_platform() == OperatingSystem::Windows { "java.exe" } else { "java" };
+    let bundle_java = dest_dir.join("jre.bundle").join("Contents").join("Home").join("bin").join(executable_name);
+    Ok(if bundle_java.is_file() { bundle_java } else { dest_dir.join("bin").join(executable_name) })
+  }
+
+  #[cfg(unix)]
+  fn finish_runtime_files(executables: &[PathBuf], links: &[(PathBuf, String)]) -> io::Result<()> {
+    use std::os::unix::fs::{ symlink, PermissionsExt };
+    for executable in executables {
+      fs::set_permissions(executable, fs::Permissions::from_mode(0o755))?;
+    }
+    for (link, link_target) in links {
+      if !link.exists() {
+        symlink(link_target, link)?;
+      }
+    }
+    Ok(())
+  }
+
+  // Windows has no executable bit and the runtimes for it don't ship links
+  #[cfg(not(unix))]
+  fn finish_runtime_files(_executables: &[PathBuf], links: &[(PathBuf, String)]) -> io::Result<()> {
+    if !links.is_empty() {
+      warn!("Skipped {} link(s) of the java runtime", links.len());
+    }
+    Ok(())
+  }
+
+  async fn write_version_json(&self, remote_version: &RemoteVersionInfo) -> Result<LocalVersionInfo, Box<dyn std::error::Error>> {
+    let bytes = remote_version.fetch_bytes().await?;
+    let local_version: LocalVersionInfo = serde_json::from_slice(&bytes)?;
+    let target_dir = &self.game_dir.join("versions").join(&local_version.get_id().to_string());
+    create_dir_all(&target_dir)?;
+    let target_json = target_dir.join(format!("{}.json", &local_version.get_id().to_string()));
+    fs::write(&target_json, &bytes)?;
+    fs::write(self.get_installed_sha1_file(local_version.get_id()), remote_version.get_sha1().to_string())?;
+    Ok(local_version)
+  }
+
+  pub async fn install_forge(
+    &self,
+    mc_version: &MCVersion,
+    forge_version: &str,
+    java_path: &Path,
+    proxy: &ProxyOptions,
+    http_config: &HttpClientConfig,
+    progress_reporter: &Arc<ProgressReporter>
+  ) -> Result<LocalVersionInfo, Box<dyn std::error::Error>> {
+    // Modern installers name the version <mc>-forge-<forge>
+    let expected_id = MCVersion::from(format!("{}-forge-{}", mc_version.to_string(), forge_version));
+    if self.get_mod_loader_type(&expected_id) == ModLoaderType::Forge {
+      info!("Forge {} is already installed", expected_id.to_string());
+      return Ok(self.get_local_version(&expected_id).unwrap());
+    }
+
+    let installer_file = temp_dir().join(format!("forge-{}-{}-installer.jar", mc_version.to_string(), forge_version));
+    let mut installer = match ForgeInstaller::download(proxy, http_config, mc_version, forge_version, &installer_file).await {
+      Ok(installer) => installer,
+      Err(err) => {
+        let _ = fs::remove_file(&installer_file);
+        return Err(err);
+      }
+    };
+    let result = self.run_forge_installer(&mut installer, java_path, proxy, http_config, progress_reporter).await;
+    if let Err(err) = installer.remove_files() {
+      warn!("Couldn't remove the forge installer files: {err}");
+    }
+    result
+  }
+
+  // The version json is only written once everything else succeeded, its presence marks forge as installed
+  async fn run_forge_installer(
+    &self,
+    installer: &mut ForgeInstaller,
+    java_path: &Path,
+    proxy: &ProxyOptions,
+    http_config: &HttpClientConfig,
+    progress_reporter: &Arc<ProgressReporter>
+  ) -> Result<LocalVersionInfo, Box<dyn std::error::Error>> {
+    let libraries_dir = Self::get_library_dir(&self.game_dir);
+
+    // The vanilla client jar is one of the processor inputs
+    let vanilla_id = installer.profile.minecraft.clone();
+    let vanilla_version = match self.get_local_version(&vanilla_id) {
+      Some(local_version) => local_version,
+      None => self.install_version(&vanilla_id).await?,
+    };
+
+    let version_json = installer.read_version_json()?;
+    let forge_version_info: LocalVersionInfo = serde_json::from_slice(&version_json)?;
+    let forge_id = forge_version_info.get_id().to_string();
+
+    let mut job = DownloadJob::builder()
+      .name("Forge libraries".to_string())
+      .progress_reporter(Arc::clone(progress_reporter))
+      .build();
+    let mut libraries = installer.profile.libraries.clone();
+    libraries.extend(forge_version_info.get_libraries().iter().cloned());
+    job.add_downloadables(installer.create_library_downloads(&libraries, proxy, http_config, &libraries_dir)?);
+    if let Some(info) = vanilla_version.get_download_url(DownloadType::Client) {
+      let vanilla_jar_id = vanilla_version.get_jar().to_string();
+      let jar_file_path = self.game_dir.join("versions").join(&vanilla_jar_id).join(format!("{vanilla_jar_id}.jar"));
+      let downloadable = PreHashedDownloadable::new(
+        proxy.create_http_client_with_config(http_config),
+        &info.url,
+        &jar_file_path,
+        false,
+        info.sha1.clone()
+      );
+      job.add_downloadables(vec![Box::new(downloadable.with_expected_size(info.size as u64))]);
+    }
+    job.start().await?;
+
+    let data = installer.get_default_data(&self.game_dir, &libraries_dir);
+    installer.run_processors(java_path, data, &libraries_dir).await?;
+
+    let target_dir = self.game_dir.join("versions").join(&forge_id);
+    create_dir_all(&target_dir)?;
+    fs::write(target_dir.join(format!("{forge_id}.json")), &version_json)?;
+    self.refresh_local()?;
+    self
+      .get_local_version(forge_version_info.get_id())
+      .ok_or(MinecraftLauncherError(format!("Forge version {forge_id} was not found after installing it")).into())
+  }
+
+  pub fn download_version(
+    &self,
+    game_runner: &MinecraftGameRunner,
+    local_version: &LocalVersionInfo,
+    download_job: &mut DownloadJob
+  ) -> Result<(), Box<dyn std::error::Error>> {
+    self.add_version_downloadables(
+      local_version,
+      &game_runner.options.proxy,
+      &game_runner.options.http_client_config,
+      game_runner.options.force_download_all,
+      download_job
+    );
+    Ok(())
+  }
+
+  fn add_version_downloadables(
+    &self,
+    local_version: &LocalVersionInfo,
+    proxy: &ProxyOptions,
+    http_config: &HttpClientConfig,
+    force_download: bool,
+    download_job: &mut DownloadJob
+  ) {
+    download_job.add_downloadables(
+      local_version.get_required_downloadables(
+        &OperatingSystem::get_current_platform(),
+        proxy,
+        http_config,
+        &self.game_dir,
+        force_download,
+        self.feature_matcher.deref()
+      )
+    );
+    let jar_id = local_version.get_jar().to_string();
+    let jar_path = format!("versions/{}/{}.jar", &jar_id, &jar_id);
+    let jar_file_path = join_path_string(&self.game_dir, &jar_path);
+
+    let info = local_version.get_download_url(DownloadType::Client);
+    let http_client = proxy.create_http_client_with_config(http_config);
+    if let Some(info) = info {
+      download_job.add_downloadables(
+        vec![
+          Box::new(PreHashedDownloadable::new(http_client, &info.url, &jar_file_path, force_download, info.sha1.clone()).with_expected_size(info.size as u64))
+        ]
+      );
+    } else {
+      let url = format!("https://s3.amazonaws.com/Minecraft.Download/{jar_path}");
+      download_job.add_downloadables(vec![Box::new(EtagDownloadable::new(http_client, &url, &jar_file_path, force_download))]);
+    }
+  }
+
+  pub async fn verify_assets(&self, local_version: &LocalVersionInfo) -> Result<VerificationStatus, Box<dyn std::error::Error>> {
+    let mut status = VerificationStatus::default();
+    let Some(asset_index_info) = &local_version.asset_index else {
+      return Ok(status);
+    };
+    let index_file = Self::get_assets_dir(&self.game_dir).join("indexes").join(format!("{}.json", asset_index_info.id));
+    let asset_index = AssetIndex::from_file(&index_file)?;
+
+    for obj in asset_index.get_unique_objects().keys() {
+      let path = self.get_asset_object_path(&obj.hash);
+      status.check_file(&path, Some(&obj.hash)).await;
+    }
+    Ok(status)
+  }
+
+  pub async fn verify_libraries(&self, local_version: &LocalVersionInfo) -> VerificationStatus {
+    let mut status = VerificationStatus::default();
+    let os = OperatingSystem::get_current_platform();
+    for library in local_version.get_relevant_libraries(self.feature_matcher.deref()) {
+      let (path, classifier) = if library.natives.is_empty() {
+        (library.get_path(&self.game_dir), None)
+      } else {
+        let Some(path) = library.get_native_path(&self.game_dir, &os) else {
+          continue;
+        };
+        (path, library.native_classifier(&os))
+      };
+      let download_info = library.downloads.as_ref().and_then(|downloads| downloads.get_download_info(classifier.as_deref()));
+      status.check_file(&path, download_info.as_ref().map(|info| &info.sha1)).await;
+    }
+    status
+  }
+
+  // Verifies the libraries and assets of every version concurrently
+  pub async fn batch_verify(&self, versions: &[MCVersion]) -> Result<HashMap<MCVersion, VerificationStatus>, Box<dyn std::error::Error>> {
+    let mut local_versions = vec![];
+    for version_id in versions {
+      let Some(local_version) = self.get_local_version(version_id) else {
+        Err(MinecraftLauncherError(format!("Version {} is not installed", version_id.to_string())))?
+      };
+      local_versions.push(local_version.resolve(self, HashSet::new()).await?);
+    }
+
+    let mut verifications = local_versions
+      .iter()
+      .map(|local_version| async move {
+        let mut status = self.verify_libraries(local_version).await;
+        let assets = self.verify_assets(local_version).await.map_err(|err| err.to_string());
+        assets.map(|assets| {
+          status.add(assets);
+          (local_version.get_id().clone(), status)
+        })
+      })
+      .collect::<FuturesUnordered<_>>();
+
+    let mut statuses = HashMap::new();
+    while let Some(result) = verifications.next().await {
+      let (version_id, status) = result.map_err(MinecraftLauncherError)?;
+      statuses.insert(version_id, status);
+    }
+    Ok(statuses)
+  }
+
+  // Downloads every version in parallel, max_concurrent_downloads is shared between all of them
+  pub fn bulk_download<'a>(
+    &'a self,
+    versions: &'a [MCVersion],
+    proxy: &'a ProxyOptions,
+    http_config: &'a HttpClientConfig,
+    max_concurrent_downloads: u16,
+    progress_reporter: &'a Arc<ProgressReporter>
+  ) -> impl Stream<Item = (MCVersion, Result<(), Box<dyn std::error::Error>>)> + 'a {
+    let concurrency_limit = Arc::new(Semaphore::new(max_concurrent_downloads as usize));
+    versions
+      .iter()
+      .map(|version_id| {
+        let concurrency_limit = Arc::clone(&concurrency_limit);
+        async move {
+          let result = self.download_full_version(version_id, proxy, http_config, max_concurrent_downloads, progress_reporter, concurrency_limit).await;
+          (version_id.clone(), result)
+        }
+      })
+      .collect::<FuturesUnordered<_>>()
+  }
+
+  async fn download_full_version(
+    &self,
+    version_id: &MCVersion,
+    proxy: &ProxyOptions,
+    http_config: &HttpClientConfig,
+    max_concurrent_downloads: u16,
+    progress_reporter: &Arc<ProgressReporter>,
+    concurrency_limit: Arc<Semaphore>
+  ) -> Result<(), Box<dyn std::error::Error>> {
+    let local_version = match self.get_local_version(version_id) {
+      Some(local_version) => local_version,
+      None => self.install_version(version_id).await?,
+    };
+    let local_version = local_version.resolve(self, HashSet::new()).await?;
+
+    let mut job = DownloadJob::builder()
+      .name(format!("Version {}", version_id.to_string()))
+      .max_concurrent(max_concurrent_downloads)
+      .concurrency_limit(concurrency_limit)
+      .progress_reporter(Arc::clone(progress_reporter))
+      .build();
+    self.add_version_downloadables(&local_version, proxy, http_config, false, &mut job);
+    job.add_downloadables(self.get_resource_files(proxy, http_config, &self.game_dir, &local_version, false).await?);
+    job.start().await?;
+    Ok(())
+  }
+
+  // Tries the jar's manifest first, then its checksum against the client jars of the installed versions and
+  // finally against the ones of the remote versions
+  pub async fn guess_version_from_jar(&self, jar_path: &Path) -> Option<MCVersion> {
+    if let Some(version) = self.guess_version_from_jar_manifest(jar_path) {
+      return Some(version);
+    }
+    let sha1 = Sha1Sum::from_file(jar_path).ok()?;
+    match self.find_installed_version_by_client_sha1(&sha1) {
+      Some(version) => Some(version),
+      None => self.find_remote_version_by_client_sha1(&sha1).await,
+    }
+  }
+
+  // The remote version list only has the sha1 of each version json, so version jsons are fetched (newest first)
+  // until one lists the jar as its client. Can take a while for old or unknown jars
+  pub async fn find_remote_version_by_client_sha1(&self, sha1: &Sha1Sum) -> Option<MCVersion> {
+    let mut remote_versions = self.get_remote_versions();
+    remote_versions.sort_by(|a, b| b.get_release_time().inner().cmp(a.get_release_time().inner()));
+    let mut client_sha1s = futures::stream
+      ::iter(remote_versions)
+      .map(|remote_version| async move {
+        let client_sha1 = match remote_version.fetch().await {
+          Ok(local_version) => local_version.get_download_url(DownloadType::Client).map(|info| info.sha1.clone()),
+          Err(err) => {
+            warn!("Couldn't fetch version {}: {err}", remote_version.get_id().to_string());
+            None
+          }
+        };
+        (remote_version.get_id().clone(), client_sha1)
+      })
+      .buffered(CLIENT_SHA1_LOOKUP_CONCURRENCY);
+    while let Some((id, client_sha1)) = client_sha1s.next().await {
+      if client_sha1.as_ref() == Some(sha1) {
+        return Some(id);
+      }
+    }
+    None
+  }
+
+  pub fn find_installed_version_by_client_sha1(&self, sha1: &Sha1Sum) -> Option<MCVersion> {
+    self
+      .get_local_versions()
+      .into_iter()
+      .find(|version| version.get_download_url(DownloadType::Client).is_some_and(|info| &info.sha1 == sha1))
+      .map(|version| version.get_id().clone())
+  }
+
+  // Reads the agent's manifest without loading it
+  pub fn verify_java_agent(&self, agent: &JavaAgent) -> Result<JavaAgentInfo, Box<dyn std::error::Error>> {
+    let not_a_jar = || JavaAgentError::NotAJar(agent.path.clone());
+    let file = File::open(&agent.path).map_err(|_| not_a_jar())?;
+    let mut archive = ZipArchive::new(file).map_err(|_| not_a_jar())?;
+    let mut manifest = String::new();
+    if let Ok(mut manifest_file) = archive.by_name("META-INF/MANIFEST.MF") {
+      manifest_file.read_to_string(&mut manifest).map_err(|_| not_a_jar())?;
+    }
+
+    let info = JavaAgentInfo::from_manifest(&manifest);
+    if info.premain_class.is_none() {
+      Err(JavaAgentError::NoPremainClass)?;
+    }
+    Ok(info)
+  }
+
+  // Implementation-Version "1.20.4" also matches its pre-releases, the one released closest to the jar's build wins
+  fn guess_version_from_jar_manifest(&self, jar_path: &Path) -> Option<MCVersion> {
+    let mut archive = ZipArchive::new(File::open(jar_path).ok()?).ok()?;
+    let mut manifest_file = archive.by_name("META-INF/MANIFEST.MF").ok()?;
+    let modified = manifest_file.last_modified();
+    let mut manifest = String::new();
+    manifest_file.read_to_string(&mut manifest).ok()?;
+    let implementation_version = manifest
+      .lines()
+      .find_map(|line| line.strip_prefix("Implementation-Version:"))?
+      .trim()
+      .to_string();
+
+    let built_at = Utc.with_ymd_and_hms(
+      modified.year().into(),
+      modified.month().into(),
+      modified.day().into(),
+      modified.hour().into(),
+      modified.minute().into(),
+      modified.second().into()
+    ).single();
+    let candidates: Vec<RemoteVersionInfo> = self
+      .get_remote_versions()
+      .into_iter()
+      .filter(|version| {
+        let id = version.get_id().to_string();
+        id == implementation_version || id.starts_with(&format!("{implementation_version}-"))
+      })
+      .collect();
+    let best = match built_at {
+      Some(built_at) => candidates.iter().min_by_key(|version| version.get_release_time().inner().signed_duration_since(built_at).num_seconds().abs()),
+      None => candidates.iter().find(|version| version.get_id().to_string() == implementation_version),
+    };
+    best.map(|version| version.get_id().clone())
+  }
+
+  // Writes <dest>/version_manifest_v2.json and the installed version jsons it points to, relative to dest
+  pub fn export_minimal_manifest(&self, versions: &[MCVersion], dest: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut manifest = RawVersionList { latest: HashMap::new(), versions: vec![] };
+    for id in versions {
+      let local_version = self.get_local_version(id).ok_or_else(|| VersionError::VersionNotInstalled(id.clone()))?;
+      let id = id.to_string();
+      let relative_path = format!("versions/{id}/{id}.json");
+      let target = join_path_string(dest, &relative_path);
+      create_dir_all(target.parent().unwrap())?;
+      fs::copy(self.game_dir.join("versions").join(&id).join(format!("{id}.json")), &target)?;
+
+      let compliance_level = self
+        .get_remote_version(local_version.get_id())
+        .map(|remote_version| remote_version.get_compliance_level())
+        .unwrap_or_default();
+      let remote_version: RemoteVersionInfo = serde_json::from_value(
+        serde_json::json!({
+          "id": local_version.get_id(),
+          "type": local_version.get_type(),
+          "url": relative_path,
+          "time": local_version.get_updated_time(),
+          "releaseTime": local_version.get_release_time(),
+          "sha1": Sha1Sum::from_file(&target)?,
+          "complianceLevel": compliance_level
+        })
+      )?;
+
+      let is_newer = manifest.latest
+        .get(remote_version.get_type())
+        .and_then(|latest| manifest.versions.iter().find(|version| version.get_id() == latest))
+        .is_none_or(|latest| latest.get_release_time().inner() < remote_version.get_release_time().inner());
+      if is_newer {
+        manifest.latest.insert(remote_version.get_type().clone(), remote_version.get_id().clone());
+      }
+      manifest.versions.push(remote_version);
+    }
+    create_dir_all(dest)?;
+    fs::write(dest.join("version_manifest_v2.json"), serde_json::to_vec_pretty(&manifest)?)?;
+    Ok(())
+  }
+
+  // Adds the manifest's versions to the remote version list until the next refresh_remote, relative urls point next to it
+  pub fn import_minimal_manifest(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let manifest: RawVersionList = serde_json::from_slice(&fs::read(path)?)?;
+    let base_dir = fs::canonicalize(path)?.parent().map(Path::to_path_buf).unwrap_or_default();
+    let mut remote_versions = self.remote_versions_cache.lock().unwrap();
+    for mut version in manifest.versions {
+      if Url::parse(version.get_url()).is_err() {
+        let file = join_path_string(&base_dir, version.get_url());
+        let url = Url::from_file_path(&file).map_err(|_| MinecraftLauncherError(format!("Invalid version path {}", file.display())))?;
+        version.set_url(url.as_str());
+      }
+      remote_versions.retain(|remote_version| remote_version.get_id() != version.get_id());
+      remote_versions.push(version);
+    }
+    Ok(())
+  }
+
+  pub async fn get_server_pack_url(&self, id: &MCVersion) -> Option<Url> {
+    self.get_resolved_download_url(id, DownloadType::Server).await
+  }
+
+  pub async fn get_client_url(&self, id: &MCVersion) -> Option<Url> {
+    self.get_resolved_download_url(id, DownloadType::Client).await
+  }
+
+  // side is "client" or "server"
+  pub async fn get_mappings_url(&self, id: &MCVersion, side: &str) -> Option<Url> {
+    let download_type = match side {
+      "client" => DownloadType::ClientMappings,
+      "server" => DownloadType::ServerMappings,
+      _ => {
+        return None;
+      }
+    };
+    self.get_resolved_download_url(id, download_type).await
+  }
+
+  // Downloads can be inherited, so the version is resolved first
+  async fn get_resolved_download_url(&self, id: &MCVersion, download_type: DownloadType) -> Option<Url> {
+    let local_version = self.get_local_version(id)?;
+    let resolved = local_version.resolve(self, HashSet::new()).await.ok()?;
+    Url::parse(&resolved.get_download_url(download_type)?.url).ok()
+  }
+
+  pub async fn get_java_version_requirement(&self, version_id: &MCVersion) -> Option<u8> {
+    let local_version = self.get_local_version(version_id)?;
+    let resolved = local_version.resolve(self, HashSet::new()).await.ok()?;
+    resolved.get_java_version().map(|java_version| java_version.major_version as u8)
+  }
+
+  /// Computes a SHA-256 fingerprint of the libraries installed for a version.
+  ///
+  /// Every library relevant to the current platform contributes one line `"{artifact_path} {sha1}\n"`, where `artifact_path`
+  /// is the maven path relative to `libraries/` (e.g. `org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1-natives-linux.jar`) and `sha1` is
+  /// the lowercase hex SHA-1 of the local file. Lines are sorted bytewise and deduplicated, concatenated and hashed with
+  /// SHA-256. The fingerprint is returned as lowercase hex.
+  pub async fn compute_fingerprint(&self, version_id: &MCVersion) -> Result<String, Box<dyn std::error::Error>> {
+    let local_version = self
+      .get_local_version(version_id)
+      .ok_or(MinecraftLauncherError(format!("Version not found: {}", version_id.to_string())))?;
+    let resolved = local_version.resolve(self, HashSet::new()).await?;
+
+    let mut lines = vec![];
+    for file in resolved.get_required_files(&OperatingSystem::get_current_platform(), self.feature_matcher.deref()) {
+      let artifact_path = file.trim_start_matches("libraries/");
+      let local_file = join_path_string(&self.game_dir, &file);
+      let sha1 = Sha1Sum::from_file(&local_file).map_err(|err| MinecraftLauncherError(format!("Couldn't read library {}: {err}", local_file.display())))?;
+      lines.push(format!("{artifact_path} {sha1}\n"));
+    }
+    lines.sort();
+    lines.dedup();
+
+    let mut hasher = Sha256::new();
+    for line in lines {
+      hasher.update(line.as_bytes());
+    }
+    Ok(hex::encode(hasher.finalize()))
+  }
+
+  // Same format as compute_fingerprint, but over every installed file of the version and its parents: version jsons,
+  // client jars, libraries for the current platform, the asset index and its objects. Paths are relative to the game dir
+  // and always use `/`, so two intact installs hash the same on any platform. Missing files are an error
+  pub fn compute_install_hash(&self, id: &MCVersion) -> Result<String, Box<dyn std::error::Error>> {
+    let Some(mut current) = self.get_local_version(id) else {
+      Err(VersionError::VersionNotInstalled(id.clone()))?
+    };
+    let os = OperatingSystem::get_current_platform();
+    let assets_dir = Self::get_assets_dir(&self.game_dir);
+
+    let mut files = HashSet::new();
+    let mut visited = HashSet::new();
+    loop {
+      if !visited.insert(current.get_id().clone()) {
+        break;
+      }
+
+      let id = current.get_id().to_string();
+      files.insert(self.game_dir.join("versions").join(&id).join(format!("{id}.json")));
+      let jar_id = current.get_jar().to_string();
+      files.insert(self.game_dir.join("versions").join(&jar_id).join(format!("{jar_id}.jar")));
+
+      for file in current.get_required_files(&os, self.feature_matcher.deref()) {
+        files.insert(join_path_string(&self.game_dir, &file));
+      }
+
+      if let Some(info) = &current.asset_index {
+        let index_file = assets_dir.join("indexes").join(format!("{}.json", info.id));
+        let asset_index = AssetIndex::from_file(&index_file).map_err(|err|
+          MinecraftLauncherError(format!("Couldn't read asset index {}: {err}", index_file.display()))
+        )?;
+        files.insert(index_file);
+        for obj in asset_index.objects.values() {
+          files.insert(get_asset_object_path(&assets_dir, &obj.hash));
+        }
+      }
+
+      let Some(parent) = current.get_inherits_from().and_then(|parent| self.get_local_version(parent)) else {
+        break;
+      };
+      current = parent;
+    }
+
+    let mut lines = vec![];
+    for file in files {
+      let relative_path = file
+        .strip_prefix(&self.game_dir)?
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+      let sha1 = Sha1Sum::from_file(&file).map_err(|err| MinecraftLauncherError(format!("Couldn't read {}: {err}", file.display())))?;
+      lines.push(format!("{relative_path} {sha1}\n"));
+    }
+    lines.sort();
+
+    let mut hasher = Sha256::new();
+    for line in lines {
+      hasher.update(line.as_bytes());
+    }
+    Ok(hex::encode(hasher.finalize()))
+  }
+
+  pub fn sign_version_json(&self, version_id: &MCVersion, private_key: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    let private_key = private_key.try_into().map_err(|_| MinecraftLauncherError("Ed25519 private keys must be 32 bytes long".to_string()))?;
+    let signing_key = SigningKey::from_bytes(private_key);
+    let id = version_id.to_string();
+    let version_dir = self.game_dir.join("versions").join(&id);
+    let signature = signing_key.sign(&Self::get_canonical_json(&version_dir.join(format!("{id}.json")))?);
+    fs::write(version_dir.join(format!("{id}.json.sig")), signature.to_bytes())?;
+    Ok(())
+  }
+
+  pub fn verify_version_json_signature(&self, version_id: &MCVersion, public_key: &[u8]) -> Result<bool, Box<dyn std::error::Error>> {
+    let public_key = public_key.try_into().map_err(|_| MinecraftLauncherError("Ed25519 public keys must be 32 bytes long".to_string()))?;
+    let verifying_key = VerifyingKey::from_bytes(public_key)?;
+    let id = version_id.to_string();
+    let version_dir = self.game_dir.join("versions").join(&id);
+    let Ok(signature) = Signature::from_slice(&fs::read(version_dir.join(format!("{id}.json.sig")))?) else {
+      return Ok(false);
+    };
+    let canonical_json = Self::get_canonical_json(&version_dir.join(format!("{id}.json")))?;
+    Ok(verifying_key.verify(&canonical_json, &signature).is_ok())
+  }
+
+  // Sorted keys and no whitespace, so reformatting the file doesn't invalidate its signature
+  fn get_canonical_json(json_file: &Path) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    fn sort_keys(value: Value) -> Value {
+      match value {
+        Value::Object(map) => {
+          let mut entries: Vec<_> = map.into_iter().collect();
+          entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+          Value::Object(entries.into_iter().map(|(key, value)| (key, sort_keys(value))).collect())
+        }
+        Value::Array(values) => Value::Array(values.into_iter().map(sort_keys).collect()),
+        value => value,
+      }
+    }
+
+    let value: Value = serde_json::from_reader(File::open(json_file)?)?;
+    Ok(serde_json::to_vec(&sort_keys(value))?)
+  }
+
+  pub async fn get_changelog(
+    &self,
+    from: &MCVersion,
+    to: &MCVersion,
+    proxy: &ProxyOptions,
+    http_config: &HttpClientConfig
+  ) -> Result<ChangelogInfo, Box<dyn std::error::Error>> {
+    let entries = self.fetch_patch_notes(proxy, http_config).await?;
+    Self::find_changelog(&entries, from, to)
+  }
+
+  // Every patch note released between the two versions (inclusive), oldest first
+  pub async fn get_patch_notes(
+    &self,
+    from_version: &MCVersion,
+    to_version: &MCVersion,
+    proxy: &ProxyOptions,
+    http_config: &HttpClientConfig
+  ) -> Result<Vec<PatchNote>, Box<dyn std::error::Error>> {
+    let entries = self.fetch_patch_notes(proxy, http_config).await?;
+    Self::find_patch_notes(&entries, from_version, to_version)
+  }
+
+  async fn fetch_patch_notes(&self, proxy: &ProxyOptions, http_config: &HttpClientConfig) -> Result<Vec<PatchNotesEntry>, Box<dyn std::error::Error>> {
+    if let Some((fetched_at, entries)) = &*self.patch_notes_cache.lock().unwrap() {
+      if fetched_at.elapsed() < PATCH_NOTES_CACHE_DURATION {
+        return Ok(entries.clone());
+      }
+    }
+
+    let patch_notes = RawPatchNotes::fetch(&proxy.create_http_client_with_config(http_config)).await?;
+    *self.patch_notes_cache.lock().unwrap() = Some((Instant::now(), patch_notes.entries.clone()));
+    Ok(patch_notes.entries)
+  }
+
+  pub async fn fetch_news(&self, proxy: &ProxyOptions, http_config: &HttpClientConfig) -> Result<Vec<NewsItem>, Box<dyn std::error::Error>> {
+    if let Some((fetched_at, news)) = &*self.news_cache.lock().unwrap() {
+      if fetched_at.elapsed() < NEWS_CACHE_DURATION {
+        return Ok(news.clone());
+      }
+    }
+
+    let raw_news = RawNews::fetch(&proxy.create_http_client_with_config(http_config)).await?;
+    let mut news = vec![];
+    for entry in &raw_news.entries {
+      match NewsItem::from_raw(entry) {
+        Ok(item) => news.push(item),
+        Err(err) => warn!("Skipping news entry '{}': {}", entry.title, err),
+      }
+    }
+    *self.news_cache.lock().unwrap() = Some((Instant::now(), news.clone()));
+    Ok(news)
+  }
+
+  // Skin and cape of any player, e.g. the one from UserAuthentication::auth_uuid
+  pub async fn fetch_skin_and_cape(
+    &self,
+    uuid: Uuid,
+    proxy: &ProxyOptions,
+    http_config: &HttpClientConfig
+  ) -> Result<PlayerProfile, Box<dyn std::error::Error>> {
+    if let Some((fetched_at, profile)) = self.profile_cache.lock().unwrap().get(&uuid) {
+      if fetched_at.elapsed() < PROFILE_CACHE_DURATION {
+        return Ok(profile.clone());
+      }
+    }
+
+    let raw_profile = RawPlayerProfile::fetch(&proxy.create_http_client_with_config(http_config), &uuid).await?;
+    let profile = PlayerProfile::from_raw(&raw_profile)?;
+    self.profile_cache.lock().unwrap().insert(uuid, (Instant::now(), profile.clone()));
+    Ok(profile)
+  }
+
+  // Which installed versions (including inherited libraries) use each library
+  pub async fn get_library_usage_map(&self) -> HashMap<Artifact, Vec<MCVersion>> {
+    let mut usage: HashMap<Artifact, Vec<MCVersion>> = HashMap::new();
+    for local_version in self.get_local_versions() {
+      let resolved = match local_version.resolve(self, HashSet::new()).await {
+        Ok(resolved) => resolved,
+        Err(err) => {
+          warn!("Failed to resolve version {}, only its own libraries are listed: {err}", local_version.get_id().to_string());
+          local_version.clone()
+        }
+      };
+      for library in resolved.get_libraries() {
+        let versions = usage.entry(library.name.clone()).or_default();
+        if !versions.contains(local_version.get_id()) {
+          versions.push(local_version.get_id().clone());
+        }
+      }
+    }
+    for versions in usage.values_mut() {
+      versions.sort_by_key(|version| version.to_string());
+    }
+    usage
+  }
+
+  pub fn get_version_release_notes_url(&self, id: &MCVersion) -> Option<Url> {
+    let release_type = match self.get_local_version(id) {
+      Some(local_version) => local_version.get_type().clone(),
+      None => self.get_remote_version(id)?.get_type().clone(),
+    };
+    Self::get_article_url(id, &release_type)
+  }
+
+  // Old betas and alphas predate the minecraft.net articles
+  fn get_article_url(id: &MCVersion, release_type: &ReleaseType) -> Option<Url> {
+    let slug = id.to_string().to_lowercase().replace(['.', ' '], "-");
+    let article = match release_type {
+      ReleaseType::Release => format!("minecraft-java-edition-{slug}"),
+      ReleaseType::Snapshot => format!("minecraft-snapshot-{slug}"),
+      _ => {
+        return None;
+      }
+    };
+    Url::parse(&format!("https://www.minecraft.net/en-us/article/{article}")).ok()
+  }
+
+  fn find_changelog(entries: &[PatchNotesEntry], from: &MCVersion, to: &MCVersion) -> Result<ChangelogInfo, Box<dyn std::error::Error>> {
+    let position = |version: &MCVersion| {
+      entries
+        .iter()
+        .position(|entry| &entry.version == version)
+        .ok_or_else(|| MinecraftLauncherError(format!("No changelog found for version {}", version.to_string())))
+    };
+    let from_index = position(from)?;
+    let to_index = position(to)?;
+    if from_index <= to_index {
+      Err(MinecraftLauncherError(format!("Version {} is not older than {}", from.to_string(), to.to_string())))?;
+    }
+
+    let entry = &entries[to_index];
+    let url = entry.release_type
+      .as_deref()
+      .and_then(|release_type| release_type.parse::<ReleaseType>().ok())
+      .and_then(|release_type| Self::get_article_url(&entry.version, &release_type))
+      .ok_or_else(|| MinecraftLauncherError(format!("No changelog article found for version {}", to.to_string())))?;
+    Ok(ChangelogInfo {
+      url: url.to_string(),
+      title: entry.title.clone(),
+      html_content: entry.body.clone(),
+    })
+  }
+
+  fn find_patch_notes(entries: &[PatchNotesEntry], from: &MCVersion, to: &MCVersion) -> Result<Vec<PatchNote>, Box<dyn std::error::Error>> {
+    let parse_date = |entry: &PatchNotesEntry| entry.date.as_deref().and_then(|date| DateTime::parse_from_rfc3339(date).ok());
+    let release_date = |version: &MCVersion| {
+      entries
+        .iter()
+        .find(|entry| &entry.version == version)
+        .and_then(parse_date)
+        .ok_or_else(|| MinecraftLauncherError(format!("No patch notes found for version {}", version.to_string())))
+    };
+    let from_date = release_date(from)?;
+    let to_date = release_date(to)?;
+
+    let mut notes = vec![];
+    for entry in entries {
+      let Some(date) = parse_date(entry) else {
+        continue;
+      };
+      if date < from_date || date > to_date {
+        continue;
+      }
+      let Some(release_type) = entry.release_type.as_deref().and_then(|release_type| release_type.parse::<ReleaseType>().ok()) else {
+        warn!("Skipping patch notes '{}': unknown release type", entry.title);
+        continue;
+      };
+      let note = PatchNote {
+        version: entry.version.clone(),
+        release_type,
+        url: entry.content_path
+          .as_ref()
+          .map(|content_path| format!("{LAUNCHER_CONTENT_URL}{content_path}"))
+          .unwrap_or_default(),
+      };
+      notes.push((date, note));
+    }
+    notes.sort_by_key(|(date, _)| *date);
+    Ok(
+      notes
+        .into_iter()
+        .map(|(_, note)| note)
+        .collect()
+    )
+  }
+
+  pub async fn snapshot_to_release_map(
+    &self,
+    proxy: &ProxyOptions,
+    http_config: &HttpClientConfig
+  ) -> Result<HashMap<MCVersion, MCVersion>, Box<dyn std::error::Error>> {
+    let raw_version_list = RawVersionList::fetch(&proxy.create_http_client_with_config(http_config)).await?;
+    Ok(Self::group_snapshots_by_release(raw_version_list.versions))
+  }
+
+  fn group_snapshots_by_release(mut versions: Vec<RemoteVersionInfo>) -> HashMap<MCVersion, MCVersion> {
+    versions.sort_by(|a, b| a.get_release_time().inner().cmp(b.get_release_time().inner()));
+
+    let mut map = HashMap::new();
+    let mut next_release: Option<&MCVersion> = None;
+    for version in versions.iter().rev() {
+      match version.get_type() {
+        ReleaseType::Release => {
+          next_release = Some(version.get_id());
+        }
+        ReleaseType::Snapshot => {
+          if let Some(release) = next_release {
+            map.insert(version.get_id().clone(), release.clone());
+          }
+        }
+        _ => {}
+      }
+    }
+    map
+  }
+
+  pub fn list_asset_indexes(&self) -> Vec<String> {
+    let indexes_dir = Self::get_assets_dir(&self.game_dir).join("indexes");
+    match read_dir(&indexes_dir) {
+      Ok(dir) => {
+        let mut ids: Vec<String> = dir
+          .filter_map(|entry| entry.ok())
+          .map(|entry| entry.path())
+          .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "json"))
+          .filter_map(|path| path.file_stem().and_then(|stem| stem.to_str()).map(|stem| stem.to_string()))
+          .collect();
+        ids.sort();
+        ids
+      }
+      Err(err) => {
+        warn!("Failed to read asset indexes directory: {}", err);
+        vec![]
+      }
+    }
+  }
+
+  pub fn get_asset_object_path(&self, hash: &Sha1Sum) -> PathBuf {
+    get_asset_object_path(&Self::get_assets_dir(&self.game_dir), hash)
+  }
+
+  pub fn list_local_asset_indexes(&self) -> Vec<LocalAssetIndex> {
+    let indexes_dir = Self::get_assets_dir(&self.game_dir).join("indexes");
+    self
+      .list_asset_indexes()
+      .into_iter()
+      .map(|id| LocalAssetIndex { file_path: indexes_dir.join(format!("{id}.json")), id, object_count: None })
+      .collect()
+  }
+
+  // Only checks that every object exists, hashes are not verified
+  pub fn asset_index_is_complete(&self, id: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    let index_file = Self::get_assets_dir(&self.game_dir).join("indexes").join(format!("{id}.json"));
+    let asset_index = AssetIndex::from_file(&index_file)?;
+
+    Ok(
+      asset_index
+        .objects
+        .values()
+        .all(|obj| self.get_asset_object_path(&obj.hash).is_file())
+    )
+  }
+
+  pub fn get_asset_index_info(&self, id: &str) -> Option<AssetIndexStats> {
+    let asset_index = self.read_asset_index(id)?;
+
+    let objects = asset_index.get_unique_objects();
+    let mut stats = AssetIndexStats { object_count: objects.len(), ..Default::default() };
+    for obj in objects.keys() {
+      stats.total_size += obj.size;
+      if self.get_asset_object_path(&obj.hash).is_file() {
+        stats.cached_count += 1;
+      }
+    }
+    Some(stats)
+  }
+
+  pub fn count_missing_assets(&self, local_version: &LocalVersionInfo) -> Result<u64, Box<dyn std::error::Error>> {
+    let Some(asset_index_info) = &local_version.asset_index else {
+      return Ok(0);
+    };
+    let index_file = Self::get_assets_dir(&self.game_dir).join("indexes").join(format!("{}.json", asset_index_info.id));
+    let asset_index = AssetIndex::from_file(&index_file)?;
+
+    let missing = asset_index
+      .get_unique_objects()
+      .keys()
+      .filter(|obj| !self.get_asset_object_path(&obj.hash).exists())
+      .count();
+    Ok(missing as u64)
+  }
+
+  // Sizes are taken from the version json, only libraries without download info are asked to their server
+  pub async fn get_required_disk_space(
+    &self,
+    id: &MCVersion,
+    proxy: &ProxyOptions,
+    http_config: &HttpClientConfig
+  ) -> Result<DiskSpaceEstimate, Box<dyn std::error::Error>> {
+    let local_version = self.get_or_fetch_version(id).await?;
+    let os = OperatingSystem::get_current_platform();
+    let mut estimate = DiskSpaceEstimate::default();
+
+    let libraries_dir = Self::get_library_dir(&self.game_dir);
+    for library in local_version.get_relevant_libraries(self.feature_matcher.deref()) {
+      let classifier = if library.natives.is_empty() {
+        None
+      } else if let Some(native) = library.native_classifier(&os) {
+        Some(native)
+      } else {
+        continue;
+      };
+      let mut name = library.name.clone();
+      if let Some(classifier) = &classifier {
+        name.classifier = Some(classifier.clone());
+      }
+      let file = name.get_local_path(&libraries_dir);
+
+      let info = library.downloads.as_ref().and_then(|downloads| downloads.get_download_info(classifier.as_deref()));
+      let size = match info {
+        Some(info) if library.url.is_none() => info.size as u64,
+        _ => {
+          let download = library.create_download(
+            proxy,
+            http_config,
+            &name.get_path_string(),
+            &file,
+            false,
+            classifier.as_deref()
+          );
+          match download {
+            Some(download) => download.estimated_size().await.unwrap_or(0),
+            None => 0,
+          }
+        }
+      };
+      estimate.libraries_bytes += size;
+      if file.is_file() {
+        estimate.already_cached_bytes += size;
+      }
+    }
+
+    // Old versions without client download info have no known size
+    if let Some(info) = local_version.get_download_url(DownloadType::Client) {
+      let jar_id = local_version.get_jar().to_string();
+      estimate.client_jar_bytes = info.size as u64;
+      if self.game_dir.join("versions").join(&jar_id).join(format!("{jar_id}.jar")).is_file() {
+        estimate.already_cached_bytes += estimate.client_jar_bytes;
+      }
+    }
+
+    if let Some(asset_index_info) = &local_version.asset_index {
+      let index_size = asset_index_info.size as u64;
+      estimate.assets_bytes = asset_index_info.total_size as u64 + index_size;
+      let index_file = Self::get_assets_dir(&self.game_dir).join("indexes").join(format!("{}.json", asset_index_info.id));
+      if let Ok(asset_index) = AssetIndex::from_file(&index_file) {
+        estimate.already_cached_bytes += index_size;
+        estimate.already_cached_bytes += asset_index
+          .get_unique_objects()
+          .keys()
+          .filter(|obj| self.get_asset_object_path(&obj.hash).is_file())
+          .map(|obj| obj.size)
+          .sum::<u64>();
+      }
+    }
+
+    estimate.total_bytes = estimate.libraries_bytes + estimate.client_jar_bytes + estimate.assets_bytes;
+    Ok(estimate)
+  }
+
+  // Libraries are matched by group, artifact and classifier, so a version bump is a single Updated change
+  pub fn compare_libraries(old: &LocalVersionInfo, new: &LocalVersionInfo) -> Vec<LibraryChange> {
+    let mut changes = vec![];
+    for library in new.get_libraries() {
+      match old.get_libraries().iter().find(|old_library| old_library.name.matches_without_version(&library.name)) {
+        None => changes.push(LibraryChange::Added(library.name.clone())),
+        Some(old_library) if old_library.name.version != library.name.version => {
+          changes.push(LibraryChange::Updated { from: old_library.name.clone(), to: library.name.clone() });
+        }
+        Some(_) => {}
+      }
+    }
+    for library in old.get_libraries() {
+      if !new.get_libraries().iter().any(|new_library| new_library.name.matches_without_version(&library.name)) {
+        changes.push(LibraryChange::Removed(library.name.clone()));
+      }
+    }
+    changes
+  }
+
+  // Version jsons that aren't installed are fetched once, versions that fail to download are left out
+  pub async fn get_compatible_versions(&self, java_version: u8, os: &OperatingSystem) -> Vec<RemoteVersionInfo> {
+    let requirements: Vec<_> = futures::stream
+      ::iter(self.get_remote_versions())
+      .map(|remote_version| async move {
+        let requirements = self.get_version_requirements(&remote_version).await;
+        (remote_version, requirements)
+      })
+      .buffered(16)
+      .collect().await;
+
+    requirements
+      .into_iter()
+      .filter_map(|(remote_version, requirements)| {
+        let requirements = requirements?;
+        requirements.is_compatible(java_version, os, self.feature_matcher.deref()).then_some(remote_version)
+      })
+      .collect()
+  }
+
+  async fn get_version_requirements(&self, remote_version: &RemoteVersionInfo) -> Option<VersionRequirements> {
+    let version_id = remote_version.get_id();
+    if let Some(requirements) = self.requirements_cache.lock().unwrap().get(version_id) {
+      return Some(requirements.clone());
+    }
+    let local_version = match self.get_local_version(version_id) {
+      Some(local_version) => local_version,
+      None =>
+        match remote_version.fetch().await {
+          Ok(local_version) => local_version,
+          Err(err) => {
+            warn!("Failed to fetch version {}: {err}", version_id.to_string());
+            return None;
+          }
+        }
+    };
+    let requirements = VersionRequirements::from_version(&local_version);
+    self.requirements_cache.lock().unwrap().insert(version_id.clone(), requirements.clone());
+    Some(requirements)
+  }
+
+  // Walks the releases from newest to oldest, version jsons are only fetched when they aren't installed
+  pub async fn get_latest_supported_version(&self, java_version: u8) -> Option<RemoteVersionInfo> {
+    let mut releases: Vec<RemoteVersionInfo> = self
+      .get_remote_versions()
+      .into_iter()
+      .filter(|remote_version| remote_version.get_type() == &ReleaseType::Release)
+      .collect();
+    releases.sort_by(|a, b| b.get_release_time().inner().cmp(a.get_release_time().inner()));
+
+    for remote_version in releases {
+      let local_version = match self.get_local_version(remote_version.get_id()) {
+        Some(local_version) => local_version,
+        None =>
+          match remote_version.fetch().await {
+            Ok(local_version) => local_version,
+            Err(err) => {
+              warn!("Failed to fetch version {}: {err}", remote_version.get_id().to_string());
+              continue;
+            }
+          }
+      };
+      let required = local_version.get_java_version().cloned().unwrap_or_default().major_version;
+      if required <= java_version as i64 {
+        return Some(remote_version);
+      }
+    }
+    None
+  }
+
+  // Last snapshot (or pre-release) published between the previous release and this one
+  pub fn get_latest_snapshot_for_release(&self, release: &MCVersion) -> Option<RemoteVersionInfo> {
+    let remote_versions = self.get_remote_versions();
+    let snapshots = Self::group_snapshots_by_release(remote_versions.clone());
+    remote_versions
+      .into_iter()
+      .filter(|remote_version| snapshots.get(remote_version.get_id()) == Some(release))
+      .max_by(|a, b| a.get_release_time().inner().cmp(b.get_release_time().inner()))
+  }
+
+  // Unknown versions are assumed to be recent
+  pub fn get_recommended_memory(&self, version_id: &MCVersion) -> MemoryRecommendation {
+    let release_time = match self.get_local_version(version_id) {
+      Some(local_version) => Some(local_version.get_release_time().clone()),
+      None => self.get_remote_version(version_id).map(|remote_version| remote_version.get_release_time().clone()),
+    };
+    match release_time {
+      Some(release_time) => MemoryRecommendation::for_release_time(&release_time),
+      None => MemoryRecommendation { min_mb: 1024, recommended_mb: 2048 },
+    }
+  }
+
+  // Versions that aren't installed can't be inspected and are reported as Other
+  pub fn get_mod_loader_type(&self, version_id: &MCVersion) -> ModLoaderType {
+    self
+      .get_local_version(version_id)
+      .map(|local_version| ModLoaderType::from_version(&local_version))
+      .unwrap_or(ModLoaderType::Other)
+  }
+
+  pub fn list_installed_with_mod_loaders(&self) -> Vec<VersionSummary> {
+    self
+      .get_local_versions()
+      .par_iter()
+      .map(|local_version| {
+        let id = local_version.get_id().clone();
+        let mut base_version = None;
+        let mut visited = HashSet::from([id.clone()]);
+        let mut parent = local_version.get_inherits_from().cloned();
+        while let Some(parent_id) = parent.filter(|parent_id| visited.insert(parent_id.clone())) {
+          parent = self.get_local_version(&parent_id).and_then(|parent| parent.get_inherits_from().cloned());
+          base_version = Some(parent_id);
+        }
+        VersionSummary {
+          release_type: local_version.get_type().clone(),
+          mod_loader: self.get_mod_loader_type(&id),
+          base_version,
+          installed_size_bytes: self.get_total_installation_size(&id),
+          id,
+        }
+      })
+      .collect()
+  }
+
+  pub fn get_version_features(&self, version_id: &MCVersion) -> HashSet<String> {
+    let mut features = HashSet::new();
+    let mut visited = HashSet::new();
+    let mut current = self.get_local_version(version_id);
+    while let Some(local_version) = current {
+      if !visited.insert(local_version.get_id().clone()) {
+        break;
+      }
+      features.extend(local_version.get_referenced_features().iter().map(|feature| feature.get_name().to_string()));
+      current = local_version.get_inherits_from().and_then(|parent| self.get_local_version(parent));
+    }
+    features
+  }
+
+  pub fn get_total_installation_size(&self, version_id: &MCVersion) -> u64 {
+    let versions_dir = self.game_dir.join("versions");
+    let os = OperatingSystem::get_current_platform();
+
+    let mut files = HashSet::new();
+    let mut visited = HashSet::new();
+    let mut current = self.get_local_version(version_id);
+    while let Some(local_version) = current {
+      if !visited.insert(local_version.get_id().clone()) {
+        break;
+      }
+
+      let id = local_version.get_id().to_string();
+      files.insert(versions_dir.join(&id).join(format!("{id}.json")));
+      let jar_id = local_version.get_jar().to_string();
+      files.insert(versions_dir.join(&jar_id).join(format!("{jar_id}.jar")));
+
+      for file in local_version.get_required_files(&os, self.feature_matcher.deref()) {
+        files.insert(join_path_string(&self.game_dir, &file));
+      }
+
+      if let Some(asset_index) = local_version.asset_index.as_ref().and_then(|info| self.read_asset_index(&info.id)) {
+        for obj in asset_index.objects.values() {
+          files.insert(self.get_asset_object_path(&obj.hash));
+        }
+      }
+
+      current = local_version.get_inherits_from().and_then(|parent| self.get_local_version(parent));
+    }
+
+    files
+      .iter()
+      .map(|file| file.metadata().map(|metadata| metadata.len()).unwrap_or(0))
+      .sum()
+  }
+
+  pub fn get_version_size_on_disk(&self, version_id: &MCVersion, options: VersionSizeOptions) -> Result<u64, Box<dyn std::error::Error>> {
+    let Some(local_version) = self.get_local_version(version_id) else {
+      Err(MinecraftLauncherError(format!("Version {} is not installed", version_id.to_string())))?
+    };
+
+    let mut files = HashSet::new();
+    let version_dir = self.game_dir.join("versions").join(version_id.to_string());
+    if version_dir.is_dir() {
+      files.extend(Self::collect_files(&version_dir)?);
+    }
+
+    // Every platform variant is counted, not only the ones the current platform needs
+    if options.include_libraries {
+      for library in local_version.get_libraries() {
+        files.extend(library.get_all_download_paths(&self.game_dir).into_iter().map(|(_, path)| path));
+      }
+    }
+
+    if options.include_assets {
+      if let Some(asset_index) = local_version.asset_index.as_ref().and_then(|info| self.read_asset_index(&info.id)) {
+        for obj in asset_index.objects.values() {
+          files.insert(self.get_asset_object_path(&obj.hash));
+        }
+      }
+    }
+
+    Ok(
+      files
+        .iter()
+        .map(|file| file.metadata().map(|metadata| metadata.len()).unwrap_or(0))
+        .sum()
+    )
+  }
+
+  fn read_asset_index(&self, id: &str) -> Option<AssetIndex> {
+    let index_file = Self::get_assets_dir(&self.game_dir).join("indexes").join(format!("{id}.json"));
+    AssetIndex::from_file(&index_file).ok()
+  }
+
+  // Objects that were added or changed between two asset indexes, for minor version updates. Unchanged objects
+  // are only queued when they're missing locally
+  pub fn get_asset_update_files(
+    proxy: &ProxyOptions,
+    http_config: &HttpClientConfig,
+    objects_dir: &Path,
+    url_base: &Url,
+    old_index: &AssetIndex,
+    new_index: &AssetIndex
+  ) -> Vec<Box<dyn Downloadable + Send + Sync>> {
+    let diff = old_index.diff(new_index);
+    let updated: HashSet<&String> = diff.added.iter().chain(&diff.changed).collect();
+    let mut vec: Vec<Box<dyn Downloadable + Send + Sync>> = vec![];
+    for (obj, name) in new_index.get_unique_objects() {
+      let downloadable = Box::new(AssetDownloadable::new(proxy.create_http_client_with_config(http_config), name, obj, url_base.as_str(), objects_dir, false));
+      if !updated.contains(name) && downloadable.get_target_file().is_file() {
+        continue;
+      }
+      downloadable.monitor.set_total(obj.size as usize);
+      vec.push(downloadable);
+    }
+    vec
+  }
+
+  pub async fn get_resource_files(
+    &self,
+    proxy: &ProxyOptions,
+    http_config: &HttpClientConfig,
+    game_dir: &PathBuf,
+    local_version: &LocalVersionInfo,
+    force_download: bool
   ) -> Result<Vec<Box<dyn Downloadable + Send + Sync>>, Box<dyn std::error::Error>> {
-    let assets_dir = game_dir.join("assets");
+    let assets_dir = Self::get_assets_dir(game_dir);
     let objects_dir = assets_dir.join("objects");
     let indexes_dir = assets_dir.join("indexes");
 
-    let mut vec: Vec<Box<dyn Downloadable + Send + Sync>> = vec![];
-
     let index_info = local_version.asset_index.as_ref().unwrap();
     let index_file = indexes_dir.join(format!("{}.json", index_info.id));
 
-    let url = &index_info.url;
-    let bytes = Client::new().get(url).send().await?.bytes().await?;
+    let download_info = local_version.get_asset_index_download_info().unwrap();
+    // An outdated index is kept around so only the objects its update touched have to be queued
+    let previous_index = if force_download || Sha1Sum::from_file(&index_file).map_or(true, |sha1| sha1 == download_info.sha1) {
+      None
+    } else {
+      AssetIndex::from_file(&index_file).ok()
+    };
     create_dir_all(indexes_dir)?;
-    fs::write(&index_file, &bytes)?;
-    let asset_index: AssetIndex = serde_json::from_reader(&mut Cursor::new(&bytes))?;
-    let objects = asset_index.get_unique_objects();
-    for (obj, value) in objects {
-      // let hash = obj.hash.to_string();
-      let downloadable = Box::new(
-        AssetDownloadable::new(proxy.create_http_client(), value, obj, "https://resources.download.minecraft.net/", &objects_dir)
-      );
-      downloadable.monitor.set_total(obj.size as usize);
-      vec.push(downloadable);
-    }
-
-    Ok(vec)
+    PreHashedDownloadable::new(
+      proxy.create_http_client_with_config(http_config),
+      &download_info.url,
+      &index_file,
+      force_download,
+      download_info.sha1.clone()
+    )
+      .with_expected_size(download_info.size as u64)
+      .with_max_size(MAX_ASSET_INDEX_SIZE)
+      .download().await
+      .map_err(|err| MinecraftLauncherError(format!("Couldn't download asset index {}: {err}", index_info.id)))?;
+    let asset_index = AssetIndex::from_file(&index_file)?;
+    let url_base = Self::get_asset_cdn_base_url(local_version);
+    Ok(match previous_index {
+      Some(previous_index) => Self::get_asset_update_files(proxy, http_config, &objects_dir, &url_base, &previous_index, &asset_index),
+      None => asset_index.get_downloadables(proxy, http_config, &objects_dir, &url_base, force_download),
+    })
   }
 }
 
@@ -237,6 +2457,1124 @@ mod tests {
     }
   }
 
+  fn remote_version(id: &str, release_type: &str, release_time: &str) -> RemoteVersionInfo {
+    let json = serde_json::json!({
+      "id": id,
+      "type": release_type,
+      "url": "",
+      "time": release_time,
+      "releaseTime": release_time,
+      "sha1": "0000000000000000000000000000000000000000",
+      "complianceLevel": 1
+    });
+    serde_json::from_value(json).unwrap()
+  }
+
+  #[tokio::test]
+  async fn test_auto_update_if_needed() -> Result<(), Box<dyn std::error::Error>> {
+    let game_dir = temp_dir().join("mclc-test-auto-update");
+    let _ = fs::remove_dir_all(&game_dir);
+    let current_sha1 = "3c1a3b8fa1bd1a1ba1b2e2d1c0c7f0c9d6a1b2c3";
+    // 1.20.2 was installed without recording the manifest sha1
+    for (id, installed_sha1) in [("1.20.4", Some(current_sha1)), ("1.20.3", Some("1111111111111111111111111111111111111111")), ("1.20.2", None), ("my-pack", None)] {
+      let version_dir = game_dir.join("versions").join(id);
+      create_dir_all(&version_dir)?;
+      let version_json = serde_json::json!({
+        "id": id,
+        "releaseTime": "2023-12-07T12:56:20+00:00",
+        "time": "2023-12-07T12:56:20+00:00",
+        "type": "release"
+      });
+      fs::write(version_dir.join(format!("{id}.json")), version_json.to_string())?;
+      if let Some(installed_sha1) = installed_sha1 {
+        fs::write(version_dir.join(format!("{id}.sha1")), installed_sha1)?;
+      }
+    }
+
+    let version_manager = VersionManager::new(game_dir.clone(), Box::new(TestFeatureMatcher));
+    version_manager.refresh_local()?;
+    let mut current = serde_json::to_value(remote_version("1.20.4", "release", "2023-12-07T12:56:20+00:00"))?;
+    current["sha1"] = current_sha1.into();
+    *version_manager.remote_versions_cache.lock().unwrap() = vec![
+      serde_json::from_value(current)?,
+      remote_version("1.20.3", "release", "2023-12-05T11:10:28+00:00"),
+      remote_version("1.20.2", "release", "2023-09-20T09:02:57+00:00")
+    ];
+
+    assert!(!version_manager.auto_update_if_needed(&MCVersion::from("1.20.4".to_string())).await?);
+    assert!(!version_manager.auto_update_if_needed(&MCVersion::from("1.20.2".to_string())).await?);
+    assert!(!version_manager.auto_update_if_needed(&MCVersion::from("my-pack".to_string())).await?);
+    // Outdated, the test manifest has no url to update it from
+    let outdated = MCVersion::from("1.20.3".to_string());
+    assert!(version_manager.auto_update_if_needed(&outdated).await.is_err());
+
+    version_manager.pin_version(&outdated)?;
+    assert!(version_manager.is_pinned(&outdated));
+    assert!(!version_manager.auto_update_if_needed(&outdated).await?);
+    assert!(version_manager.is_up_to_date(&version_manager.get_local_version(&outdated).unwrap()).await);
+    version_manager.unpin_version(&outdated)?;
+    assert!(!version_manager.is_pinned(&outdated));
+    assert!(version_manager.pin_version(&MCVersion::from("1.8.9".to_string())).is_err());
+
+    fs::remove_dir_all(game_dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_verify_java_agent() -> Result<(), Box<dyn std::error::Error>> {
+    use zip::{ write::FileOptions, ZipWriter };
+
+    let game_dir = temp_dir().join("mclc-test-verify-java-agent");
+    let _ = fs::remove_dir_all(&game_dir);
+    create_dir_all(&game_dir)?;
+    let write_jar = |name: &str, manifest: &str| -> Result<JavaAgent, Box<dyn std::error::Error>> {
+      let path = game_dir.join(name);
+      let mut zip = ZipWriter::new(File::create(&path)?);
+      zip.start_file("META-INF/MANIFEST.MF", FileOptions::default())?;
+      io::Write::write_all(&mut zip, manifest.as_bytes())?;
+      zip.finish()?;
+      Ok(JavaAgent { path, options: None })
+    };
+    let agent = write_jar("agent.jar", "Manifest-Version: 1.0\r\nPremain-Class: com.example.Agent\r\n")?;
+    let library = write_jar("library.jar", "Manifest-Version: 1.0\r\n")?;
+    let not_a_jar = JavaAgent { path: game_dir.join("agent.txt"), options: None };
+    fs::write(&not_a_jar.path, "not a jar")?;
+
+    let version_manager = VersionManager::new(game_dir.clone(), Box::new(TestFeatureMatcher));
+    let info = version_manager.verify_java_agent(&agent)?;
+    assert_eq!(info.premain_class.as_deref(), Some("com.example.Agent"));
+    assert!(!info.can_redefine_classes);
+    assert_eq!(version_manager.verify_java_agent(&library).unwrap_err().to_string(), JavaAgentError::NoPremainClass.to_string());
+    assert_eq!(
+      version_manager.verify_java_agent(&not_a_jar).unwrap_err().to_string(),
+      JavaAgentError::NotAJar(not_a_jar.path.clone()).to_string()
+    );
+
+    fs::remove_dir_all(game_dir)?;
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_guess_version_from_jar() -> Result<(), Box<dyn std::error::Error>> {
+    use zip::{ write::FileOptions, ZipWriter, DateTime };
+
+    let game_dir = temp_dir().join("mclc-test-guess-version");
+    let _ = fs::remove_dir_all(&game_dir);
+    create_dir_all(&game_dir)?;
+    let write_jar = |name: &str, manifest: Option<(&str, DateTime)>| -> Result<PathBuf, Box<dyn std::error::Error>> {
+      let path = game_dir.join(name);
+      let mut zip = ZipWriter::new(File::create(&path)?);
+      if let Some((manifest, built_at)) = manifest {
+        zip.start_file("META-INF/MANIFEST.MF", FileOptions::default().last_modified_time(built_at))?;
+        io::Write::write_all(&mut zip, manifest.as_bytes())?;
+      }
+      zip.start_file("net/minecraft/client/main/Main.class", FileOptions::default())?;
+      zip.finish()?;
+      Ok(path)
+    };
+
+    let manifest = "Manifest-Version: 1.0\r\nImplementation-Version: 1.20.4\r\n";
+    let release_jar = write_jar("release.jar", Some((manifest, DateTime::from_date_and_time(2023, 12, 7, 10, 0, 0).unwrap())))?;
+    let rc_jar = write_jar("rc.jar", Some((manifest, DateTime::from_date_and_time(2023, 12, 5, 10, 0, 0).unwrap())))?;
+    let unknown_jar = write_jar("unknown.jar", None)?;
+    let remote_jar = game_dir.join("remote.jar");
+    fs::write(&remote_jar, "remote client")?;
+
+    let version_dir = game_dir.join("versions").join("1.8.9");
+    create_dir_all(&version_dir)?;
+    let version_json = serde_json::json!({
+      "id": "1.8.9",
+      "releaseTime": "2015-12-03T09:24:39+00:00",
+      "time": "2015-12-03T09:24:39+00:00",
+      "type": "release",
+      "downloads": { "client": { "url": "https://example.com", "sha1": Sha1Sum::from_file(&unknown_jar)?, "size": 3 } }
+    });
+    fs::write(version_dir.join("1.8.9.json"), version_json.to_string())?;
+
+    let version_manager = VersionManager::new(game_dir.clone(), Box::new(TestFeatureMatcher));
+    version_manager.refresh_local()?;
+    // Only known through the manifest, its version json is served from a file:// url
+    let remote_json = game_dir.join("1.7.10.json");
+    let remote_json_content = serde_json::json!({
+      "id": "1.7.10",
+      "releaseTime": "2014-05-14T17:29:23+00:00",
+      "time": "2014-05-14T17:29:23+00:00",
+      "type": "release",
+      "downloads": { "client": { "url": "https://example.com", "sha1": Sha1Sum::from_file(&remote_jar)?, "size": 13 } }
+    });
+    fs::write(&remote_json, remote_json_content.to_string())?;
+    let remote_1_7_10: RemoteVersionInfo = serde_json::from_value(
+      serde_json::json!({
+      "id": "1.7.10",
+      "type": "release",
+      "url": Url::from_file_path(&remote_json).unwrap().to_string(),
+      "time": "2014-05-14T17:29:23+00:00",
+      "releaseTime": "2014-05-14T17:29:23+00:00",
+      "sha1": Sha1Sum::from_file(&remote_json)?,
+      "complianceLevel": 0
+    })
+    )?;
+    *version_manager.remote_versions_cache.lock().unwrap() = vec![
+      remote_version("1.20.4", "release", "2023-12-07T12:56:20+00:00"),
+      remote_version("1.20.4-rc1", "snapshot", "2023-12-04T13:22:00+00:00"),
+      remote_version("1.20.3", "release", "2023-12-05T11:10:28+00:00"),
+      remote_1_7_10
+    ];
+
+    assert_eq!(version_manager.guess_version_from_jar(&release_jar).await, Some(MCVersion::from("1.20.4".to_string())));
+    assert_eq!(version_manager.guess_version_from_jar(&rc_jar).await, Some(MCVersion::from("1.20.4-rc1".to_string())));
+    assert_eq!(version_manager.guess_version_from_jar(&unknown_jar).await, Some(MCVersion::from("1.8.9".to_string())));
+    assert_eq!(version_manager.guess_version_from_jar(&remote_jar).await, Some(MCVersion::from("1.7.10".to_string())));
+    assert_eq!(version_manager.guess_version_from_jar(&game_dir.join("missing.jar")).await, None);
+    assert_eq!(version_manager.find_installed_version_by_client_sha1(&Sha1Sum::from_file(&release_jar)?), None);
+
+    fs::remove_dir_all(game_dir)?;
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_minimal_manifest() -> Result<(), Box<dyn std::error::Error>> {
+    let root = temp_dir().join("mclc-test-minimal-manifest");
+    let _ = fs::remove_dir_all(&root);
+    let (game_dir, export_dir, offline_dir) = (root.join("game"), root.join("export"), root.join("offline"));
+    for (id, release_type, release_time) in [
+      ("1.20.4", "release", "2023-12-07T12:56:20+00:00"),
+      ("1.20.3", "release", "2023-12-05T11:10:28+00:00"),
+      ("23w51a", "snapshot", "2023-12-18T14:24:37+00:00"),
+    ] {
+      let version_dir = game_dir.join("versions").join(id);
+      create_dir_all(&version_dir)?;
+      let version_json = serde_json::json!({ "id": id, "releaseTime": release_time, "time": release_time, "type": release_type });
+      fs::write(version_dir.join(format!("{id}.json")), version_json.to_string())?;
+    }
+    let version_manager = VersionManager::new(game_dir.clone(), Box::new(TestFeatureMatcher));
+    version_manager.refresh_local()?;
+    let exported = ["1.20.3", "1.20.4", "23w51a"].map(|id| MCVersion::from(id.to_string()));
+    version_manager.export_minimal_manifest(&exported, &export_dir)?;
+    assert!(version_manager.export_minimal_manifest(&[MCVersion::from("1.8.9".to_string())], &export_dir).is_err());
+
+    let manifest: RawVersionList = serde_json::from_slice(&fs::read(export_dir.join("version_manifest_v2.json"))?)?;
+    assert_eq!(manifest.versions.len(), 3);
+    assert_eq!(manifest.versions[0].get_url(), "versions/1.20.3/1.20.3.json");
+    assert_eq!(manifest.latest[&ReleaseType::Release], exported[1]);
+    assert_eq!(manifest.latest[&ReleaseType::Snapshot], exported[2]);
+
+    let offline_manager = VersionManager::new(offline_dir.clone(), Box::new(TestFeatureMatcher));
+    offline_manager.import_minimal_manifest(&export_dir.join("version_manifest_v2.json"))?;
+    assert_eq!(offline_manager.get_remote_versions().len(), 3);
+    let installed = offline_manager.install_version(&exported[1]).await?;
+    assert_eq!(installed.get_id(), &exported[1]);
+    assert!(offline_manager.is_up_to_date_cached(&installed));
+
+    fs::remove_dir_all(root)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_get_asset_update_files() -> Result<(), Box<dyn std::error::Error>> {
+    let objects_dir = temp_dir().join("mclc-test-asset-update-files").join("objects");
+    let _ = fs::remove_dir_all(&objects_dir);
+    let old_index: AssetIndex = serde_json::from_value(
+      serde_json::json!({ "objects": {
+        "icons/icon_16x16.png": { "hash": "bdf48ef6b5d0d23bbb02e17d04865216179f510a", "size": 3 },
+        "icons/icon_32x32.png": { "hash": "9040a7d6cdf7a0d6cab1823831c6ceb7d01af97f", "size": 3 },
+        "lang/en_us.json": { "hash": "f92e777f4341930bad9b2422283c4680d00dbc06", "size": 3 }
+      } })
+    )?;
+    let new_index: AssetIndex = serde_json::from_value(
+      serde_json::json!({ "objects": {
+        "icons/icon_16x16.png": { "hash": "bdf48ef6b5d0d23bbb02e17d04865216179f510a", "size": 3 },
+        "icons/icon_32x32.png": { "hash": "9040a7d6cdf7a0d6cab1823831c6ceb7d01af97f", "size": 3 },
+        "lang/en_us.json": { "hash": "0a4cb3c8e4b3e4e6b2e7e4e2b4d0a1c2e3f4a5b6", "size": 3 },
+        "sounds/new.ogg": { "hash": "1b2c3d4e5f6a7b8c9d0e1f2a3b4c5d6e7f8a9b0c", "size": 3 }
+      } })
+    )?;
+    // icon_32x32 is unchanged but was never downloaded
+    let unchanged = objects_dir.join("bd").join("bdf48ef6b5d0d23bbb02e17d04865216179f510a");
+    create_dir_all(unchanged.parent().unwrap())?;
+    fs::write(unchanged, "abc")?;
+
+    let url_base = Url::parse(ASSET_CDN_BASE_URL)?;
+    let downloadables = VersionManager::get_asset_update_files(&ProxyOptions::NoProxy, &HttpClientConfig::default(), &objects_dir, &url_base, &old_index, &new_index);
+    let mut hashes: Vec<String> = downloadables
+      .iter()
+      .map(|downloadable| downloadable.get_target_file().file_name().unwrap().to_string_lossy().to_string())
+      .collect();
+    hashes.sort();
+    assert_eq!(hashes, [
+      "0a4cb3c8e4b3e4e6b2e7e4e2b4d0a1c2e3f4a5b6",
+      "1b2c3d4e5f6a7b8c9d0e1f2a3b4c5d6e7f8a9b0c",
+      "9040a7d6cdf7a0d6cab1823831c6ceb7d01af97f",
+    ]);
+
+    fs::remove_dir_all(objects_dir.parent().unwrap())?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_get_asset_cdn_base_url() -> Result<(), Box<dyn std::error::Error>> {
+    let version = |extra: Value| -> LocalVersionInfo {
+      let mut json = serde_json::json!({
+        "id": "b1.7.3", "releaseTime": "2011-07-07T22:00:00+00:00", "time": "2011-07-07T22:00:00+00:00", "type": "old_beta"
+      });
+      json.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+      serde_json::from_value(json).unwrap()
+    };
+    assert_eq!(VersionManager::get_asset_cdn_base_url(&version(serde_json::json!({}))).as_str(), ASSET_CDN_BASE_URL);
+    let custom = VersionManager::get_asset_cdn_base_url(&version(serde_json::json!({ "assetBaseUrl": "https://cdn.example.com/legacy" })));
+    assert_eq!(custom.as_str(), "https://cdn.example.com/legacy/");
+    assert_eq!(VersionManager::get_asset_cdn_base_url(&version(serde_json::json!({ "assetBaseUrl": "not a url" }))).as_str(), ASSET_CDN_BASE_URL);
+
+    let asset_index: AssetIndex = serde_json::from_value(
+      serde_json::json!({ "objects": { "icons/icon_16x16.png": { "hash": "bdf48ef6b5d0d23bbb02e17d04865216179f510a", "size": 3 } } })
+    )?;
+    let downloads = asset_index.get_downloadables(&ProxyOptions::NoProxy, &HttpClientConfig::default(), &PathBuf::from("objects"), &custom, false);
+    assert_eq!(downloads[0].url(), "https://cdn.example.com/legacy/bd/bdf48ef6b5d0d23bbb02e17d04865216179f510a");
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_get_download_urls() -> Result<(), Box<dyn std::error::Error>> {
+    let game_dir = temp_dir().join("mclc-test-download-urls");
+    let _ = fs::remove_dir_all(&game_dir);
+    let sha1 = Sha1Sum::from_reader(&mut "jar".as_bytes())?;
+    let versions = [
+      serde_json::json!({
+        "id": "1.20.4",
+        "releaseTime": "2023-12-07T12:56:20+00:00",
+        "time": "2023-12-07T12:56:20+00:00",
+        "type": "release",
+        "downloads": {
+          "client": { "url": "https://example.com/client.jar", "sha1": sha1, "size": 3 },
+          "server": { "url": "https://example.com/server.jar", "sha1": sha1, "size": 3 },
+          "client_mappings": { "url": "https://example.com/client.txt", "sha1": sha1, "size": 3 }
+        }
+      }),
+      serde_json::json!({
+        "id": "fabric-1.20.4",
+        "inheritsFrom": "1.20.4",
+        "releaseTime": "2023-12-07T12:56:20+00:00",
+        "time": "2023-12-07T12:56:20+00:00",
+        "type": "release"
+      }),
+    ];
+    for version_json in versions {
+      let id = version_json["id"].as_str().unwrap();
+      let version_dir = game_dir.join("versions").join(id);
+      create_dir_all(&version_dir)?;
+      fs::write(version_dir.join(format!("{id}.json")), version_json.to_string())?;
+    }
+
+    let version_manager = VersionManager::new(game_dir.clone(), Box::new(TestFeatureMatcher));
+    version_manager.refresh_local()?;
+    let id = MCVersion::from("fabric-1.20.4".to_string());
+    assert_eq!(version_manager.get_server_pack_url(&id).await, Some(Url::parse("https://example.com/server.jar")?));
+    assert_eq!(version_manager.get_client_url(&id).await, Some(Url::parse("https://example.com/client.jar")?));
+    assert_eq!(version_manager.get_mappings_url(&id, "client").await, Some(Url::parse("https://example.com/client.txt")?));
+    assert_eq!(version_manager.get_mappings_url(&id, "server").await, None);
+    assert_eq!(version_manager.get_mappings_url(&id, "bedrock").await, None);
+    assert_eq!(version_manager.get_client_url(&MCVersion::from("1.8.9".to_string())).await, None);
+
+    fs::remove_dir_all(game_dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_group_snapshots_by_release() {
+    let versions = vec![
+      remote_version("1.20.4", "release", "2023-12-07T12:56:20+00:00"),
+      remote_version("23w51a", "snapshot", "2023-12-18T14:24:37+00:00"),
+      remote_version("1.20.3", "release", "2023-12-05T11:10:28+00:00"),
+      remote_version("1.20.4-rc1", "snapshot", "2023-12-06T13:22:00+00:00"),
+      remote_version("23w46a", "snapshot", "2023-11-16T12:44:21+00:00"),
+      remote_version("b1.7.3", "old_beta", "2011-07-07T22:00:00+00:00")
+    ];
+
+    let map = VersionManager::group_snapshots_by_release(versions);
+    assert_eq!(map.len(), 2);
+    assert_eq!(map.get(&MCVersion::new("23w46a")), Some(&MCVersion::new("1.20.3")));
+    assert_eq!(map.get(&MCVersion::new("1.20.4-rc1")), Some(&MCVersion::new("1.20.4")));
+    assert_eq!(map.get(&MCVersion::new("23w51a")), None);
+  }
+
+  #[test]
+  fn test_find_changelog() {
+    let entries: Vec<PatchNotesEntry> = serde_json::from_value(
+      serde_json::json!([
+        { "title": "Minecraft: Java Edition 1.20.4", "version": "1.20.4", "type": "release", "body": "<p>1.20.4</p>", "contentPath": "1.20.4.json" },
+        { "title": "Minecraft: Java Edition 1.20.3", "version": "1.20.3" },
+        { "title": "Minecraft: Java Edition 1.20.2", "version": "1.20.2", "type": "release" }
+      ])
+    ).unwrap();
+
+    let changelog = VersionManager::find_changelog(&entries, &MCVersion::new("1.20.2"), &MCVersion::new("1.20.4")).unwrap();
+    assert_eq!(changelog.title, "Minecraft: Java Edition 1.20.4");
+    assert_eq!(changelog.url, "https://www.minecraft.net/en-us/article/minecraft-java-edition-1-20-4");
+    assert_eq!(changelog.html_content.as_deref(), Some("<p>1.20.4</p>"));
+
+    assert!(VersionManager::find_changelog(&entries, &MCVersion::new("1.20.4"), &MCVersion::new("1.20.2")).is_err());
+    assert!(VersionManager::find_changelog(&entries, &MCVersion::new("1.19"), &MCVersion::new("1.20.4")).is_err());
+    // Without a release type there's no article to link to
+    assert!(VersionManager::find_changelog(&entries, &MCVersion::new("1.20.2"), &MCVersion::new("1.20.3")).is_err());
+  }
+
+  #[test]
+  fn test_find_patch_notes() {
+    let entries: Vec<PatchNotesEntry> = serde_json::from_value(
+      serde_json::json!([
+        { "title": "Minecraft: Java Edition 1.20.4", "version": "1.20.4", "type": "release", "date": "2023-12-07T12:56:20Z", "contentPath": "1.20.4.json" },
+        { "title": "Minecraft: Java Edition 1.20.4 Release Candidate 1", "version": "1.20.4-rc1", "type": "snapshot", "date": "2023-12-05T14:18:53Z" },
+        { "title": "Minecraft: Java Edition 1.20.3", "version": "1.20.3", "type": "release", "date": "2023-12-05T12:10:26Z" },
+        { "title": "Minecraft: Java Edition 1.20.2", "version": "1.20.2", "type": "release", "date": "2023-09-20T09:02:57Z" }
+      ])
+    ).unwrap();
+
+    let notes = VersionManager::find_patch_notes(&entries, &MCVersion::new("1.20.3"), &MCVersion::new("1.20.4")).unwrap();
+    assert_eq!(notes, [
+      PatchNote { version: MCVersion::new("1.20.3"), release_type: ReleaseType::Release, url: String::new() },
+      PatchNote { version: MCVersion::new("1.20.4-rc1"), release_type: ReleaseType::Snapshot, url: String::new() },
+      PatchNote {
+        version: MCVersion::new("1.20.4"),
+        release_type: ReleaseType::Release,
+        url: "https://launchercontent.mojang.com/1.20.4.json".to_string(),
+      },
+    ]);
+
+    assert!(VersionManager::find_patch_notes(&entries, &MCVersion::new("1.20.4"), &MCVersion::new("1.20.3")).unwrap().is_empty());
+    assert!(VersionManager::find_patch_notes(&entries, &MCVersion::new("1.19"), &MCVersion::new("1.20.4")).is_err());
+  }
+
+  #[test]
+  fn test_player_profile_from_raw() {
+    let textures = serde_json::json!({
+      "profileName": "Notch",
+      "textures": {
+        "SKIN": { "url": "http://textures.minecraft.net/texture/skin", "metadata": { "model": "slim" } },
+        "CAPE": { "url": "http://textures.minecraft.net/texture/cape" }
+      }
+    });
+    let raw_profile: RawPlayerProfile = serde_json::from_value(
+      serde_json::json!({
+        "id": "069a79f444e94726a5befca90e38aaf5",
+        "name": "Notch",
+        "properties": [{ "name": "textures", "value": STANDARD.encode(textures.to_string()) }]
+      })
+    ).unwrap();
+    assert_eq!(PlayerProfile::from_raw(&raw_profile).unwrap(), PlayerProfile {
+      skin_url: Some(Url::parse("http://textures.minecraft.net/texture/skin").unwrap()),
+      cape_url: Some(Url::parse("http://textures.minecraft.net/texture/cape").unwrap()),
+      is_slim: true,
+    });
+
+    let raw_profile: RawPlayerProfile = serde_json::from_value(serde_json::json!({ "id": "069a79f444e94726a5befca90e38aaf5", "name": "Notch" })).unwrap();
+    assert_eq!(PlayerProfile::from_raw(&raw_profile).unwrap(), PlayerProfile::default());
+  }
+
+  #[test]
+  fn test_news_item_from_raw() {
+    let entry: RawNewsEntry = serde_json::from_value(
+      serde_json::json!({
+        "title": "Minecraft Java Edition 1.20.4",
+        "date": "2023-12-07",
+        "text": "A new release",
+        "newsPageImage": { "url": "/images/1.20.4.jpg" },
+        "readMoreLink": "https://www.minecraft.net/article/1-20-4"
+      })
+    ).unwrap();
+
+    let item = NewsItem::from_raw(&entry).unwrap();
+    assert_eq!(item.date.inner().to_rfc3339(), "2023-12-07T00:00:00+00:00");
+    assert_eq!(item.image_url.unwrap().as_str(), "https://launchercontent.mojang.com/images/1.20.4.jpg");
+    assert_eq!(item.article_url.as_str(), "https://www.minecraft.net/article/1-20-4");
+
+    let invalid_date = RawNewsEntry { date: "07/12/2023".to_string(), ..entry };
+    assert!(NewsItem::from_raw(&invalid_date).is_err());
+  }
+
+  #[test]
+  fn test_sign_version_json() -> Result<(), Box<dyn std::error::Error>> {
+    let game_dir = temp_dir().join("mclc-test-signing");
+    let version_dir = game_dir.join("versions").join("1.20.4");
+    create_dir_all(&version_dir)?;
+    fs::write(version_dir.join("1.20.4.json"), r#"{ "id": "1.20.4", "type": "release" }"#)?;
+
+    let version_manager = VersionManager::new(game_dir.clone(), Box::new(TestFeatureMatcher));
+    let version_id = MCVersion::new("1.20.4");
+    let private_key = [7u8; 32];
+    let public_key = SigningKey::from_bytes(&private_key).verifying_key().to_bytes();
+    version_manager.sign_version_json(&version_id, &private_key)?;
+    assert!(version_manager.verify_version_json_signature(&version_id, &public_key)?);
+
+    // Formatting changes keep the signature valid, content changes don't
+    fs::write(version_dir.join("1.20.4.json"), r#"{"type":"release","id":"1.20.4"}"#)?;
+    assert!(version_manager.verify_version_json_signature(&version_id, &public_key)?);
+    fs::write(version_dir.join("1.20.4.json"), r#"{"type":"snapshot","id":"1.20.4"}"#)?;
+    assert!(!version_manager.verify_version_json_signature(&version_id, &public_key)?);
+
+    fs::remove_dir_all(game_dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_prune_orphaned_libraries() -> Result<(), Box<dyn std::error::Error>> {
+    let game_dir = temp_dir().join("mclc-test-prune");
+    let _ = fs::remove_dir_all(&game_dir);
+    let version_dir = game_dir.join("versions").join("1.20.4");
+    create_dir_all(&version_dir)?;
+    let version_json = serde_json::json!({
+      "id": "1.20.4",
+      "releaseTime": "2023-12-07T12:56:20+00:00",
+      "time": "2023-12-07T12:56:20+00:00",
+      "type": "release",
+      "libraries": [{ "name": "org.lwjgl:lwjgl:3.3.2" }]
+    });
+    fs::write(version_dir.join("1.20.4.json"), version_json.to_string())?;
+
+    let libraries_dir = game_dir.join("libraries");
+    let used = libraries_dir.join("org/lwjgl/lwjgl/3.3.2/lwjgl-3.3.2.jar");
+    let stale = libraries_dir.join("org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1.jar");
+    let manual = libraries_dir.join("com/example/mod/1.0/mod-1.0.jar");
+    for file in [&used, &stale, &manual] {
+      create_dir_all(file.parent().unwrap())?;
+      fs::write(file, "jar")?;
+    }
+
+    let version_manager = VersionManager::new(game_dir.clone(), Box::new(TestFeatureMatcher));
+    version_manager.refresh_local()?;
+    let report = version_manager.prune_orphaned_libraries()?;
+    assert_eq!(report.deleted, vec![stale.clone()]);
+    assert_eq!(report.bytes_freed, 3);
+    assert!(used.is_file() && manual.is_file());
+    assert!(!stale.parent().unwrap().exists());
+
+    fs::remove_dir_all(game_dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_list_local_asset_indexes() -> Result<(), Box<dyn std::error::Error>> {
+    let game_dir = temp_dir().join("mclc-test-asset-indexes");
+    let _ = fs::remove_dir_all(&game_dir);
+    let indexes_dir = game_dir.join("assets").join("indexes");
+    create_dir_all(&indexes_dir)?;
+    let hash = "bdf48ef6b5d0d23bbb02e17d04865216179f510a";
+    let asset_index = serde_json::json!({ "objects": { "icons/icon_16x16.png": { "hash": hash, "size": 3 } } });
+    fs::write(indexes_dir.join("17.json"), asset_index.to_string())?;
+
+    let version_manager = VersionManager::new(game_dir.clone(), Box::new(TestFeatureMatcher));
+    let mut indexes = version_manager.list_local_asset_indexes();
+    assert_eq!(indexes.len(), 1);
+    assert_eq!(indexes[0].id, "17");
+    assert_eq!(indexes[0].object_count, None);
+    assert_eq!(indexes[0].read_object_count(), Some(1));
+
+    assert!(!version_manager.asset_index_is_complete("17")?);
+    let object = game_dir.join("assets").join("objects").join(&hash[0..2]).join(hash);
+    create_dir_all(object.parent().unwrap())?;
+    fs::write(object, "png")?;
+    assert!(version_manager.asset_index_is_complete("17")?);
+    assert!(version_manager.asset_index_is_complete("missing").is_err());
+
+    fs::remove_dir_all(game_dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_lock() -> Result<(), Box<dyn std::error::Error>> {
+    let game_dir = temp_dir().join("mclc-test-lock");
+    let version_manager = VersionManager::new(game_dir.clone(), Box::new(TestFeatureMatcher));
+    let other = VersionManager::new(game_dir.clone(), Box::new(TestFeatureMatcher));
+
+    let guard = version_manager.lock()?;
+    assert!(other.lock().is_err());
+    drop(guard);
+    drop(other.lock()?);
+
+    fs::remove_dir_all(game_dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_get_orphaned_version_dirs() -> Result<(), Box<dyn std::error::Error>> {
+    let game_dir = temp_dir().join("mclc-test-orphaned-versions");
+    let _ = fs::remove_dir_all(&game_dir);
+    let versions_dir = game_dir.join("versions");
+    for id in ["empty", "broken"] {
+      create_dir_all(versions_dir.join(id))?;
+    }
+    fs::write(versions_dir.join("broken").join("broken.json"), "{")?;
+
+    let version_manager = VersionManager::new(game_dir.clone(), Box::new(TestFeatureMatcher));
+    assert_eq!(version_manager.get_orphaned_version_dirs()?, vec![versions_dir.join("broken"), versions_dir.join("empty")]);
+    assert!(versions_dir.join("empty").is_dir());
+
+    fs::remove_dir_all(game_dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_create_modpack_version() -> Result<(), Box<dyn std::error::Error>> {
+    let game_dir = temp_dir().join("mclc-test-modpack");
+    let _ = fs::remove_dir_all(&game_dir);
+    let version_manager = VersionManager::new(game_dir.clone(), Box::new(TestFeatureMatcher));
+    let sha1 = Sha1Sum::try_from("bdf48ef6b5d0d23bbb02e17d04865216179f510a".to_string())?;
+    let mods = [
+      ModEntry { name: "sodium".to_string(), url: "https://example.com/sodium.jar".to_string(), sha1: sha1.clone(), size: 10 },
+      ModEntry { name: "com.example:lithium:0.11.2".to_string(), url: "https://example.com/lithium.jar".to_string(), sha1, size: 20 },
+    ];
+    let base = MCVersion::from("1.20.1".to_string());
+    let local_version = version_manager.create_modpack_version("my-pack", &base, &mods)?;
+    assert_eq!(local_version.get_inherits_from(), Some(&base));
+    let libraries = local_version.get_libraries();
+    assert_eq!(libraries[0].get_artifact_path(None), "modpack/my-pack/sodium/bdf48ef6/sodium-bdf48ef6.jar");
+    assert_eq!(libraries[1].get_artifact_path(None), "com/example/lithium/0.11.2/lithium-0.11.2.jar");
+    assert_eq!(libraries[1].downloads.as_ref().unwrap().artifact.size, 20);
+
+    version_manager.refresh_local()?;
+    assert!(version_manager.get_local_version(&MCVersion::from("my-pack".to_string())).is_some());
+
+    fs::remove_dir_all(game_dir)?;
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_get_library_usage_map() -> Result<(), Box<dyn std::error::Error>> {
+    let game_dir = temp_dir().join("mclc-test-library-usage");
+    let _ = fs::remove_dir_all(&game_dir);
+    let write_version = |id: &str, libraries: &[&str]| -> Result<(), Box<dyn std::error::Error>> {
+      let version_dir = game_dir.join("versions").join(id);
+      create_dir_all(&version_dir)?;
+      let libraries: Vec<_> = libraries
+        .iter()
+        .map(|name| serde_json::json!({ "name": name }))
+        .collect();
+      let json = serde_json::json!({
+        "id": id, "releaseTime": "2021-12-10T08:23:00+00:00", "time": "2021-12-10T08:23:00+00:00", "type": "release", "libraries": libraries
+      });
+      fs::write(version_dir.join(format!("{id}.json")), json.to_string())?;
+      Ok(())
+    };
+    write_version("1.18", &["org.apache.logging.log4j:log4j-core:2.14.1", "com.google.guava:guava:31.0.1-jre"])?;
+    write_version("1.18.1", &["org.apache.logging.log4j:log4j-core:2.17.0", "com.google.guava:guava:31.0.1-jre"])?;
+
+    let version_manager = VersionManager::new(game_dir.clone(), Box::new(TestFeatureMatcher));
+    version_manager.refresh_local()?;
+    let usage = version_manager.get_library_usage_map().await;
+    let artifact = |descriptor: &str| Artifact::try_from(descriptor.to_string()).unwrap();
+    assert_eq!(usage[&artifact("org.apache.logging.log4j:log4j-core:2.14.1")], [MCVersion::new("1.18")]);
+    assert_eq!(usage[&artifact("com.google.guava:guava:31.0.1-jre@jar")], [MCVersion::new("1.18"), MCVersion::new("1.18.1")]);
+
+    fs::remove_dir_all(game_dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_version_snapshot() -> Result<(), Box<dyn std::error::Error>> {
+    let game_dir = temp_dir().join("mclc-test-version-snapshot");
+    let _ = fs::remove_dir_all(&game_dir);
+    let version_dir = game_dir.join("versions").join("1.20.4");
+    create_dir_all(&version_dir)?;
+    // Unusual formatting has to survive the round trip
+    let version_json = r#"{"id":"1.20.4",  "releaseTime":"2023-12-07T12:56:20+00:00","time":"2023-12-07T12:56:20+00:00","type":"release"}"#;
+    fs::write(version_dir.join("1.20.4.json"), version_json)?;
+
+    let version_manager = VersionManager::new(game_dir.clone(), Box::new(TestFeatureMatcher));
+    let archive = game_dir.join("snapshots").join("1.20.4.json");
+    version_manager.save_version_snapshot(&MCVersion::new("1.20.4"), &archive)?;
+    assert!(version_manager.save_version_snapshot(&MCVersion::new("missing"), &archive).is_err());
+
+    fs::remove_dir_all(&version_dir)?;
+    let restored = version_manager.restore_version_snapshot(&archive)?;
+    assert_eq!(restored.get_id(), &MCVersion::new("1.20.4"));
+    assert_eq!(fs::read_to_string(version_dir.join("1.20.4.json"))?, version_json);
+    assert!(version_manager.get_local_version(&MCVersion::new("1.20.4")).is_some());
+
+    let mut tampered: VersionSnapshot = serde_json::from_slice(&fs::read(&archive)?)?;
+    tampered.version_json = tampered.version_json.replace("release", "snapshot");
+    fs::write(&archive, serde_json::to_vec(&tampered)?)?;
+    assert!(version_manager.restore_version_snapshot(&archive).is_err());
+
+    fs::remove_dir_all(game_dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_compare_libraries() {
+    let version = |libraries: &[&str]| -> LocalVersionInfo {
+      let libraries: Vec<_> = libraries
+        .iter()
+        .map(|name| serde_json::json!({ "name": name }))
+        .collect();
+      serde_json::from_value(
+        serde_json::json!({
+          "id": "1.18", "releaseTime": "2021-12-10T08:23:00+00:00", "time": "2021-12-10T08:23:00+00:00", "type": "release", "libraries": libraries
+        })
+      ).unwrap()
+    };
+    let artifact = |descriptor: &str| Artifact::try_from(descriptor.to_string()).unwrap();
+    let old = version(&["org.apache.logging.log4j:log4j-core:2.14.1", "com.google.guava:guava:31.0.1-jre", "oshi-project:oshi-core:1.1"]);
+    let new = version(&["org.apache.logging.log4j:log4j-core:2.17.0", "com.google.guava:guava:31.0.1-jre", "com.mojang:logging:1.0.0"]);
+
+    assert_eq!(VersionManager::compare_libraries(&old, &new), [
+      LibraryChange::Updated { from: artifact("org.apache.logging.log4j:log4j-core:2.14.1"), to: artifact("org.apache.logging.log4j:log4j-core:2.17.0") },
+      LibraryChange::Added(artifact("com.mojang:logging:1.0.0")),
+      LibraryChange::Removed(artifact("oshi-project:oshi-core:1.1")),
+    ]);
+    assert!(VersionManager::compare_libraries(&new, &new).is_empty());
+  }
+
+  #[tokio::test]
+  async fn test_get_compatible_versions() {
+    let version_manager = VersionManager::new(temp_dir(), Box::new(TestFeatureMatcher));
+    version_manager.remote_versions_cache
+      .lock()
+      .unwrap()
+      .extend([
+        remote_version("1.20.4", "release", "2023-12-07T12:56:20+00:00"),
+        remote_version("1.16.5", "release", "2021-01-14T16:05:32+00:00"),
+        remote_version("osx-only", "release", "2012-01-01T00:00:00+00:00"),
+      ]);
+    let osx_only = vec![serde_json::from_value(serde_json::json!({ "action": "allow", "os": { "name": "osx" } })).unwrap()];
+    version_manager.requirements_cache
+      .lock()
+      .unwrap()
+      .extend([
+        (MCVersion::new("1.20.4"), VersionRequirements { java_major_version: 17, compatibility_rules: vec![] }),
+        (MCVersion::new("1.16.5"), VersionRequirements { java_major_version: 8, compatibility_rules: vec![] }),
+        (MCVersion::new("osx-only"), VersionRequirements { java_major_version: 8, compatibility_rules: osx_only }),
+      ]);
+
+    let ids = |versions: Vec<RemoteVersionInfo>| versions.iter().map(|version| version.get_id().to_string()).collect::<Vec<_>>();
+    assert_eq!(ids(version_manager.get_compatible_versions(8, &OperatingSystem::Windows).await), ["1.16.5"]);
+    assert_eq!(ids(version_manager.get_compatible_versions(17, &OperatingSystem::Osx).await), ["1.20.4", "1.16.5", "osx-only"]);
+  }
+
+  #[test]
+  fn test_get_version_release_notes_url() {
+    let version_manager = VersionManager::new(temp_dir(), Box::new(TestFeatureMatcher));
+    version_manager.remote_versions_cache
+      .lock()
+      .unwrap()
+      .extend([
+        remote_version("1.20.4", "release", "2023-12-07T12:56:20+00:00"),
+        remote_version("23w51a", "snapshot", "2023-12-18T14:24:37+00:00"),
+        remote_version("b1.7.3", "old_beta", "2011-07-07T22:00:00+00:00"),
+      ]);
+
+    let url = |id: &str| version_manager.get_version_release_notes_url(&MCVersion::new(id)).map(|url| url.to_string());
+    assert_eq!(url("1.20.4").as_deref(), Some("https://www.minecraft.net/en-us/article/minecraft-java-edition-1-20-4"));
+    assert_eq!(url("23w51a").as_deref(), Some("https://www.minecraft.net/en-us/article/minecraft-snapshot-23w51a"));
+    assert_eq!(url("b1.7.3"), None);
+    assert_eq!(url("unknown"), None);
+  }
+
+  #[test]
+  fn test_get_latest_snapshot_for_release() {
+    let version_manager = VersionManager::new(temp_dir(), Box::new(TestFeatureMatcher));
+    let remote_versions = vec![
+      remote_version("1.21", "release", "2024-06-13T08:24:03+00:00"),
+      remote_version("1.21-rc1", "snapshot", "2024-06-10T12:23:45+00:00"),
+      remote_version("1.20.6", "release", "2024-04-29T14:05:22+00:00"),
+      remote_version("24w14a", "snapshot", "2024-04-03T14:10:04+00:00"),
+      remote_version("1.0", "release", "2011-11-17T22:00:00+00:00")
+    ];
+    version_manager.remote_versions_cache.lock().unwrap().extend(remote_versions);
+
+    let snapshot = version_manager.get_latest_snapshot_for_release(&MCVersion::new("1.21"));
+    assert_eq!(snapshot.map(|snapshot| snapshot.get_id().to_string()), Some("1.21-rc1".to_string()));
+    let snapshot = version_manager.get_latest_snapshot_for_release(&MCVersion::new("1.20.6"));
+    assert_eq!(snapshot.map(|snapshot| snapshot.get_id().to_string()), Some("24w14a".to_string()));
+    assert!(version_manager.get_latest_snapshot_for_release(&MCVersion::new("1.0")).is_none());
+    assert!(version_manager.get_latest_snapshot_for_release(&MCVersion::new("1.21-rc1")).is_none());
+  }
+
+  #[test]
+  fn test_memory_recommendation() -> Result<(), Box<dyn std::error::Error>> {
+    let recommendation = |release_time: &str| -> Result<MemoryRecommendation, serde_json::Error> {
+      Ok(MemoryRecommendation::for_release_time(&serde_json::from_value(serde_json::json!(release_time))?))
+    };
+    assert_eq!(recommendation("2023-12-07T12:56:20+00:00")?.recommended_mb, 2048);
+    assert_eq!(recommendation("2018-07-18T15:11:46+00:00")?.recommended_mb, 1024);
+    assert_eq!(recommendation("2015-12-09T15:00:00+00:00")?, MemoryRecommendation { min_mb: 256, recommended_mb: 512 });
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_batch_verify() -> Result<(), Box<dyn std::error::Error>> {
+    let game_dir = temp_dir().join("mclc-test-batch-verify");
+    let _ = fs::remove_dir_all(&game_dir);
+    let jar_sha1 = Sha1Sum::from_reader(&mut "jar".as_bytes())?;
+    let version_dir = game_dir.join("versions").join("1.20.4");
+    create_dir_all(&version_dir)?;
+    let version_json = serde_json::json!({
+      "id": "1.20.4",
+      "releaseTime": "2023-12-07T12:56:20+00:00",
+      "time": "2023-12-07T12:56:20+00:00",
+      "type": "release",
+      "libraries": [
+        { "name": "com.example:good:1.0", "downloads": { "artifact": { "url": "https://example.com", "sha1": jar_sha1, "size": 3 } } },
+        { "name": "com.example:corrupt:1.0", "downloads": { "artifact": { "url": "https://example.com", "sha1": jar_sha1, "size": 3 } } },
+        { "name": "com.example:missing:1.0" }
+      ]
+    });
+    fs::write(version_dir.join("1.20.4.json"), version_json.to_string())?;
+    let libraries_dir = game_dir.join("libraries").join("com").join("example");
+    for (name, content) in [("good", "jar"), ("corrupt", "rar")] {
+      let dir = libraries_dir.join(name).join("1.0");
+      create_dir_all(&dir)?;
+      fs::write(dir.join(format!("{name}-1.0.jar")), content)?;
+    }
+
+    let version_manager = VersionManager::new(game_dir.clone(), Box::new(TestFeatureMatcher));
+    version_manager.refresh_local()?;
+    let version_id = MCVersion::from("1.20.4".to_string());
+    let statuses = version_manager.batch_verify(std::slice::from_ref(&version_id)).await?;
+    assert_eq!(statuses[&version_id], VerificationStatus { missing_files: 1, corrupt_files: 1, total_checked: 3 });
+    assert!(version_manager.batch_verify(&[MCVersion::from("1.8.9".to_string())]).await.is_err());
+
+    fs::remove_dir_all(game_dir)?;
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_get_required_disk_space() -> Result<(), Box<dyn std::error::Error>> {
+    let game_dir = temp_dir().join("mclc-test-required-disk-space");
+    let _ = fs::remove_dir_all(&game_dir);
+    let sha1 = Sha1Sum::from_reader(&mut "jar".as_bytes())?;
+    let cached_hash = Sha1Sum::from_reader(&mut "png".as_bytes())?;
+    let missing_hash = Sha1Sum::from_reader(&mut "ogg".as_bytes())?;
+    let version_dir = game_dir.join("versions").join("1.20.4");
+    create_dir_all(&version_dir)?;
+    let version_json = serde_json::json!({
+      "id": "1.20.4",
+      "releaseTime": "2023-12-07T12:56:20+00:00",
+      "time": "2023-12-07T12:56:20+00:00",
+      "type": "release",
+      "downloads": { "client": { "url": "https://example.com", "sha1": sha1, "size": 1000 } },
+      "assetIndex": { "id": "12", "totalSize": 300, "url": "https://example.com", "sha1": sha1, "size": 20 },
+      "libraries": [
+        { "name": "com.example:cached:1.0", "downloads": { "artifact": { "url": "https://example.com", "sha1": sha1, "size": 3 } } },
+        { "name": "com.example:missing:1.0", "downloads": { "artifact": { "url": "https://example.com", "sha1": sha1, "size": 50 } } }
+      ]
+    });
+    fs::write(version_dir.join("1.20.4.json"), version_json.to_string())?;
+    let library_dir = game_dir.join("libraries").join("com").join("example").join("cached").join("1.0");
+    create_dir_all(&library_dir)?;
+    fs::write(library_dir.join("cached-1.0.jar"), "jar")?;
+
+    let assets_dir = VersionManager::get_assets_dir(&game_dir);
+    create_dir_all(assets_dir.join("indexes"))?;
+    let asset_index = serde_json::json!({
+      "objects": {
+        "icon.png": { "hash": cached_hash, "size": 100 },
+        "sound.ogg": { "hash": missing_hash, "size": 200 }
+      }
+    });
+    fs::write(assets_dir.join("indexes").join("12.json"), asset_index.to_string())?;
+    let object_path = get_asset_object_path(&assets_dir, &cached_hash);
+    create_dir_all(object_path.parent().unwrap())?;
+    fs::write(object_path, "png")?;
+
+    let version_manager = VersionManager::new(game_dir.clone(), Box::new(TestFeatureMatcher));
+    version_manager.refresh_local()?;
+    let estimate = version_manager.get_required_disk_space(&MCVersion::from("1.20.4".to_string()), &ProxyOptions::NoProxy, &HttpClientConfig::default()).await?;
+    assert_eq!(estimate, DiskSpaceEstimate {
+      libraries_bytes: 53,
+      client_jar_bytes: 1000,
+      assets_bytes: 320,
+      total_bytes: 1373,
+      already_cached_bytes: 3 + 20 + 100,
+    });
+
+    fs::remove_dir_all(game_dir)?;
+    Ok(())
+  }
+
+  // 1.20.4 with its jar, one library and one asset object installed, each file holds 3 bytes
+  fn install_test_version(name: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let game_dir = temp_dir().join(name);
+    let _ = fs::remove_dir_all(&game_dir);
+    let hash = Sha1Sum::from_reader(&mut "png".as_bytes())?;
+    let version_dir = game_dir.join("versions").join("1.20.4");
+    create_dir_all(&version_dir)?;
+    let version_json = serde_json::json!({
+      "id": "1.20.4",
+      "releaseTime": "2023-12-07T12:56:20+00:00",
+      "time": "2023-12-07T12:56:20+00:00",
+      "type": "release",
+      "assetIndex": { "id": "12", "totalSize": 3, "url": "https://example.com", "sha1": hash, "size": 20 },
+      "libraries": [
+        { "name": "com.example:lib:1.0", "downloads": { "artifact": { "url": "https://example.com", "sha1": hash, "size": 3 } } }
+      ]
+    });
+    fs::write(version_dir.join("1.20.4.json"), version_json.to_string())?;
+    fs::write(version_dir.join("1.20.4.jar"), "jar")?;
+    let library_dir = game_dir.join("libraries").join("com").join("example").join("lib").join("1.0");
+    create_dir_all(&library_dir)?;
+    fs::write(library_dir.join("lib-1.0.jar"), "lib")?;
+
+    let assets_dir = VersionManager::get_assets_dir(&game_dir);
+    create_dir_all(assets_dir.join("indexes"))?;
+    let asset_index = serde_json::json!({ "objects": { "icon.png": { "hash": hash, "size": 3 } } });
+    fs::write(assets_dir.join("indexes").join("12.json"), asset_index.to_string())?;
+    let object_path = get_asset_object_path(&assets_dir, &hash);
+    create_dir_all(object_path.parent().unwrap())?;
+    fs::write(object_path, "png")?;
+    Ok(game_dir)
+  }
+
+  #[test]
+  fn test_get_version_size_on_disk() -> Result<(), Box<dyn std::error::Error>> {
+    let game_dir = install_test_version("mclc-test-version-size")?;
+    let version_manager = VersionManager::new(game_dir.clone(), Box::new(TestFeatureMatcher));
+    version_manager.refresh_local()?;
+    let id = MCVersion::from("1.20.4".to_string());
+    let size = |include_libraries, include_assets| version_manager.get_version_size_on_disk(&id, VersionSizeOptions { include_assets, include_libraries });
+
+    let version_dir_size = fs::metadata(game_dir.join("versions").join("1.20.4").join("1.20.4.json"))?.len() + 3;
+    assert_eq!(size(false, false)?, version_dir_size);
+    assert_eq!(size(true, false)?, version_dir_size + 3);
+    assert_eq!(size(false, true)?, version_dir_size + 3);
+    assert_eq!(size(true, true)?, version_dir_size + 6);
+
+    // Missing files don't count
+    fs::remove_file(game_dir.join("libraries").join("com").join("example").join("lib").join("1.0").join("lib-1.0.jar"))?;
+    assert_eq!(size(true, false)?, version_dir_size);
+    assert!(version_manager.get_version_size_on_disk(&MCVersion::from("1.8.9".to_string()), VersionSizeOptions::default()).is_err());
+
+    fs::remove_dir_all(game_dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_compute_install_hash() -> Result<(), Box<dyn std::error::Error>> {
+    let id = MCVersion::from("1.20.4".to_string());
+
+    let first_dir = install_test_version("mclc-test-install-hash-1")?;
+    let second_dir = install_test_version("mclc-test-install-hash-2")?;
+    let first = VersionManager::new(first_dir.clone(), Box::new(TestFeatureMatcher));
+    first.refresh_local()?;
+    let second = VersionManager::new(second_dir.clone(), Box::new(TestFeatureMatcher));
+    second.refresh_local()?;
+    let hash = first.compute_install_hash(&id)?;
+    assert_eq!(hash.len(), 64);
+    assert_eq!(hash, second.compute_install_hash(&id)?);
+
+    fs::write(second_dir.join("libraries").join("com").join("example").join("lib").join("1.0").join("lib-1.0.jar"), "corrupted")?;
+    assert_ne!(hash, second.compute_install_hash(&id)?);
+    fs::remove_file(second_dir.join("versions").join("1.20.4").join("1.20.4.jar"))?;
+    assert!(second.compute_install_hash(&id).is_err());
+
+    fs::remove_dir_all(first_dir)?;
+    fs::remove_dir_all(second_dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_health_check() -> Result<(), Box<dyn std::error::Error>> {
+    let game_dir = temp_dir().join("mclc-test-health-check");
+    let _ = fs::remove_dir_all(&game_dir);
+    let version_dir = game_dir.join("versions").join("1.20.4");
+    create_dir_all(&version_dir)?;
+    let version_json = serde_json::json!({
+      "id": "1.20.4",
+      "releaseTime": "2023-12-07T12:56:20+00:00",
+      "time": "2023-12-07T12:56:20+00:00",
+      "type": "release"
+    });
+    fs::write(version_dir.join("1.20.4.json"), version_json.to_string())?;
+    fs::write(game_dir.join("versions").join("version_cache.json"), "{")?;
+    create_dir_all(game_dir.join("assets").join("indexes"))?;
+
+    let version_manager = VersionManager::new(game_dir.clone(), Box::new(TestFeatureMatcher));
+    let status = version_manager.health_check(&game_dir.join("java"))?;
+    assert!(!status.ok);
+    assert_eq!(status.warnings.len(), 2);
+    assert!(status.warnings[0].contains("version_cache.json"));
+    assert!(status.warnings[1].contains("objects"));
+    assert_eq!(status.errors, [format!("Java executable not found: {}", game_dir.join("java").display())]);
+
+    fs::remove_dir_all(game_dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_gc_old_natives() -> Result<(), Box<dyn std::error::Error>> {
+    let game_dir = temp_dir().join("mclc-test-gc-old-natives");
+    let _ = fs::remove_dir_all(&game_dir);
+    let version_dir = game_dir.join("versions").join("1.20.4");
+    create_dir_all(version_dir.join("1.20.4-natives-1"))?;
+    create_dir_all(version_dir.join("saves"))?;
+    fs::write(version_dir.join("1.20.4-natives-1").join("liblwjgl.so"), "native")?;
+    let version_json = serde_json::json!({
+      "id": "1.20.4",
+      "releaseTime": "2023-12-07T12:56:20+00:00",
+      "time": "2023-12-07T12:56:20+00:00",
+      "type": "release"
+    });
+    fs::write(version_dir.join("1.20.4.json"), version_json.to_string())?;
+
+    let version_manager = VersionManager::new(game_dir.clone(), Box::new(TestFeatureMatcher));
+    version_manager.refresh_local()?;
+    let report = version_manager.gc_old_natives(Duration::from_secs(3600))?;
+    assert_eq!(report.inspected, [version_dir.join("1.20.4-natives-1")]);
+    assert!(report.deleted.is_empty());
+
+    let report = version_manager.gc_old_natives(Duration::ZERO)?;
+    assert_eq!(report.deleted, [version_dir.join("1.20.4-natives-1")]);
+    assert_eq!(report.bytes_freed, 6);
+    assert!(!version_dir.join("1.20.4-natives-1").exists());
+    assert!(version_dir.join("saves").is_dir());
+
+    fs::remove_dir_all(game_dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_list_installed_with_mod_loaders() -> Result<(), Box<dyn std::error::Error>> {
+    let game_dir = temp_dir().join("mclc-test-list-installed");
+    let _ = fs::remove_dir_all(&game_dir);
+    let write_version = |id: &str, extra: Value| -> Result<(), Box<dyn std::error::Error>> {
+      let version_dir = game_dir.join("versions").join(id);
+      create_dir_all(&version_dir)?;
+      let mut json = serde_json::json!({
+        "id": id,
+        "releaseTime": "2023-12-07T12:56:20+00:00",
+        "time": "2023-12-07T12:56:20+00:00",
+        "type": "release"
+      });
+      json.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+      fs::write(version_dir.join(format!("{id}.json")), json.to_string())?;
+      Ok(())
+    };
+    write_version("1.20.4", serde_json::json!({}))?;
+    fs::write(game_dir.join("versions").join("1.20.4").join("1.20.4.jar"), "jar")?;
+    write_version(
+      "fabric-loader-0.15.3-1.20.4",
+      serde_json::json!({ "inheritsFrom": "1.20.4", "jar": "1.20.4", "libraries": [{ "name": "net.fabricmc:fabric-loader:0.15.3" }] })
+    )?;
+    write_version("my-pack", serde_json::json!({ "inheritsFrom": "fabric-loader-0.15.3-1.20.4" }))?;
+
+    let version_manager = VersionManager::new(game_dir.clone(), Box::new(TestFeatureMatcher));
+    version_manager.refresh_local()?;
+    let mut summaries = version_manager.list_installed_with_mod_loaders();
+    summaries.sort_by_key(|summary| summary.id.to_string());
+    let summary = |id: &str| summaries.iter().find(|summary| summary.id.to_string() == id).unwrap();
+    assert_eq!(summaries.len(), 3);
+    assert_eq!(summary("1.20.4").mod_loader, ModLoaderType::Vanilla);
+    assert_eq!(summary("1.20.4").base_version, None);
+    assert_eq!(summary("fabric-loader-0.15.3-1.20.4").mod_loader, ModLoaderType::Fabric);
+    assert_eq!(summary("fabric-loader-0.15.3-1.20.4").base_version, Some(MCVersion::new("1.20.4")));
+    assert_eq!(summary("my-pack").base_version, Some(MCVersion::new("1.20.4")));
+    assert_eq!(summary("1.20.4").installed_size_bytes, version_manager.get_total_installation_size(&MCVersion::new("1.20.4")));
+
+    fs::remove_dir_all(game_dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_get_asset_object_path() -> Result<(), Box<dyn std::error::Error>> {
+    let assets_dir = Path::new("assets");
+    let hash = Sha1Sum::try_from("0A0B48EF6B5D0D23BBB02E17D04865216179F510".to_string())?;
+    let path = get_asset_object_path(assets_dir, &hash);
+    assert_eq!(path, assets_dir.join("objects").join("0a").join("0a0b48ef6b5d0d23bbb02e17d04865216179f510"));
+
+    let hash = Sha1Sum::try_from("00000000000000000000000000000000000000ff".to_string())?;
+    assert_eq!(get_asset_object_path(assets_dir, &hash).parent().unwrap().file_name().unwrap(), "00");
+    Ok(())
+  }
+
+  #[test]
+  fn test_get_version_id_from_json() {
+    let versions_dir = Path::new("/mc/versions");
+    let version_id = |path: &str| VersionManager::get_version_id_from_json(versions_dir, Path::new(path));
+    assert_eq!(version_id("/mc/versions/1.20.4/1.20.4.json"), Some(MCVersion::from("1.20.4".to_string())));
+    assert_eq!(version_id("/mc/versions/1.20.4/1.20.4.jar"), None);
+    assert_eq!(version_id("/mc/versions/1.20.4/other.json"), None);
+    assert_eq!(version_id("/mc/versions/version_cache.json"), None);
+    assert_eq!(version_id("/mc/libraries/1.20.4/1.20.4.json"), None);
+  }
+
+  #[test]
+  fn test_migrate_old_game_dir() -> Result<(), Box<dyn std::error::Error>> {
+    let root = temp_dir().join("mclc-test-migration");
+    let _ = fs::remove_dir_all(&root);
+    let old_dir = root.join("old");
+    create_dir_all(old_dir.join("libraries").join("a"))?;
+    create_dir_all(old_dir.join("assets").join("objects"))?;
+    fs::write(old_dir.join("libraries").join("a").join("a.jar"), b"library")?;
+    fs::write(old_dir.join("assets").join("objects").join("b"), b"asset")?;
+    fs::write(old_dir.join("options.txt"), b"ignored")?;
+
+    let version_manager = VersionManager::new(root.join("new"), Box::new(TestFeatureMatcher));
+    let report = version_manager.migrate_old_game_dir(&old_dir)?;
+    assert_eq!((report.copied, report.skipped, report.failed), (2, 0, 0));
+    assert_eq!(fs::read(root.join("new").join("libraries").join("a").join("a.jar"))?, b"library");
+    assert!(!root.join("new").join("options.txt").exists());
+
+    let report = version_manager.migrate_old_game_dir(&old_dir)?;
+    assert_eq!((report.copied, report.skipped, report.failed), (0, 2, 0));
+
+    fs::remove_dir_all(&root)?;
+    Ok(())
+  }
+
+  // Proxy that records the first line of every request and refuses it
+  async fn recording_proxy() -> (ProxyOptions, Arc<Mutex<Vec<String>>>) {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let proxy = ProxyOptions::Proxy(Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap());
+    let requests = Arc::new(Mutex::new(vec![]));
+    let recorded = Arc::clone(&requests);
+    tokio::spawn(async move {
+      while let Ok((mut socket, _)) = listener.accept().await {
+        let mut request = [0; 1024];
+        let read = tokio::io::AsyncReadExt::read(&mut socket, &mut request).await.unwrap_or(0);
+        let request = String::from_utf8_lossy(&request[..read]).to_string();
+        recorded.lock().unwrap().push(request.lines().next().unwrap_or_default().to_string());
+        let _ = tokio::io::AsyncWriteExt::write_all(&mut socket, b"HTTP/1.1 502 Bad Gateway\r\nContent-Length: 0\r\n\r\n").await;
+      }
+    });
+    (proxy, requests)
+  }
+
+  #[tokio::test]
+  async fn test_remote_content_uses_proxy() {
+    let (proxy, requests) = recording_proxy().await;
+    let http_config = HttpClientConfig::default();
+    let version_manager = VersionManager::new(temp_dir().join("mclc-test-remote-proxy"), Box::new(TestFeatureMatcher));
+    assert!(version_manager.fetch_news(&proxy, &http_config).await.is_err());
+    assert!(version_manager.fetch_patch_notes(&proxy, &http_config).await.is_err());
+    assert!(version_manager.fetch_skin_and_cape(Uuid::nil(), &proxy, &http_config).await.is_err());
+    assert!(version_manager.snapshot_to_release_map(&proxy, &http_config).await.is_err());
+    assert_eq!(*requests.lock().unwrap(), [
+      "CONNECT launchercontent.mojang.com:443 HTTP/1.1",
+      "CONNECT launchercontent.mojang.com:443 HTTP/1.1",
+      "CONNECT sessionserver.mojang.com:443 HTTP/1.1",
+      "CONNECT piston-meta.mojang.com:443 HTTP/1.1",
+    ]);
+  }
+
+  // The proxy accepts the connection but never answers, only the configured timeout ends the request
+  #[tokio::test]
+  async fn test_remote_content_timeout() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let proxy = ProxyOptions::AuthenticatedProxy {
+      url: Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap(),
+      username: "steve".to_string(),
+      password: "hunter2".to_string(),
+    };
+    let http_config = HttpClientConfig { total_timeout: Some(Duration::from_millis(200)), ..HttpClientConfig::default() };
+    let version_manager = VersionManager::new(temp_dir().join("mclc-test-remote-timeout"), Box::new(TestFeatureMatcher));
+
+    let fetches = async {
+      assert!(version_manager.fetch_news(&proxy, &http_config).await.is_err());
+      assert!(version_manager.fetch_patch_notes(&proxy, &http_config).await.is_err());
+      assert!(version_manager.fetch_skin_and_cape(Uuid::nil(), &proxy, &http_config).await.is_err());
+    };
+    tokio::time::timeout(Duration::from_secs(10), fetches).await.unwrap();
+    drop(listener);
+  }
+
   #[tokio::test]
   async fn test_version_manager() -> Result<(), Box<dyn std::error::Error>> {
     SimpleLogger::new().init().unwrap();